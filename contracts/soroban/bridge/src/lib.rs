@@ -1,18 +1,146 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, contractclient, Address, Env, String, Vec,
-    symbol_short, log, Error as SorobanError,
+    contract, contracterror, contractimpl, contracttype, contractclient, Address, BytesN, Env, String, Vec,
+    symbol_short, log, IntoVal, TryFromVal, Val,
 };
+use soroban_sdk::xdr::ToXdr;
+use soroban_sdk::token::Client as TokenClient;
 
 // ============ DATA TYPES ============
 
+/// Storage keys of contract-wide and per-account entries
+/// 
+/// Placement rule: instance storage holds the small, admin-written
+/// configuration read by most calls (admin, config, pause flag, roles,
+/// collectors, limits, queued changes). Everything written by plan
+/// traffic (per-account data and the protocol-wide counters
+/// `PlanCounter`, `TotalOutstanding`, `InsuredOutstanding`, `RebatePool`,
+/// `Stats` and `EventSeq`) is persistent, one ledger entry per key.
 #[contracttype]
 #[derive(Clone)]
 pub enum DataKey {
-    Plan(String),           // Plan identified by plan_id
-    UserPlans(Address),     // List of plans for a user
+    UserPlans(Address, u32), // Bucket of a user's plan IDs
+    UserPlanCount(Address), // Number of plans a user has created
     PlanCounter,            // Counter to generate unique IDs
+    Admin,                  // Contract administrator
+    Config,                 // Deployment configuration (Buffer address...)
+    CollateralFactor(Address), // Required collateral (bps) per Buffer asset
+    Underwriting,           // Credit-score underwriting configuration
+    InsuredOutstanding,     // Shortfall currently underwritten by the fund
+    ReleaseDelay,           // Seconds between completion and collateral release
+    CrossMargin(Address),   // Whether new plans of a user use portfolio margin
+    CrossExposure(Address), // Outstanding amount of a user's cross-margin plans
+    RebateConfig,           // Token and rate of collateral rebates
+    RebatePool,             // Rebate tokens available for claims
+    Reserve(Address),       // Buffer floor (asset amount) collections keep in Available
+    Paused,                 // Emergency stop for plan creation and collection
+    Version,                // Storage layout version, advanced by migrate
+    PlanVersion,            // Layout of stored plan records, advanced by migrate_plans
+    PlanMigrationCursor,    // Next plan index migrate_plans will upgrade
+    Buffer,                 // Legacy (v0) Buffer address, moved into Config by migrate
+    Collector(Address),     // Worker allowed to collect due installments
+    Merchant(Address),      // Merchant profile and onboarding status
+    Role(Role, Address),    // Role granted to an account
+    PendingAdmin,           // Proposed admin awaiting acceptance
+    TimelockDelay,          // Seconds a parameter change waits before execution
+    ChangeCounter,          // Counter to generate parameter change IDs
+    PendingChange(u32),     // Queued parameter change
+    Blacklisted(Address),   // Address barred from new plans and settlements
+    MerchantCap(Address),   // Max outstanding amount a merchant may originate
+    MerchantExposure(Address), // Outstanding amount of a merchant's plans
+    UserLimit(Address),     // Max outstanding amount across a user's plans
+    UserExposure(Address),  // Outstanding amount of a user's plans
+    RateLimit,              // Plan creation throttle
+    CreationWindow(Address), // Plans a user created in the current window
+    GlobalCap,              // Protocol-wide outstanding cap
+    TotalOutstanding,       // Outstanding amount across all plans
+    ApprovalThreshold,      // Plan amount above which co-approval is required
+    DueBucket(u64),         // Pending installments due on a given day
+    DueDays,                // Sorted days that have a non-empty DueBucket
+    Stats,                  // Protocol-level counters
+    MerchantDefaulted(Address), // Unpaid amount of a merchant's defaulted plans
+    CreditHistory(Address), // Repayment counters of a user
+    Settlements(Address, u32), // Page of a merchant's settlement history
+    SettlementCount(Address), // Number of settlements paid to a merchant
+    ReadModel,              // Projection contract notified of plan transitions
+    CleanupBounty,          // Reward per storage entry reclaimed by cleanup
+    CleanupCursor,          // Next parameter change ID cleanup inspects
+    EventSeq,               // Sequence number of the last published event
+}
+
+/// Storage keys of per-plan entries
+/// 
+/// Split from `DataKey`, which is at the contract spec's 50-variant limit.
+/// Variants serialize by name, so moving one here keeps its storage layout.
+#[contracttype]
+#[derive(Clone)]
+pub enum PlanKey {
+    Plan(u64),              // Plan header identified by plan_id
+    Installment(u64, u32),  // Installment of a plan by number (1-based)
+    Auction(u64),           // Collateral auction of a defaulted plan
+    Frozen(u64),            // Plan frozen during an investigation
+    PlanFees(u64),          // Protocol fees withheld from a plan's collections
+    PlanHistory(u64, u32),  // Page of a plan's audit trail
+    PlanHistoryLen(u64),    // Number of audit trail entries of a plan
+    ClosedAt(u64),          // When a plan was completed or liquidated
+    ArchivedPlan(u64),      // Header kept after a closed plan is archived
+    ByIndex(u64),           // Creation index of a plan with a derived ID
+    DueDates(u64),          // Due dates of a plan with an irregular schedule
+    AnchorRef(u64, u32),    // Anchor transaction of a settlement (installment 0 = auction)
+    Refunded(u64),          // Plan whose refund was already recorded
+    MerchantSlot(u64),      // Position of a plan in its merchant's index
+    StatusSlot(u64),        // Position of a plan in the index of its status
+}
+
+/// Storage keys of the recent transitions feed
+/// 
+/// Split from `DataKey` for the same reason as `PlanKey`.
+#[contracttype]
+#[derive(Clone)]
+pub enum FeedKey {
+    TransitionCount,        // Number of transitions recorded so far
+    Transition(u32),        // Slot of the ring buffer (number % RECENT_TRANSITIONS)
+}
+
+/// Storage keys of per-account entries (users and merchants) added after
+/// `DataKey` filled up
+/// 
+/// Split from `DataKey` for the same reason as `PlanKey`.
+#[contracttype]
+#[derive(Clone)]
+pub enum UserKey {
+    Recovery(Address),      // Progress of a user towards forgiving their defaults
+    MerchantIncidents(Address), // Refunds, disputes and defaults on a merchant's plans
+    CrossLocked(Address),   // Protected shares locked for a user's cross-margin plans
+    MerchantDisputed(Address), // Unpaid amount of a merchant's frozen plans
+}
+
+/// Storage keys of the bucketed merchant and status indexes
+/// 
+/// Split from `DataKey` for the same reason as `PlanKey`.
+#[contracttype]
+#[derive(Clone)]
+pub enum IndexKey {
+    MerchantPlans(Address, u32), // Bucket of the plans where a merchant is the payee
+    MerchantPlanCount(Address), // Number of plans where a merchant is the payee
+    StatusPlans(PlanStatus, u32), // Bucket of the plans currently in a status
+    StatusPlanCount(PlanStatus), // Number of plans currently in a status
+}
+
+/// Keys of plan layout 0 (string plan IDs), read by `migrate_plans`
+#[contracttype]
+#[derive(Clone)]
+pub enum PlanKeyV0 {
+    Plan(String),
+    UserPlans(Address),
+    Auction(String),
+    Frozen(String),
+    PlanFees(String),
+    PlanHistory(String, u32),
+    PlanHistoryLen(String),
+    MerchantPlans(Address),
+    StatusPlans(PlanStatus),
 }
 
 #[contracttype]
@@ -21,6 +149,8 @@ pub enum PlanStatus {
     Active,      // Active plan with pending installments
     Completed,   // Plan completed - all installments paid
     Defaulted,   // Plan in default - some installment failed
+    Liquidated,  // Defaulted plan whose collateral was auctioned
+    PendingApproval, // Large plan awaiting risk officer co-approval
 }
 
 #[contracttype]
@@ -29,6 +159,45 @@ pub enum InstallmentStatus {
     Pending,  // Installment pending payment
     Paid,     // Installment paid successfully
     Failed,   // Installment failed due to lack of funds
+    Overdue,  // Past due and still pending, flagged by a keeper
+}
+
+impl InstallmentStatus {
+    /// Whether the installment is still to be collected
+    pub fn is_open(&self) -> bool {
+        matches!(self, InstallmentStatus::Pending | InstallmentStatus::Overdue)
+    }
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PlanAction {
+    Created,    // Plan created by the user
+    Approved,   // Large plan co-approved by a risk officer
+    Collected,  // Installment collected
+    Failed,     // Installment declared failed
+    Defaulted,  // Plan went into default
+    Completed,  // Every installment paid
+    Liquidated, // Collateral auction settled
+    Archived,   // Installments pruned after the retention period
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Role {
+    Admin,      // Code upgrades, roles and deployment config (the stored admin)
+    Operator,   // Pausing, collectors and merchant onboarding
+    Treasurer,  // Collateral factors, underwriting and rebates
+    Arbiter,    // Dispute window and plan freezes
+    RiskOfficer, // Co-approval of large plans
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MerchantStatus {
+    Pending,    // Registered, awaiting admin approval
+    Approved,   // May receive new plans
+    Suspended,  // Approval withdrawn by the admin
 }
 
 // ============================================================
@@ -60,6 +229,15 @@ impl PaymentSource {
     }
 }
 
+/// Outcome of the checks run before an installment is collected
+struct Collection {
+    installment: Installment,
+    config: Config,
+    shares_needed: i128,
+    source: PaymentSource,
+    payout: Address,
+}
+
 #[contracttype]
 #[derive(Clone)]
 pub struct Installment {
@@ -67,677 +245,10228 @@ pub struct Installment {
     pub amount: i128,
     pub due_date: u64,
     pub paid_at: Option<u64>,
-    pub payment_source: Option<PaymentSource>,
+    pub payment_source: Option<u32>, // PaymentSource::to_u32() once paid
+    pub status: InstallmentStatus,
+}
+
+/// Stored form of an `Installment`
+/// 
+/// The number is part of the key and the due date comes from the plan's
+/// schedule, so neither is repeated in every record.
+#[contracttype]
+#[derive(Clone)]
+pub struct InstallmentRecord {
+    pub amount: i128,
+    pub paid_at: Option<u64>,
+    pub payment_source: Option<u32>, // PaymentSource::to_u32() once paid
     pub status: InstallmentStatus,
 }
 
 #[contracttype]
 #[derive(Clone)]
 pub struct BridgePlan {
-    pub plan_id: String,             // Unique plan ID
+    pub plan_id: u64,                // Unique plan ID
     pub user: Address,               // User who created the plan
     pub merchant: Address,           // Merchant who receives payments
     pub total_amount: i128,          // Total plan amount in tokens
     pub total_shares: i128,          // Total shares locked as collateral
     pub installments_count: u32,     // Number of installments
+    pub first_due: u64,              // Due date of the first installment
+    pub interval: u64,               // Seconds between due dates (0 = irregular, see DueDates)
     pub installments: Vec<Installment>, // List of plan installments
     pub protected_shares: i128,      // Shares currently protected (decreasing)
     pub status: PlanStatus,          // Current plan status
+    pub paid_bitmap: u64,            // Bit n-1 set once installment n is paid
+    pub failed_bitmap: u64,          // Bit n-1 set once installment n failed
     pub created_at: u64,             // Creation timestamp
+    pub insured_amount: i128,        // Part of the plan not backed by locked shares
+    pub release_at: u64,             // When remaining collateral can be released (0 = none pending)
+    pub cross_margin: bool,          // Backed by the user's shared protected balance
+    pub rebate_accrued: i128,        // Collateral rebate earned so far
+    pub rebate_checkpoint: u64,      // Last time the rebate was accrued
 }
 
-// ============ BUFFER CONTRACT INTERFACE ============
+/// `BridgePlan` without the installments vector
+/// 
+/// This is what a plan is stored as; each installment is a separate entry,
+/// so a collection only rewrites the header and one installment.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanHeader {
+    pub plan_id: u64,                // Unique plan ID
+    pub user: Address,               // User who created the plan
+    pub merchant: Address,           // Merchant who receives payments
+    pub total_amount: i128,          // Total plan amount in tokens
+    pub total_shares: i128,          // Total shares locked as collateral
+    pub installments_count: u32,     // Number of installments
+    pub first_due: u64,              // Due date of the first installment
+    pub interval: u64,               // Seconds between due dates (0 = irregular, see DueDates)
+    pub protected_shares: i128,      // Shares currently protected (decreasing)
+    pub status: PlanStatus,          // Current plan status
+    pub paid_bitmap: u64,            // Bit n-1 set once installment n is paid
+    pub failed_bitmap: u64,          // Bit n-1 set once installment n failed
+    pub created_at: u64,             // Creation timestamp
+    pub insured_amount: i128,        // Part of the plan not backed by locked shares
+    pub release_at: u64,             // When remaining collateral can be released (0 = none pending)
+    pub cross_margin: bool,          // Backed by the user's shared protected balance
+    pub rebate_accrued: i128,        // Collateral rebate earned so far
+    pub rebate_checkpoint: u64,      // Last time the rebate was accrued
+}
+
+impl From<BridgePlan> for PlanHeader {
+    fn from(plan: BridgePlan) -> Self {
+        PlanHeader {
+            plan_id: plan.plan_id,
+            user: plan.user,
+            merchant: plan.merchant,
+            total_amount: plan.total_amount,
+            total_shares: plan.total_shares,
+            installments_count: plan.installments_count,
+            first_due: plan.first_due,
+            interval: plan.interval,
+            protected_shares: plan.protected_shares,
+            status: plan.status,
+            paid_bitmap: plan.paid_bitmap,
+            failed_bitmap: plan.failed_bitmap,
+            created_at: plan.created_at,
+            insured_amount: plan.insured_amount,
+            release_at: plan.release_at,
+            cross_margin: plan.cross_margin,
+            rebate_accrued: plan.rebate_accrued,
+            rebate_checkpoint: plan.rebate_checkpoint,
+        }
+    }
+}
+
+impl PlanHeader {
+    fn bit(number: u32) -> u64 {
+        1u64 << (number - 1)
+    }
+    
+    /// Whether installment `number` is neither paid nor failed
+    fn is_pending(&self, number: u32) -> bool {
+        (self.paid_bitmap | self.failed_bitmap) & Self::bit(number) == 0
+    }
+    
+    fn is_paid(&self, number: u32) -> bool {
+        self.paid_bitmap & Self::bit(number) != 0
+    }
+    
+    fn is_failed(&self, number: u32) -> bool {
+        self.failed_bitmap & Self::bit(number) != 0
+    }
+    
+    /// Installments paid so far, without reading any of them
+    fn paid_count(&self) -> u32 {
+        self.paid_bitmap.count_ones()
+    }
+    
+    fn all_paid(&self) -> bool {
+        self.paid_count() == self.installments_count
+    }
+    
+    fn mark_paid(&mut self, number: u32) {
+        self.paid_bitmap |= Self::bit(number);
+    }
+    
+    fn mark_failed(&mut self, number: u32) {
+        self.failed_bitmap |= Self::bit(number);
+    }
+    
+    fn with_installments(self, installments: Vec<Installment>) -> BridgePlan {
+        BridgePlan {
+            plan_id: self.plan_id,
+            user: self.user,
+            merchant: self.merchant,
+            total_amount: self.total_amount,
+            total_shares: self.total_shares,
+            installments_count: self.installments_count,
+            first_due: self.first_due,
+            interval: self.interval,
+            installments,
+            protected_shares: self.protected_shares,
+            status: self.status,
+            paid_bitmap: self.paid_bitmap,
+            failed_bitmap: self.failed_bitmap,
+            created_at: self.created_at,
+            insured_amount: self.insured_amount,
+            release_at: self.release_at,
+            cross_margin: self.cross_margin,
+            rebate_accrued: self.rebate_accrued,
+            rebate_checkpoint: self.rebate_checkpoint,
+        }
+    }
+}
 
 #[contracttype]
-#[derive(Clone)]
-pub struct BufferBalance {
-    pub available_shares: i128,    // Shares available for use
-    pub protected_shares: i128,    // Shares locked as collateral
-    pub total_deposited: i128,     // Total deposited historically
-    pub last_deposit_ts: u64,      // Timestamp of last deposit
-    pub version: u64,              // Balance version
+#[derive(Clone, Debug, PartialEq)]
+pub struct MerchantProfile {
+    pub name_hash: BytesN<32>,       // Hash of the merchant's legal name
+    pub payout: Address,             // Address receiving collected payments
+    pub fee_tier: u32,               // Fee tier assigned at onboarding
+    pub status: MerchantStatus,      // Pending | Approved | Suspended
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RoutingPolicy {
+    AvailableFirst, // Debit available shares, fall back to protected
+    ProtectedFirst, // Debit the locked collateral, fall back to available
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub buffer: Address,             // Canonical Buffer Contract address
+    pub compliance: Option<Address>, // Optional eligibility (KYC/allowlist) contract
+    pub max_installments: u32,       // Most installments a plan may have
+    pub max_ltv_bps: i128,           // Max exposure over total Buffer value
+    pub liquidation_threshold_bps: i128, // Health factor threshold for at-risk plans
+    pub routing: RoutingPolicy,      // Order collections debit the Buffer in
+    pub grace_period_secs: u64,      // Time after due date before a default
+    pub fee_bps: i128,               // Protocol fee withheld from each collection
+    pub fee_recipient: Option<Address>, // Receives the protocol fee
+    pub min_due_spacing_secs: u64,   // Least time between consecutive due dates (0 = any)
+    pub min_installment_amount: i128, // Smallest installment a plan may have (0 = any)
+    pub sequential_collection: bool, // Collect a plan's installments in number order
+    pub max_horizon_secs: u64,       // Latest last due date after creation (0 = any)
+    pub due_tolerance_secs: u64,     // How early a due installment may be collected
+    pub credit_curve: CreditCurve,   // History-based exposure limit (off by default)
+    pub collateral_tiers: Vec<CollateralTier>, // Score bands to collateral ratios (empty = 100%)
 }
 
+impl Config {
+    /// Default parameters for a deployment using `buffer`
+    pub fn new(buffer: Address) -> Self {
+        Config {
+            compliance: None,
+            max_installments: DEFAULT_MAX_INSTALLMENTS,
+            max_ltv_bps: DEFAULT_MAX_LTV_BPS,
+            liquidation_threshold_bps: DEFAULT_LIQUIDATION_THRESHOLD_BPS,
+            routing: RoutingPolicy::AvailableFirst,
+            grace_period_secs: 0,
+            fee_bps: 0,
+            fee_recipient: None,
+            min_due_spacing_secs: 0,
+            min_installment_amount: 0,
+            sequential_collection: true,
+            max_horizon_secs: 0,
+            due_tolerance_secs: 0,
+            credit_curve: CreditCurve::default(),
+            collateral_tiers: Vec::new(buffer.env()),
+            buffer,
+        }
+    }
+}
+
+/// Config layout of storage version 1, read by `migrate`
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigV1 {
+    pub buffer: Address,
+}
+
+/// Config layout of storage version 2, read by `migrate`
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigV2 {
+    pub buffer: Address,
+    pub compliance: Option<Address>,
+}
+
+/// Config layout of storage versions 3 and 4, read by `migrate`
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigV4 {
+    pub buffer: Address,
+    pub compliance: Option<Address>,
+    pub max_installments: u32,
+    pub max_ltv_bps: i128,
+    pub liquidation_threshold_bps: i128,
+    pub routing: RoutingPolicy,
+    pub grace_period_secs: u64,
+    pub fee_bps: i128,
+    pub fee_recipient: Option<Address>,
+}
+
+/// Plan record of plan layout 0 (string ID, inline installments), read by
+/// `migrate_plans`
 #[contracttype]
 #[derive(Clone)]
-pub struct LockResult {
-    pub shares_locked: i128,       // Amount of shares that were locked
-    pub new_available: i128,       // New balance of available shares
-    pub new_protected: i128,       // New balance of protected shares
+pub struct BridgePlanV0 {
+    pub plan_id: String,
+    pub user: Address,
+    pub merchant: Address,
+    pub total_amount: i128,
+    pub total_shares: i128,
+    pub installments_count: u32,
+    pub installments: Vec<Installment>,
+    pub protected_shares: i128,
+    pub status: PlanStatus,
+    pub created_at: u64,
+    pub insured_amount: i128,
+    pub release_at: u64,
+    pub cross_margin: bool,
+    pub rebate_accrued: i128,
+    pub rebate_checkpoint: u64,
+}
+
+/// Auction of plan layout 0, read by `migrate_plans`
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuctionV0 {
+    pub plan_id: String,
+    pub debt_token: Address,
+    pub collateral_shares: i128,
+    pub debt_amount: i128,
+    pub start_price: i128,
+    pub floor_price: i128,
+    pub started_at: u64,
+    pub duration: u64,
+    pub settled: bool,
+}
+
+/// Settlement of plan layout 0, read by `migrate_plans`
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SettlementV0 {
+    pub plan_id: String,
+    pub installment_number: u32,
+    pub amount: i128,
+    pub fee: i128,
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+#[allow(clippy::large_enum_variant)] // Contract types can't box, and changes are only ever stored
+pub enum ParamChange {
+    CollateralFactor(Address, i128), // Collateral factor (bps) of a Buffer asset
+    Underwriting(UnderwritingConfig), // Credit-score underwriting
+    ReleaseDelay(u64),               // Dispute window before collateral release
+    Rebate(RebateConfig),            // Collateral rebate token and rate
+    Config(Config),                  // Deployment configuration
+    TimelockDelay(u64),              // Delay applied to later changes
+    Fee(i128, Option<Address>),      // Protocol fee (bps) and its recipient
+    RiskParams(i128, i128),          // Max LTV and liquidation threshold (bps)
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingChange {
+    pub change: ParamChange,         // Parameter update to apply
+    pub eta: u64,                    // Earliest execution time
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    pub max_plans: u32,              // Plans a user may create per window
+    pub window_secs: u64,            // Window length (e.g. 86400 = 24h)
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreationWindow {
+    pub started_at: u64,             // Start of the user's current window
+    pub count: u32,                  // Plans created since then
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DueInstallment {
+    pub plan_id: u64,                // Plan the installment belongs to
+    pub installment_number: u32,     // Installment number (1-based)
+    pub amount: i128,                // Installment amount
+    pub due_date: u64,               // Payment due date
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Utilization {
+    pub outstanding: i128,           // Outstanding amount across all plans
+    pub cap: Option<i128>,           // Protocol-wide cap, None when uncapped
+    pub utilization_bps: Option<i128>, // outstanding / cap in basis points
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CleanupReport {
+    pub reclaimed: u32,              // Proposals purged, plans archived and index entries dropped
+    pub bounty: i128,                // Rebate tokens paid to the caller
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RebateConfig {
+    pub token: Address,              // Token rebates are paid in
+    pub rate_bps: i128,              // Annual rate on locked shares in bps
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnderwritingConfig {
+    pub score_source: Address,       // Contract exposing get_score(user)
+    pub insurance_fund: Address,     // Fund absorbing the uncollateralized share
+    pub min_score: u32,              // Score required for reduced collateral
+    pub lock_ratio_bps: i128,        // Share of the plan that must still be locked
+    pub fund_capacity: i128,         // Max total shortfall the fund underwrites
+}
+
+/// Curve turning a user's repayment history into a plan size limit
+#[contracttype]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CreditCurve {
+    pub base_limit: i128,            // Limit of a user with no history
+    pub volume_bps: i128,            // Share of the amount repaid so far added to it
+    pub tenure_amount: i128,         // Added per full tenure period since the first plan
+    pub tenure_period_secs: u64,     // Length of a tenure period (0 = tenure ignored)
+    pub default_penalty_bps: i128,   // Cut per default, applied to the whole limit
+    pub max_limit: i128,             // Ceiling of the limit (0 = curve off)
+}
+
+/// Band of the collateral tier table, from `min_score` up to the next band
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollateralTier {
+    pub min_score: u32,              // Lowest credit score in the band
+    pub ratio_bps: i128,             // Share of a plan's collateral the user locks
 }
 
 #[contracttype]
 #[derive(Clone)]
-pub struct WithdrawResult {
-    pub shares_burned: i128,            // Shares burned in the operation
-    pub amounts_received: Vec<i128>,    // Amounts received per asset
-    pub new_available_balance: i128,    // New available balance
-    pub from_protected: bool,           // Whether debited from protected
+pub struct PlanHealth {
+    pub outstanding_amount: i128,    // Unpaid installments in tokens
+    pub locked_shares: i128,         // Shares still protected for this plan
+    pub locked_value: i128,          // Token value of the locked shares
+    pub collateral_value: i128,      // Total Buffer value backing the plan
+    pub ltv_bps: i128,               // outstanding / collateral in bps
+    pub health_factor_bps: i128,     // collateral * threshold / outstanding in bps
+    pub at_risk: bool,               // Health factor below 10000 (= 1.0)
 }
 
-// Client to call Buffer Contract functions
-#[contractclient(name = "BufferContractClient")]
-pub trait BufferContract {
-    // Get user balance
-    fn get_balance(env: Env, user: Address) -> BufferBalance;
-    
-    // Lock shares as collateral
-    fn lock_shares(env: Env, user: Address, shares: i128) -> LockResult;
-    
-    // Unlock shares (release collateral)
-    fn unlock_shares(env: Env, user: Address, shares: i128) -> LockResult;
-    
-    // Debit from available shares
-    fn debit_available(env: Env, user: Address, shares: i128, to: Address) -> WithdrawResult;
-    
-    // Debit from protected shares (fallback)
-    fn debit_protected(env: Env, user: Address, shares: i128, to: Address) -> WithdrawResult;
-    
-    // Get values in tokens (available, protected, total)
-    fn get_values(env: Env, user: Address) -> (i128, i128, i128);
-    
-    // Calculate shares needed for a token amount
-    fn shares_for_amount(env: Env, amount: i128) -> i128;
+#[contracttype]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProtocolStats {
+    pub volume_originated: i128,     // Total amount of all plans created
+    pub volume_collected: i128,      // Total amount of installments collected
+    pub active_plans: u32,           // Plans currently active
+    pub completed_plans: u32,        // Plans fully repaid
+    pub defaulted_plans: u32,        // Plans that went into default
 }
 
-// ============ COLLATERALIZATION CONSTANTS ============
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub action: PlanAction,          // State transition
+    pub installment_number: u32,     // Installment involved (0 = whole plan)
+    pub actor: Option<Address>,      // Who triggered it (None = permissionless)
+    pub timestamp: u64,              // Ledger time of the transition
+}
 
-/// Maximum Loan-to-Value ratio in basis points (10000 = 100%)
-/// 8000 = 80% - Plan can use up to 80% of total Buffer value
-const MAX_LTV_BPS: i128 = 8000;
+/// Checkpoint of the counters an indexer follows, read together
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexCursor {
+    pub plan_count: u64,             // Plans ever created (`get_plan_count`)
+    pub event_seq: u64,              // Sequence number of the last published event
+    pub transition_count: u64,       // Transitions recorded (`get_transition_count`)
+    pub ledger: u32,                 // Ledger the checkpoint was read at
+    pub timestamp: u64,              // Ledger close time
+}
 
-/// Liquidation threshold in basis points (for future alerts)
-/// 8500 = 85% - Point where risk should be alerted
-const LIQUIDATION_THRESHOLD_BPS: i128 = 8500;
+/// Outcome of `check_invariants` for one user
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvariantReport {
+    pub user: Address,               // User whose plans were checked
+    pub plans_checked: u32,          // Live plans inspected (archived ones hold nothing)
+    pub required_shares: i128,       // Shares the user's plans still need locked
+    pub locked_shares: i128,         // Protected shares the Buffer holds for the user
+    pub locks_balanced: bool,        // required_shares == locked_shares
+    pub unbalanced_plans: Vec<u64>,  // Plans whose installments don't add up to their total
+    pub ok: bool,                    // Every invariant holds
+    pub checked_at: u64,             // Ledger time of the check
+}
 
-// ============ ERRORS ============
+/// Entry of the recent transitions feed
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition {
+    pub number: u64,                 // Position in the feed, from 0 (cursor)
+    pub plan_id: u64,
+    pub action: PlanAction,
+    pub installment_number: u32,     // Installment involved (0 = whole plan)
+    pub actor: Option<Address>,      // Who triggered it (None = permissionless)
+    pub timestamp: u64,
+}
 
 #[contracttype]
-#[derive(Clone, Copy, Debug, PartialEq)]
-#[repr(u32)]
-pub enum ContractError {
-    InvalidAmount = 1,           // Invalid or negative amount
-    InvalidInstallments = 2,     // Invalid installment quantity (0 or >12)
-    InsufficientCollateral = 3,  // Total buffer less than requested amount
-    InsufficientAvailable = 4,   // Insufficient available buffer to lock
-    DatesMismatch = 5,           // Number of dates does not match installments
-    InvalidDueDate = 6,          // Due date in the past
-    PlanNotFound = 7,            // Plan not found in storage
-    InstallmentNotFound = 8,     // Installment not found in plan
-    AlreadyPaid = 9,             // Installment already paid
-    NotDueYet = 10,              // Installment not yet due
-    InsufficientFunds = 11,      // Insufficient funds to pay installment
-    TooManyInstallments = 12,    // More than 12 installments requested
-    BufferContractError = 13,    // Error calling Buffer Contract
-    InvalidShares = 14,          // Invalid shares calculation
-    ExceedsMaxLTV = 15,          // Plan exceeds maximum Loan-to-Value ratio
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CreditHistory {
+    pub plans_completed: u32,        // Plans fully repaid
+    pub installments_paid: u32,      // Installments collected
+    pub on_time_payments: u32,       // Of which collected by the grace deadline
+    pub total_repaid: i128,          // Amount collected across all plans
+    pub defaults: u32,               // Plans that went into default
 }
 
-// Conversion of our error to SorobanError
-impl From<ContractError> for SorobanError {
-    fn from(e: ContractError) -> Self {
-        SorobanError::from_contract_error(e as u32)
-    }
+/// Stored apart from `CreditHistory`, so existing histories still decode
+#[contracttype]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recovery {
+    pub on_time_streak: u32,         // On-time installments since the last late one or default
+    pub forgiven_defaults: u32,      // Defaults no longer counted against the user
 }
 
-impl From<&ContractError> for SorobanError {
-    fn from(e: &ContractError) -> Self {
-        SorobanError::from_contract_error(*e as u32)
-    }
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserCredit {
+    pub history: CreditHistory,      // Repayment counters
+    pub on_time_bps: i128,           // on_time_payments / installments_paid in bps
+    pub outstanding: i128,           // Current outstanding obligations
+    pub score: u32,                  // On-chain credit score (see get_credit_score)
 }
 
-// Conversion of SorobanError to our error (catch-all)
-impl From<SorobanError> for ContractError {
-    fn from(_: SorobanError) -> Self {
-        ContractError::BufferContractError
-    }
+/// What a user can do to improve their score, from `get_credit_standing`
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreditStanding {
+    pub score: u32,                  // Current credit score
+    pub outstanding_defaults: u32,   // Defaults still counted against the user
+    pub on_time_streak: u32,         // Consecutive on-time installments so far
+    pub payments_to_recover: u32,    // On-time installments until a default is forgiven (0 = none to forgive)
+    pub score_after_on_time: u32,    // Score after one more on-time installment
+    pub score_after_late: u32,       // Score after one late installment
+    pub score_after_recovery: u32,   // Score once the next default is forgiven
 }
 
-// ============ MAIN CONTRACT ============
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settlement {
+    pub plan_id: u64,                // Plan the payment belongs to
+    pub installment_number: u32,     // Installment covered (0 = auction proceeds)
+    pub amount: i128,                // Amount paid to the merchant's payout address
+    pub fee: i128,                   // Protocol fee withheld from it
+    pub timestamp: u64,              // Ledger time of the payment
+}
 
-#[contract]
-pub struct BridgeContract;
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MerchantReceivables {
+    pub expected: i128,              // Pending installments of active plans
+    pub in_default: i128,            // Unpaid amount held until auctions settle
+    pub outstanding: i128,           // expected + in_default
+    pub disputed: i128,              // Part of outstanding on frozen plans
+}
 
-#[contractimpl]
-impl BridgeContract {
-    
-    /// Create an installment plan
-    /// 
-    /// Creates a new installment financing plan, locking Buffer shares
-    /// as collateral. Validates that the user has sufficient collateral
-    /// and locks the necessary shares.
-    pub fn create_plan(
-        env: Env,
-        user: Address,               // User who creates the plan
-        merchant: Address,           // Merchant who will receive payments
-        total_amount: i128,          // Total amount to finance
-        installments_count: u32,     // Number of installments (1-12)
-        due_dates: Vec<u64>,         // Due dates of each installment
-        buffer_contract: Address,    // Address of Buffer Contract
-    ) -> Result<String, ContractError> {
-        
-        // Verify that user signed the transaction
-        user.require_auth();
-        
-        // ===== BASIC VALIDATIONS =====
-        
-        if total_amount <= 0 {
-            log!(&env, "Error: Invalid amount {}", total_amount);
-            return Err(ContractError::InvalidAmount);
+#[contracttype]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MerchantIncidents {
+    pub refunds: u32,                // Refunds an arbiter recorded on the merchant's plans
+    pub disputes: u32,               // Times one of the merchant's plans was frozen
+    pub defaults: u32,               // Merchant's plans that went into default
+}
+
+/// Risk profile of a merchant, from `get_merchant_risk`
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MerchantRisk {
+    pub plans_originated: u32,       // Plans where the merchant is the payee
+    pub incidents: MerchantIncidents, // Raw counters
+    pub refund_rate_bps: i128,       // refunds / plans_originated in bps
+    pub dispute_rate_bps: i128,      // disputes / plans_originated in bps
+    pub default_rate_bps: i128,      // defaults / plans_originated in bps
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanSummary {
+    pub status: PlanStatus,          // Current plan status
+    pub total_amount: i128,          // Total plan amount in tokens
+    pub outstanding: i128,           // Unpaid installments in tokens
+    pub paid_count: u32,             // Installments paid so far
+    pub installments_count: u32,     // Number of installments
+    pub next_due_number: Option<u32>, // First pending installment, if any
+    pub next_due_date: Option<u64>,  // Its due date
+    pub next_due_amount: Option<i128>, // Its amount
+    pub fees_paid: i128,             // Protocol fees withheld so far (tokens)
+    pub progress_bps: i128,          // Paid share of the total amount in bps
+    pub available_value: i128,       // Token value of the user's available shares
+    pub protected_value: i128,       // Token value of the user's protected shares
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct Auction {
+    pub plan_id: u64,                // Defaulted plan being liquidated
+    pub debt_token: Address,         // Asset bidders pay the debt in
+    pub collateral_shares: i128,     // Seized shares sold to the winner
+    pub debt_amount: i128,           // Outstanding debt owed to the merchant
+    pub start_price: i128,           // Price for the whole lot at start
+    pub floor_price: i128,           // Price reached once the auction ends
+    pub started_at: u64,             // Auction start timestamp
+    pub duration: u64,               // Seconds until price reaches the floor
+    pub settled: bool,               // Whether a bid already won the lot
+}
+
+// ============ EVENTS ============
+// 
+// Plan lifecycle events publish `(version, name, plan_id, seq)` as topics,
+// so indexers can filter on a schema version and a plan, and one of these
+// structs as data. `seq` is a global sequence shared by every event. They are
+// emitted through the `events` module; changes to an existing plan carry
+// its values before and after the change.
+
+/// `plan_new`: a plan was created
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanCreated {
+    pub plan_id: u64,
+    pub user: Address,
+    pub merchant: Address,               // Payee of the installments
+    pub total_amount: i128,
+    pub installments_count: u32,
+    pub shares_locked: i128,             // Collateral locked for the plan
+    pub status: PlanStatus,              // Active, or PendingApproval for large plans
+    pub created_at: u64,
+}
+
+/// `plan_ok`: a large plan was co-approved
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlanApproved {
+    pub plan_id: u64,
+    pub approver: Address,               // Risk officer
+}
+
+/// `inst_paid`: an installment was collected
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstallmentPaid {
+    pub plan_id: u64,
+    pub installment_number: u32,
+    pub amount: i128,                    // Installment amount, fee included
+    pub fee: i128,                       // Protocol fee withheld from the merchant
+    pub payment_source: PaymentSource,   // Buffer balance that was debited
+    pub shares_used: i128,
+    pub collector: Address,              // Caller: the user or a collector
+    pub paid_at: u64,
+}
+
+/// `plan_done`: every installment of a plan was paid
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlanCompleted {
+    pub plan_id: u64,
+    pub user: Address,
+    pub merchant: Address,
+    pub total_paid: i128,
+    pub completed_at: u64,
+    pub unlocked_shares: i128,           // Collateral returned to the user on completion
+    pub release_at: u64,                 // When held collateral can be released, 0 if none is held
+}
+
+/// `inst_fail`: an installment was declared failed
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstallmentFailed {
+    pub plan_id: u64,
+    pub installment_number: u32,
+    pub amount: i128,
+    pub due_date: u64,
+}
+
+/// `overdue`: a keeper flagged a past-due installment
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstallmentOverdue {
+    pub plan_id: u64,
+    pub installment_number: u32,
+    pub user: Address,
+    pub amount: i128,
+    pub due_date: u64,
+    pub marked_at: u64,
+}
+
+/// `anchored`: a merchant matched a settlement to its off-ramp transaction
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SettlementAnchored {
+    pub plan_id: u64,
+    pub installment_number: u32,         // Settlement's installment (0 = auction proceeds)
+    pub merchant: Address,
+    pub anchor_ref: BytesN<32>,          // Hash of the anchor (e.g. SEP-31) transaction
+}
+
+/// `plan_dflt`: a plan went into default
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlanDefaulted {
+    pub plan_id: u64,
+    pub user: Address,
+    pub merchant: Address,
+    pub installment_number: u32,         // Installment that failed first
+    pub shortfall: i128,                 // Part of that installment the Buffer couldn't cover
+    pub unpaid_amount: i128,             // Pending and failed installments
+    pub defaulted_at: u64,
+}
+
+/// `auc_start`: a collateral auction was opened
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuctionStarted {
+    pub plan_id: u64,
+    pub debt_token: Address,             // Asset bidders pay with
+    pub collateral_shares: i128,
+    pub debt_amount: i128,
+    pub start_price: i128,
+    pub floor_price: i128,
+    pub ends_at: u64,                    // When the price reaches the floor
+}
+
+/// `auc_bid`: a bid settled an auction, liquidating the plan
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlanLiquidated {
+    pub plan_id: u64,
+    pub bidder: Address,
+    pub price: i128,                     // Paid by the bidder
+    pub debt_repaid: i128,               // Paid to the merchant, insurance cover included
+    pub collateral_shares: i128,         // Shares handed to the bidder
+}
+
+/// `released`: held collateral of a completed plan was released
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CollateralReleased {
+    pub plan_id: u64,
+    pub user: Address,
+    pub shares: i128,
+}
+
+/// `rebate`: the rebate of a completed plan was claimed
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RebateClaimed {
+    pub plan_id: u64,
+    pub user: Address,
+    pub amount: i128,
+}
+
+/// `locked` / `unlocked` / `freed`: a plan moved protected shares
+/// 
+/// `locked_before` and `locked_after` are the plan's own locked shares;
+/// cross-margin plans hold none, so only `shares` moves for them.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CollateralChanged {
+    pub plan_id: u64,
+    pub user: Address,
+    pub shares: i128,                    // Shares locked, or unlocked, debited or seized
+    pub locked_before: i128,
+    pub locked_after: i128,
+}
+
+/// `settled`: funds were paid out to a merchant's payout address
+/// 
+/// `installments` lists the installments the payment covers: the collected
+/// one, or every unpaid installment for auction proceeds.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerchantSettled {
+    pub plan_id: u64,
+    pub merchant: Address,
+    pub payout: Address,                 // Address that received the funds
+    pub installments: Vec<u32>,
+    pub gross: i128,                     // Amount owed for the covered installments
+    pub fee: i128,                       // Protocol fee deducted from it
+    pub amount: i128,                    // Net amount paid out
+    pub settled_at: u64,
+}
+
+/// `status`: a plan moved between statuses, whatever the feature behind it
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanStatusChanged {
+    pub plan_id: u64,
+    pub before: PlanStatus,
+    pub after: PlanStatus,
+    pub changed_at: u64,
+}
+
+/// `frozen` / `unfrozen`: an arbiter froze or unfroze a plan
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlanFreezeChanged {
+    pub plan_id: u64,
+    pub arbiter: Address,
+    pub before: bool,                    // Frozen before the call
+    pub after: bool,                     // Frozen after the call
+}
+
+// ============ BUFFER CONTRACT INTERFACE ============
+
+#[contracttype]
+#[derive(Clone)]
+pub struct BufferBalance {
+    pub available_shares: i128,    // Shares available for use
+    pub protected_shares: i128,    // Shares locked as collateral
+    pub total_deposited: i128,     // Total deposited historically
+    pub last_deposit_ts: u64,      // Timestamp of last deposit
+    pub version: u64,              // Balance version
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct LockResult {
+    pub shares_locked: i128,       // Amount of shares that were locked
+    pub new_available: i128,       // New balance of available shares
+    pub new_protected: i128,       // New balance of protected shares
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct WithdrawResult {
+    pub shares_burned: i128,            // Shares burned in the operation
+    pub amounts_received: Vec<i128>,    // Amounts received per asset
+    pub new_available_balance: i128,    // New available balance
+    pub from_protected: bool,           // Whether debited from protected
+}
+
+// Client to call Buffer Contract functions
+#[contractclient(name = "BufferContractClient")]
+pub trait BufferContract {
+    // Get user balance
+    fn get_balance(env: Env, user: Address) -> BufferBalance;
+    
+    // Lock shares as collateral
+    fn lock_shares(env: Env, user: Address, shares: i128) -> LockResult;
+    
+    // Top up protected shares to `amount` if available covers the gap;
+    // returns the balance it checked, before the lock
+    fn check_and_lock(env: Env, user: Address, amount: i128) -> BufferBalance;
+    
+    // Unlock shares (release collateral)
+    fn unlock_shares(env: Env, user: Address, shares: i128) -> LockResult;
+    
+    // Grant the bridge a spending allowance (user signs)
+    fn increase_bridge_allowance(env: Env, user: Address, shares: i128) -> i128;
+    
+    // Debit from available shares (consumes allowance)
+    fn debit_available(env: Env, user: Address, shares: i128, to: Address) -> WithdrawResult;
+    
+    // Debit from protected shares (fallback, consumes allowance)
+    fn debit_protected(env: Env, user: Address, shares: i128, to: Address) -> WithdrawResult;
+    
+    // Get values in tokens (available, protected, total)
+    fn get_values(env: Env, user: Address) -> (i128, i128, i128);
+    
+    // Calculate shares needed for a token amount
+    fn shares_for_amount(env: Env, amount: i128) -> i128;
+    
+    // Underlying asset held by the Buffer vault
+    fn get_asset(env: Env) -> Address;
+}
+
+// ============ CREDIT SCORE INTERFACE ============
+
+// Client to query an external credit score source
+#[contractclient(name = "CreditScoreClient")]
+pub trait CreditScoreSource {
+    // Repayment-history score of a user
+    fn get_score(env: Env, user: Address) -> u32;
+}
+
+// ============ ELIGIBILITY INTERFACE ============
+
+// Client to query a deployment's compliance policy
+#[contractclient(name = "ComplianceClient")]
+pub trait Compliance {
+    // Whether a user may open new plans (KYC, allowlists...)
+    fn is_eligible(env: Env, user: Address) -> bool;
+}
+
+// ============ READ MODEL INTERFACE ============
+
+/// Plan transition pushed to the read-model contract
+/// 
+/// Mirrors the read model's own `PlanUpdate` type (same field names and
+/// order), so both sides serialize it identically.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlanUpdate {
+    pub plan_id: u64,
+    pub action: PlanAction,
+    pub installment_number: u32,     // 0 for plan-level actions
+    pub user: Address,
+    pub merchant: Address,
+    pub amount: i128,                // Plan total on Created, installment amount on Collected/Failed
+    pub timestamp: u64,
+}
+
+// Client to notify the projection contract serving analytical queries
+#[contractclient(name = "ReadModelClient")]
+pub trait ReadModel {
+    // Apply one plan transition to the projections
+    fn on_plan_update(env: Env, update: PlanUpdate);
+}
+
+// ============ COLLATERALIZATION CONSTANTS ============
+
+/// Default maximum Loan-to-Value ratio in basis points (10000 = 100%)
+/// 8000 = 80% - Plan can use up to 80% of total Buffer value
+const DEFAULT_MAX_LTV_BPS: i128 = 8000;
+
+/// Default liquidation threshold in basis points (used by health alerts)
+/// 8500 = 85% - Point where risk should be alerted
+const DEFAULT_LIQUIDATION_THRESHOLD_BPS: i128 = 8500;
+
+/// Default maximum number of installments per plan
+const DEFAULT_MAX_INSTALLMENTS: u32 = 12;
+
+/// Highest `max_installments` allowed: installment flags are packed into a u64
+const MAX_INSTALLMENTS_LIMIT: u32 = 64;
+
+/// Highest protocol fee the admin may configure (10%)
+const MAX_FEE_BPS: i128 = 1000;
+
+/// Basis points denominator (10000 = 100%)
+const BPS_DENOMINATOR: i128 = 10000;
+
+/// Collateral factor applied to assets without an explicit setting (100%)
+const DEFAULT_COLLATERAL_FACTOR_BPS: i128 = 10000;
+
+/// Highest collateral factor the admin may configure (500%)
+const MAX_COLLATERAL_FACTOR_BPS: i128 = 50000;
+
+/// Seconds in a year, used to prorate annual rebate rates
+const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+/// Longest dispute window the admin may set before collateral release (30 days)
+const MAX_RELEASE_DELAY_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Longest timelock on parameter changes (30 days)
+const MAX_TIMELOCK_DELAY_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Largest minimum spacing between due dates the admin may set (90 days)
+const MAX_DUE_SPACING_SECS: u64 = 90 * 24 * 60 * 60;
+
+/// Largest tolerance the admin may allow before a due date (1 hour)
+const MAX_DUE_TOLERANCE_SECS: u64 = 60 * 60;
+
+/// Most bands the collateral tier table may have
+const MAX_COLLATERAL_TIERS: u32 = 10;
+
+/// Most entries a paginated query returns per call
+const MAX_PAGE_SIZE: u32 = 50;
+
+/// Plan IDs per bucket of the user, merchant and status indexes
+const INDEX_BUCKET_SIZE: u32 = 50;
+
+/// Entries per page of a plan's audit trail
+const HISTORY_PAGE_SIZE: u32 = 20;
+
+/// Transitions kept by the recent transitions feed
+const RECENT_TRANSITIONS: u32 = 200;
+
+/// Width of a due-date index bucket (1 day)
+const DUE_BUCKET_SECS: u64 = 86400;
+
+// ============ LIQUIDATION AUCTION CONSTANTS ============
+
+/// Auction opening price as a share of the outstanding debt (150%)
+const AUCTION_START_PRICE_BPS: i128 = 15000;
+
+/// Price the auction decays to as a share of the outstanding debt (50%)
+const AUCTION_FLOOR_PRICE_BPS: i128 = 5000;
+
+/// Time for the price to decay linearly from start to floor (6 hours)
+const AUCTION_DURATION_SECS: u64 = 21600;
+
+/// Time a closed plan keeps its installments before it can be archived (90 days)
+const ARCHIVE_RETENTION_SECS: u64 = 90 * 86400;
+
+/// Time a parameter change stays executable after its timelock (30 days)
+const CHANGE_EXPIRY_SECS: u64 = 30 * 86400;
+
+// ============ CREDIT SCORE CONSTANTS ============
+
+/// Score of a user with no repayment history
+const CREDIT_SCORE_BASE: i64 = 500;
+
+/// Highest possible score
+const CREDIT_SCORE_MAX: i64 = 1000;
+
+/// Points per installment collected by its grace deadline
+const CREDIT_SCORE_ON_TIME: i64 = 5;
+
+/// Points per installment collected after its grace deadline (deducted)
+const CREDIT_SCORE_LATE: i64 = 10;
+
+/// Points per plan fully repaid
+const CREDIT_SCORE_COMPLETED: i64 = 20;
+
+/// Points per plan that went into default (deducted)
+const CREDIT_SCORE_DEFAULT: i64 = 100;
+
+/// Consecutive on-time installments that forgive one default
+const RECOVERY_STREAK: u32 = 6;
+
+// ============ STORAGE TTL CONSTANTS ============
+
+/// Ledgers per day at the ~5 second close time
+const LEDGERS_PER_DAY: u32 = 17280;
+
+/// Remaining TTL below which accessed entries are extended (30 days)
+const TTL_THRESHOLD: u32 = 30 * LEDGERS_PER_DAY;
+
+/// TTL that accessed entries are extended to (180 days)
+const TTL_EXTEND_TO: u32 = 180 * LEDGERS_PER_DAY;
+
+// ============ UPGRADE CONSTANTS ============
+
+/// Storage layout version written by this code; bump with a new migrate step
+/// 
+/// Versions are per release, not per change: every `Config` field added
+/// since version 4 ships together in version 5, so no deployment holds a
+/// partial v5 layout. Any layout change after v5 is released needs v6.
+const STORAGE_VERSION: u32 = 5;
+
+/// Plan record layout written by this code; bump with a new migrate_plans step
+const PLAN_VERSION: u32 = 1;
+
+/// Length of the string plan IDs issued before IDs became `u64`
+const LEGACY_ID_LEN: usize = 16;
+
+/// High bit set on plan IDs derived from (user, merchant, nonce), so they
+/// never collide with counter IDs
+const KEYED_ID_FLAG: u64 = 1 << 63;
+
+// ============ ERRORS ============
+
+#[contracterror]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum ContractError {
+    InvalidAmount = 1,           // Invalid or negative amount, or its math overflows
+    InvalidInstallments = 2,     // Installment count is 0 or above max_installments
+    DueDatesOutOfOrder = 3,      // Due date before the previous one
+    InsufficientAvailable = 4,   // Insufficient available buffer to lock
+    DatesMismatch = 5,           // Number of dates does not match installments
+    InvalidDueDate = 6,          // Due date in the past, too close or too far out
+    PlanNotFound = 7,            // Plan not found in storage (or not awaiting approval)
+    InstallmentNotFound = 8,     // Installment not found in plan
+    AlreadyPaid = 9,             // Installment already paid, or plan completed or liquidated
+    NotDueYet = 10,              // Installment not yet due
+    InsufficientFunds = 11,      // Insufficient funds to pay installment
+    DuplicateDueDate = 12,       // Two installments share a due date
+    SelfFinancing = 13,          // Plan user and merchant are the same account
+    NonceReused = 14,            // Plan nonce already used with different terms
+    ExceedsMaxLTV = 15,          // Plan exceeds maximum Loan-to-Value ratio
+    PlanNotDefaulted = 16,       // Operation requires a defaulted plan
+    AuctionExists = 17,          // Plan collateral is already being auctioned
+    AuctionNotFound = 18,        // No auction for this plan
+    AwaitingApproval = 19,       // Large plan awaiting co-approval
+    PriceAboveMax = 20,          // Current auction price exceeds bidder limit
+    NoCollateral = 21,           // No locked collateral left to seize
+    StillCollectable = 22,       // Installment can still be paid
+    AlreadyInitialized = 23,     // initialize already called
+    NotInitialized = 24,         // Admin not configured yet
+    PlanNotClosed = 25,          // Plan must be completed or liquidated first
+    CreditLimitExceeded = 26,    // Plan exceeds the user's credit limit
+    RateLimitExceeded = 27,      // User created too many plans in the window
+    NothingToRelease = 28,       // No collateral release pending for the plan
+    ReleaseLocked = 29,          // Dispute or retention window has not elapsed yet
+    RebatesNotConfigured = 30,   // No rebate token configured
+    RebateNotClaimable = 31,     // Plan not completed or collateral still locked
+    InsufficientRebatePool = 32, // Rebate pool can't cover the claim
+    Paused = 33,                 // Contract is paused or plans await migration
+    AlreadyMigrated = 34,        // Storage or plans already at the given version
+    NotCollector = 35,           // Caller is neither the plan user nor a collector
+    MerchantExists = 36,         // Merchant is already registered
+    MerchantNotFound = 37,       // Merchant never registered
+    MerchantNotApproved = 38,    // Merchant can't receive new plans
+    UserNotEligible = 39,        // Compliance contract rejected the user
+    InstallmentOutOfOrder = 40,  // An earlier installment is still pending
+    Unauthorized = 41,           // Caller lacks the required role, or no admin transfer to accept
+    GlobalCapExceeded = 42,      // Plan exceeds the protocol-wide cap
+    ChangeNotFound = 43,         // No queued change with this ID (or it expired)
+    TimelockNotExpired = 44,     // Queued change can't execute yet
+    AlreadyRefunded = 45,        // Refund of this plan already recorded
+    InvalidConfig = 46,          // Parameters of a config, role or queued change out of range
+    PlanFrozen = 47,             // Plan is frozen
+    Blacklisted = 48,            // User or merchant is blacklisted
+    MerchantCapExceeded = 49,    // Plan exceeds the merchant's cap
+    UserLimitExceeded = 50,      // Plan exceeds the user's exposure limit
+}
+
+// ============ MAIN CONTRACT ============
+
+#[contract]
+pub struct BridgeContract;
+
+#[contractimpl]
+impl BridgeContract {
+    
+    /// Initialize the contract with its administrator and configuration
+    /// 
+    /// Can only run once. The admin manages risk parameters such as
+    /// per-asset collateral factors; the Buffer address in `config` is
+    /// persisted so callers can't point plans or collections at a fake Buffer.
+    pub fn initialize(env: Env, admin: Address, config: Config) -> Result<(), ContractError> {
+        if env.storage().instance().has(&DataKey::Admin) {
+            log!(&env, "Error: Contract already initialized");
+            return Err(ContractError::AlreadyInitialized);
         }
         
-        if installments_count == 0 || installments_count > 12 {
-            log!(&env, "Error: Invalid installment quantity {}", installments_count);
-            return Err(ContractError::InvalidInstallments);
+        admin.require_auth();
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage().instance().set(&DataKey::Config, &config);
+        env.storage().instance().set(&DataKey::Version, &STORAGE_VERSION);
+        env.storage().instance().set(&DataKey::PlanVersion, &PLAN_VERSION);
+        
+        events::notice(&env, (symbol_short!("init"), admin, config.buffer));
+        
+        Ok(())
+    }
+    
+    /// Replace the contract code (admin only)
+    /// 
+    /// Storage is kept as is; call `migrate` afterwards when the new code
+    /// changes the storage layout.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), ContractError> {
+        let admin = Self::require_admin(&env)?;
+        
+        env.deployer().update_current_contract_wasm(new_wasm_hash.clone());
+        events::notice(&env, (symbol_short!("upgrade"), admin, new_wasm_hash));
+        
+        Ok(())
+    }
+    
+    /// Bring storage to the layout of this code version (admin only)
+    /// 
+    /// Applies each step between the stored version and `STORAGE_VERSION`
+    /// in order, so any older deployment can be migrated in one call.
+    pub fn migrate(env: Env) -> Result<u32, ContractError> {
+        let admin = Self::require_admin(&env)?;
+        
+        let from_version = Self::get_version(env.clone());
+        if from_version >= STORAGE_VERSION {
+            log!(&env, "Error: Storage already at version {}", from_version);
+            return Err(ContractError::AlreadyMigrated);
         }
         
-        if due_dates.len() != installments_count {
-            log!(&env, "Error: Number of dates {} does not match installments {}", 
-                due_dates.len(), installments_count);
-            return Err(ContractError::DatesMismatch);
+        // v0 -> v1: the Buffer address moved into Config
+        if from_version < 1 {
+            if let Some(buffer) = env.storage().instance().get::<_, Address>(&DataKey::Buffer) {
+                env.storage().instance().set(&DataKey::Config, &ConfigV1 { buffer });
+                env.storage().instance().remove(&DataKey::Buffer);
+            }
         }
         
-        // Validate that all dates are in the future
-        let current_time = env.ledger().timestamp();
-        for i in 0..due_dates.len() {
-            let date = due_dates.get(i).unwrap();
-            if date <= current_time {
-                log!(&env, "Error: Due date in the past {}", date);
-                return Err(ContractError::InvalidDueDate);
+        // v1 -> v2: Config gained the optional compliance contract
+        if from_version < 2 {
+            if let Some(old) = env.storage().instance().get::<_, ConfigV1>(&DataKey::Config) {
+                let config = ConfigV2 { buffer: old.buffer, compliance: None };
+                env.storage().instance().set(&DataKey::Config, &config);
             }
         }
         
-        // ===== QUERY BUFFER AND VALIDATE COLLATERALIZATION =====
+        // v2 -> v3: hardcoded limits moved into Config (current defaults)
+        if from_version < 3 {
+            if let Some(old) = env.storage().instance().get::<_, ConfigV2>(&DataKey::Config) {
+                let mut config = Config::new(old.buffer);
+                config.compliance = old.compliance;
+                env.storage().instance().set(&DataKey::Config, &config);
+            }
+        }
         
-        let buffer_client = BufferContractClient::new(&env, &buffer_contract);
+        // v3 -> v4: counters written by plan traffic moved to persistent storage
+        if from_version < 4 {
+            for key in Self::counter_keys(&env).iter() {
+                if let Some(value) = env.storage().instance().get::<_, Val>(&key) {
+                    Self::set_counter(&env, &key, &value);
+                    env.storage().instance().remove(&key);
+                }
+            }
+        }
         
-        // Get values in tokens for validation
-        let (available_value, _, total_value) = buffer_client.get_values(&user);
-        
-        // ===== LTV VALIDATION: Calculate maximum allowed amount =====
-        // MAX_LTV_BPS = 8000 means 80%
-        // max_bridge_amount = total_value * 80 / 100 = total_value * 0.8
-        let max_bridge_amount = (total_value * MAX_LTV_BPS) / 10000;
+        // v4 -> v5: Config gained the due date rules (minimum spacing,
+        // horizon, due tolerance), the minimum installment amount, the credit
+        // curve, the collateral tiers and sequential collection. Each keeps
+        // the v4 behavior: sequential collection on, everything else off.
+        // Versions below 3 already got the v5 layout from the v2 -> v3 step
+        if (3..5).contains(&from_version) {
+            if let Some(old) = env.storage().instance().get::<_, ConfigV4>(&DataKey::Config) {
+                let config = Config {
+                    buffer: old.buffer,
+                    compliance: old.compliance,
+                    max_installments: old.max_installments,
+                    max_ltv_bps: old.max_ltv_bps,
+                    liquidation_threshold_bps: old.liquidation_threshold_bps,
+                    routing: old.routing,
+                    grace_period_secs: old.grace_period_secs,
+                    fee_bps: old.fee_bps,
+                    fee_recipient: old.fee_recipient,
+                    min_due_spacing_secs: 0,
+                    min_installment_amount: 0,
+                    sequential_collection: true,
+                    max_horizon_secs: 0,
+                    due_tolerance_secs: 0,
+                    credit_curve: CreditCurve::default(),
+                    collateral_tiers: Vec::new(&env),
+                };
+                env.storage().instance().set(&DataKey::Config, &config);
+            }
+        }
         
-        log!(&env, "Total Buffer: {}, Max allowed (LTV 80%): {}, Requested: {}", 
-            total_value, max_bridge_amount, total_amount);
+        env.storage().instance().set(&DataKey::Version, &STORAGE_VERSION);
+        events::notice(&env, (symbol_short!("migrate"), admin, from_version, STORAGE_VERSION));
         
-        // Validate plan doesn't exceed maximum LTV
-        if total_amount > max_bridge_amount {
-            log!(&env, "Error: Plan exceeds max LTV {} > {}", total_amount, max_bridge_amount);
-            return Err(ContractError::ExceedsMaxLTV);
-        }
+        Ok(STORAGE_VERSION)
+    }
+    
+    /// Storage layout version of this deployment
+    pub fn get_version(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::Version).unwrap_or(0)
+    }
+    
+    /// Upgrade the next `batch` plan records from `from_version` (admin only)
+    /// 
+    /// Plan records are too many to rewrite in one transaction, so they are
+    /// upgraded in creation order over several calls. Plan operations fail
+    /// with `Paused` until the last batch sets the plan layout to
+    /// `PLAN_VERSION`. Returns the number of plans left to migrate.
+    pub fn migrate_plans(env: Env, from_version: u32, batch: u32) -> Result<u64, ContractError> {
+        let admin = Self::require_admin(&env)?;
         
-        // Validate that there is sufficient available to lock
-        if total_amount > available_value {
-            log!(&env, "Error: Insufficient available balance {} > {}", 
-                total_amount, available_value);
-            return Err(ContractError::InsufficientAvailable);
+        let stored_version = Self::get_plan_version(env.clone());
+        if stored_version >= PLAN_VERSION || from_version != stored_version {
+            log!(&env, "Error: Plans stored at version {}, not {}", stored_version, from_version);
+            return Err(ContractError::AlreadyMigrated);
         }
         
-        // Calculate how many shares need to be locked
-        let shares_needed = buffer_client.shares_for_amount(&total_amount);
+        let cursor: u64 = env.storage()
+            .instance()
+            .get(&DataKey::PlanMigrationCursor)
+            .unwrap_or(0);
+        let count = Self::get_plan_count(env.clone());
+        let end = cursor.saturating_add(batch.min(MAX_PAGE_SIZE) as u64).min(count);
         
-        if shares_needed <= 0 {
-            log!(&env, "Error: Invalid shares calculation");
-            return Err(ContractError::InvalidShares);
+        for index in cursor..end {
+            match from_version {
+                // v0 -> v1: string IDs and inline installments became u64 IDs,
+                // per-installment keys and status bitmaps
+                0 => Self::migrate_plan_v0(&env, index)?,
+                _ => return Err(ContractError::AlreadyMigrated),
+            }
         }
         
-        // ===== LOCK SHARES IN BUFFER =====
-        
-        let _lock_result = buffer_client.lock_shares(&user, &shares_needed);
+        if end < count {
+            env.storage().instance().set(&DataKey::PlanMigrationCursor, &end);
+            return Ok(count - end);
+        }
         
-        // ===== GENERATE UNIQUE PLAN ID =====
+        env.storage().instance().remove(&DataKey::PlanMigrationCursor);
+        env.storage().instance().set(&DataKey::PlanVersion, &PLAN_VERSION);
+        events::notice(&env, (symbol_short!("plans_mig"), admin, from_version, PLAN_VERSION));
         
-        let counter: u64 = env.storage()
+        Ok(0)
+    }
+    
+    /// Layout version of the stored plan records
+    pub fn get_plan_version(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::PlanVersion).unwrap_or(0)
+    }
+    
+    /// Get the contract administrator
+    pub fn admin(env: Env) -> Result<Address, ContractError> {
+        env.storage()
             .instance()
-            .get(&DataKey::PlanCounter)
-            .unwrap_or(0);
-        
-        // Create ID from bytes (avoids issues with to_string())
-        let mut id_bytes = [0u8; 16];
-        id_bytes[0..8].copy_from_slice(&counter.to_be_bytes());
-        let plan_id = String::from_bytes(&env, &id_bytes);
-        
-        // Increment counter for next plan
+            .get(&DataKey::Admin)
+            .ok_or(ContractError::NotInitialized)
+    }
+    
+    /// Get the contract configuration
+    pub fn get_config(env: Env) -> Result<Config, ContractError> {
         env.storage()
             .instance()
-            .set(&DataKey::PlanCounter, &(counter + 1));
+            .get(&DataKey::Config)
+            .ok_or(ContractError::NotInitialized)
+    }
+    
+    /// Propose a new admin (admin only)
+    /// 
+    /// The handover completes only when `new_admin` calls `accept_admin`,
+    /// so a mistyped address can't take over (or brick) the contract.
+    /// Proposing again replaces the pending admin.
+    pub fn transfer_admin(env: Env, new_admin: Address) -> Result<(), ContractError> {
+        let admin = Self::require_admin(&env)?;
         
-        // ===== CALCULATE INSTALLMENTS =====
+        env.storage().instance().set(&DataKey::PendingAdmin, &new_admin);
+        events::notice(&env, (symbol_short!("adm_prop"), admin, new_admin));
         
-        // Divide total amount into equal installments
-        let amount_per_installment = total_amount / installments_count as i128;
-        let remainder = total_amount % installments_count as i128;
+        Ok(())
+    }
+    
+    /// Accept a pending admin transfer (signed by the proposed admin)
+    pub fn accept_admin(env: Env) -> Result<(), ContractError> {
+        let new_admin = Self::get_pending_admin(env.clone())
+            .ok_or(ContractError::Unauthorized)?;
+        new_admin.require_auth();
         
-        let mut installments: Vec<Installment> = Vec::new(&env);
+        let old_admin = Self::admin(env.clone())?;
+        env.storage().instance().set(&DataKey::Admin, &new_admin);
+        env.storage().instance().remove(&DataKey::PendingAdmin);
+        events::notice(&env, (symbol_short!("adm_acc"), old_admin, new_admin));
         
-        for i in 0..installments_count {
-            let mut amount = amount_per_installment;
-            
-            // The last installment carries the remainder to complete the exact total
-            if i == installments_count - 1 {
-                amount += remainder;
-            }
-            
-            let installment = Installment {
-                number: i + 1,
-                amount,
-                due_date: due_dates.get(i).unwrap(),
-                paid_at: None,
-                payment_source: None,
-                status: InstallmentStatus::Pending,
-            };
-            
-            installments.push_back(installment);
+        Ok(())
+    }
+    
+    /// Admin proposed by `transfer_admin`, if any
+    pub fn get_pending_admin(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::PendingAdmin)
+    }
+    
+    /// Grant a role to an account (admin only)
+    /// 
+    /// The admin implicitly holds every role; `Admin` itself is the stored
+    /// admin address and can't be granted.
+    pub fn grant_role(env: Env, role: Role, account: Address) -> Result<(), ContractError> {
+        Self::require_admin(&env)?;
+        
+        if role == Role::Admin {
+            log!(&env, "Error: Admin role can't be granted");
+            return Err(ContractError::InvalidConfig);
         }
         
-        // Clone merchant to use it twice
-        let merchant_for_plan = merchant.clone();
+        env.storage()
+            .instance()
+            .set(&DataKey::Role(role.clone(), account.clone()), &true);
+        events::notice(&env, (symbol_short!("role_set"), role, account));
         
-        // ===== CREATE AND SAVE PLAN =====
+        Ok(())
+    }
+    
+    /// Revoke a role from an account (admin only)
+    pub fn revoke_role(env: Env, role: Role, account: Address) -> Result<(), ContractError> {
+        Self::require_admin(&env)?;
         
-        let plan = BridgePlan {
-            plan_id: plan_id.clone(),
-            user: user.clone(),
-            merchant: merchant_for_plan,
-            total_amount,
-            total_shares: shares_needed,
-            installments_count,
-            installments: installments.clone(),
-            protected_shares: shares_needed,  // Initially all shares are protected
-            status: PlanStatus::Active,
-            created_at: current_time,
-        };
+        if role == Role::Admin {
+            log!(&env, "Error: Admin role can't be revoked");
+            return Err(ContractError::InvalidConfig);
+        }
         
-        // Save plan in persistent storage
         env.storage()
-            .persistent()
-            .set(&DataKey::Plan(plan_id.clone()), &plan);
+            .instance()
+            .remove(&DataKey::Role(role.clone(), account.clone()));
+        events::notice(&env, (symbol_short!("role_del"), role, account));
         
-        // Add plan to user's plan list
-        let mut user_plans: Vec<String> = env.storage()
-            .persistent()
-            .get(&DataKey::UserPlans(user.clone()))
-            .unwrap_or(Vec::new(&env));
+        Ok(())
+    }
+    
+    /// Whether an account holds a role (the admin holds all of them)
+    pub fn has_role(env: Env, role: Role, account: Address) -> bool {
+        if Self::admin(env.clone()).map(|admin| admin == account).unwrap_or(false) {
+            return true;
+        }
+        role != Role::Admin && env.storage().instance().has(&DataKey::Role(role, account))
+    }
+    
+    /// Get the canonical Buffer Contract address
+    pub fn get_buffer(env: Env) -> Result<Address, ContractError> {
+        Ok(Self::get_config(env)?.buffer)
+    }
+    
+    /// Queue a new configuration (admin only)
+    /// 
+    /// Covers the Buffer address, the compliance hook (`create_plan` calls
+    /// its `is_eligible(user)` when set), plan limits, collection routing,
+    /// the grace period before defaults and the protocol fee. Applied
+    /// through the parameter timelock; returns the queued change ID.
+    pub fn update_config(env: Env, config: Config) -> Result<u32, ContractError> {
+        Self::require_admin(&env)?;
+        Self::queue_change(&env, ParamChange::Config(config))
+    }
+    
+    /// Queue a new timelock delay for parameter changes (admin only)
+    /// 
+    /// The change itself waits out the current delay, so integrators get
+    /// notice before the timelock is shortened.
+    pub fn set_timelock_delay(env: Env, delay_secs: u64) -> Result<u32, ContractError> {
+        Self::require_admin(&env)?;
+        Self::queue_change(&env, ParamChange::TimelockDelay(delay_secs))
+    }
+    
+    /// Queue any parameter change (role required by the change)
+    /// 
+    /// Generic propose/execute interface for a governance or DAO contract:
+    /// it proposes here as `caller` (authorizing as the direct invoker), the
+    /// change is validated like the typed setters, then anyone executes it
+    /// with `execute_change` once the timelock expired.
+    pub fn propose_change(env: Env, caller: Address, change: ParamChange) -> Result<u32, ContractError> {
+        Self::require_role(&env, &caller, Self::change_role(&change))?;
+        Self::queue_change(&env, change)
+    }
+    
+    /// Queue a new protocol fee (treasurer role)
+    /// 
+    /// Only touches the fee fields of the config, so it can't race a
+    /// pending `update_config`. A non-zero fee needs a recipient.
+    pub fn set_fee(
+        env: Env,
+        caller: Address,
+        fee_bps: i128,
+        fee_recipient: Option<Address>,
+    ) -> Result<u32, ContractError> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::queue_change(&env, ParamChange::Fee(fee_bps, fee_recipient))
+    }
+    
+    /// Queue new LTV risk limits (treasurer role)
+    pub fn set_risk_params(
+        env: Env,
+        caller: Address,
+        max_ltv_bps: i128,
+        liquidation_threshold_bps: i128,
+    ) -> Result<u32, ContractError> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::queue_change(&env, ParamChange::RiskParams(max_ltv_bps, liquidation_threshold_bps))
+    }
+    
+    /// Seconds a queued parameter change waits before it can execute
+    pub fn get_timelock_delay(env: Env) -> u64 {
+        env.storage().instance().get(&DataKey::TimelockDelay).unwrap_or(0)
+    }
+    
+    /// Get a queued parameter change
+    pub fn get_pending_change(env: Env, change_id: u32) -> Result<PendingChange, ContractError> {
+        env.storage()
+            .instance()
+            .get(&DataKey::PendingChange(change_id))
+            .ok_or(ContractError::ChangeNotFound)
+    }
+    
+    /// Apply a queued parameter change once its timelock expired
+    /// 
+    /// Permissionless: the change was authorized when it was queued. It
+    /// must run within 30 days of its ETA; later it's stale and only
+    /// `cleanup` can drop it.
+    pub fn execute_change(env: Env, change_id: u32) -> Result<(), ContractError> {
+        let pending = Self::get_pending_change(env.clone(), change_id)?;
         
-        user_plans.push_back(plan_id.clone());
+        if env.ledger().timestamp() < pending.eta {
+            log!(&env, "Error: Change {} locked until {}", change_id, pending.eta);
+            return Err(ContractError::TimelockNotExpired);
+        }
+        if env.ledger().timestamp() >= pending.eta.saturating_add(CHANGE_EXPIRY_SECS) {
+            log!(&env, "Error: Change {} expired", change_id);
+            return Err(ContractError::ChangeNotFound);
+        }
         
-        env.storage()
-            .persistent()
-            .set(&DataKey::UserPlans(user.clone()), &user_plans);
+        env.storage().instance().remove(&DataKey::PendingChange(change_id));
+        Self::apply_change(&env, pending.change)?;
         
-        // ===== EMIT EVENT =====
+        events::notice(&env, (symbol_short!("chg_exec"), change_id));
         
-        env.events().publish((
-            symbol_short!("plan_new"),
-            plan_id.clone(),
-            user,
-            merchant,
-            total_amount,
-            installments_count,
-            shares_needed,
-        ), ());
+        Ok(())
+    }
+    
+    /// Drop a queued parameter change (same role that may queue it)
+    pub fn cancel_change(env: Env, caller: Address, change_id: u32) -> Result<(), ContractError> {
+        let pending = Self::get_pending_change(env.clone(), change_id)?;
+        Self::require_role(&env, &caller, Self::change_role(&pending.change))?;
         
-        log!(&env, "Bridge plan created with {} shares locked", shares_needed);
+        env.storage().instance().remove(&DataKey::PendingChange(change_id));
+        events::notice(&env, (symbol_short!("chg_drop"), change_id));
         
-        Ok(plan_id)
+        Ok(())
     }
     
-    /// Query a plan by its ID
-    pub fn get_plan(env: Env, plan_id: String) -> Result<BridgePlan, ContractError> {
-        env.storage()
-            .persistent()
-            .get(&DataKey::Plan(plan_id))
-            .ok_or(ContractError::PlanNotFound)
+    /// Pause plan creation and collection (operator role)
+    /// 
+    /// Meant for incidents: `create_plan` and `collect_installment` fail
+    /// with `Paused` while read-only queries keep working.
+    pub fn pause(env: Env, caller: Address) -> Result<(), ContractError> {
+        Self::require_role(&env, &caller, Role::Operator)?;
+        
+        env.storage().instance().set(&DataKey::Paused, &true);
+        events::notice(&env, (symbol_short!("paused"), caller));
+        
+        Ok(())
     }
     
-    /// Get all plans for a user
-    pub fn get_user_plans(env: Env, user: Address) -> Vec<String> {
-        env.storage()
-            .persistent()
-            .get(&DataKey::UserPlans(user))
-            .unwrap_or(Vec::new(&env))
+    /// Resume plan creation and collection (operator role)
+    pub fn unpause(env: Env, caller: Address) -> Result<(), ContractError> {
+        Self::require_role(&env, &caller, Role::Operator)?;
+        
+        env.storage().instance().remove(&DataKey::Paused);
+        events::notice(&env, (symbol_short!("unpaused"), caller));
+        
+        Ok(())
     }
     
-    /// Collect an installment (called by automatic worker)
+    /// Freeze a plan during a fraud investigation (arbiter role)
     /// 
-    /// Attempts to collect an overdue installment. First tries from available shares,
-    /// if insufficient falls back to protected shares. If still insufficient,
-    /// marks the installment failed and plan as defaulted.
-    pub fn collect_installment(
-        env: Env,
-        plan_id: String,             // Plan ID
-        installment_number: u32,     // Installment number to collect
-        buffer_contract: Address,    // Buffer Contract address
-        merchant_address: Address,   // Merchant address (receives payment)
-    ) -> Result<PaymentSource, ContractError> {
+    /// Collections, defaults, auctions, releases and rebate claims on the
+    /// plan fail with `PlanFrozen` until it is unfrozen; the rest of the
+    /// contract keeps running.
+    pub fn freeze_plan(env: Env, caller: Address, plan_id: u64) -> Result<(), ContractError> {
+        Self::require_role(&env, &caller, Role::Arbiter)?;
+        let plan = Self::load_header(&env, plan_id)?;
         
-        // ===== GET AND VALIDATE PLAN =====
+        let was_frozen = Self::is_frozen(env.clone(), plan_id);
+        env.storage().persistent().set(&PlanKey::Frozen(plan_id), &true);
+        Self::extend_persistent(&env, &PlanKey::Frozen(plan_id));
+        if !was_frozen {
+            Self::update_incidents(&env, &plan.merchant, |incidents| incidents.disputes += 1);
+            let unpaid = Self::unpaid_amount(&env, &plan)?;
+            Self::add_merchant_disputed(&env, &plan.merchant, unpaid);
+        }
+        events::freeze_changed(&env, plan_id, caller, was_frozen, true);
         
-        let mut plan: BridgePlan = env.storage()
-            .persistent()
-            .get(&DataKey::Plan(plan_id.clone()))
-            .ok_or(ContractError::PlanNotFound)?;
+        Ok(())
+    }
+    
+    /// Lift a plan freeze (arbiter role)
+    pub fn unfreeze_plan(env: Env, caller: Address, plan_id: u64) -> Result<(), ContractError> {
+        Self::require_role(&env, &caller, Role::Arbiter)?;
         
-        // Verify user authentication
-        plan.user.require_auth();
+        let was_frozen = Self::is_frozen(env.clone(), plan_id);
+        env.storage().persistent().remove(&PlanKey::Frozen(plan_id));
         
-        // Search for installment in plan
-        let installment_index = installment_number - 1;
+        // Nothing is collected while frozen, so the held amount is unchanged
+        if was_frozen {
+            let plan = Self::load_header(&env, plan_id)?;
+            let unpaid = Self::unpaid_amount(&env, &plan)?;
+            Self::add_merchant_disputed(&env, &plan.merchant, -unpaid);
+        }
+        events::freeze_changed(&env, plan_id, caller, was_frozen, false);
         
-        if installment_index >= plan.installments.len() {
-            log!(&env, "Error: Installment not found {}", installment_number);
-            return Err(ContractError::InstallmentNotFound);
+        Ok(())
+    }
+    
+    /// Record a refund the merchant granted on one of its plans (arbiter role)
+    /// 
+    /// Refunds are settled off-chain; this only counts them towards the
+    /// merchant's refund rate in `get_merchant_risk`. A plan is refunded at
+    /// most once, so repeat calls can't inflate the rate.
+    pub fn record_refund(env: Env, caller: Address, plan_id: u64) -> Result<(), ContractError> {
+        Self::require_role(&env, &caller, Role::Arbiter)?;
+        let plan = Self::load_header(&env, plan_id)?;
+        
+        let key = PlanKey::Refunded(plan_id);
+        if env.storage().persistent().has(&key) {
+            log!(&env, "Error: Refund of plan {} already recorded", plan_id);
+            return Err(ContractError::AlreadyRefunded);
         }
+        env.storage().persistent().set(&key, &true);
+        Self::extend_persistent(&env, &key);
         
-        let mut installment = plan.installments.get(installment_index).unwrap();
+        Self::update_incidents(&env, &plan.merchant, |incidents| incidents.refunds += 1);
+        events::notice(&env, (symbol_short!("refund"), plan_id, plan.merchant));
         
-        // Validate that installment is pending
-        if installment.status != InstallmentStatus::Pending {
-            log!(&env, "Error: Installment already paid {}", installment_number);
-            return Err(ContractError::AlreadyPaid);
+        Ok(())
+    }
+    
+    /// Whether a plan is frozen
+    pub fn is_frozen(env: Env, plan_id: u64) -> bool {
+        env.storage().persistent().has(&PlanKey::Frozen(plan_id))
+    }
+    
+    /// Require co-approval for plans above `threshold` (admin only)
+    /// 
+    /// Such plans are created in `PendingApproval`: their collateral is
+    /// locked, but nothing can be collected until a risk officer calls
+    /// `approve_large_plan`. `None` removes the requirement.
+    pub fn set_approval_threshold(env: Env, threshold: Option<i128>) -> Result<(), ContractError> {
+        Self::require_admin(&env)?;
+        
+        match threshold {
+            Some(amount) if amount < 0 => {
+                log!(&env, "Error: Invalid approval threshold {}", amount);
+                return Err(ContractError::InvalidAmount);
+            }
+            Some(amount) => env.storage().instance().set(&DataKey::ApprovalThreshold, &amount),
+            None => env.storage().instance().remove(&DataKey::ApprovalThreshold),
         }
         
-        // Validate that installment is due
-        let current_time = env.ledger().timestamp();
+        events::notice(&env, (symbol_short!("apprv_thr"), threshold));
         
-        if current_time < installment.due_date {
-            log!(&env, "Error: Installment not yet due {}", installment_number);
-            return Err(ContractError::NotDueYet);
+        Ok(())
+    }
+    
+    /// Plan amount above which co-approval is required, if any
+    pub fn get_approval_threshold(env: Env) -> Option<i128> {
+        env.storage().instance().get(&DataKey::ApprovalThreshold)
+    }
+    
+    /// Co-approve a large plan, activating it (risk officer role)
+    /// 
+    /// Fails with `PlanNotFound` unless the plan is awaiting approval.
+    pub fn approve_large_plan(env: Env, caller: Address, plan_id: u64) -> Result<(), ContractError> {
+        Self::require_role(&env, &caller, Role::RiskOfficer)?;
+        
+        let mut plan = Self::load_header(&env, plan_id)?;
+        if plan.status != PlanStatus::PendingApproval {
+            log!(&env, "Error: Plan is not awaiting approval");
+            return Err(ContractError::PlanNotFound);
         }
         
-        // ===== CALCULATE NEEDED SHARES AND GET BALANCE =====
+        Self::set_status(&env, &mut plan, PlanStatus::Active);
+        Self::record(&env, plan_id, PlanAction::Approved, 0, Some(caller.clone()));
+        Self::save_plan(&env, &plan);
+        events::plan_approved(&env, PlanApproved { plan_id, approver: caller });
         
-        let buffer_client = BufferContractClient::new(&env, &buffer_contract);
-        let shares_needed = buffer_client.shares_for_amount(&installment.amount);
-        let balance = buffer_client.get_balance(&plan.user);
+        Ok(())
+    }
+    
+    /// Add or remove an address from the blacklist (admin only)
+    /// 
+    /// Blacklisted users can't create plans; blacklisted merchants can't
+    /// receive new plans, collections or auction proceeds.
+    pub fn set_blacklisted(env: Env, account: Address, blocked: bool) -> Result<(), ContractError> {
+        Self::require_admin(&env)?;
         
-        // ===== ATTEMPT COLLECTION (Available first, Protected as fallback) =====
+        if blocked {
+            env.storage().persistent().set(&DataKey::Blacklisted(account.clone()), &true);
+        } else {
+            env.storage().persistent().remove(&DataKey::Blacklisted(account.clone()));
+        }
         
-        let payment_source = if balance.available_shares >= shares_needed {
-            
-            // CASE 1: Collect from available shares
-            buffer_client.debit_available(&plan.user, &shares_needed, &merchant_address);
-            
-            // Update protected shares proportionally
-            if plan.total_amount > 0 {
-                let shares_to_unlock = (shares_needed as i128)
-                    .checked_mul(plan.total_shares)
-                    .unwrap_or(0)
-                    .checked_div(plan.total_amount)
-                    .unwrap_or(0);
-                
-                plan.protected_shares = plan.protected_shares.checked_sub(shares_to_unlock)
-                    .unwrap_or(0);
-            }
-            
-            log!(&env, "Collected from Available: {} shares", shares_needed);
-            PaymentSource::available()
-            
-        } else if balance.protected_shares >= shares_needed {
-            
-            // CASE 2: Fallback - Collect from protected shares
-            buffer_client.debit_protected(&plan.user, &shares_needed, &merchant_address);
-            
-            // Reduce plan's protected shares
-            plan.protected_shares = plan.protected_shares.checked_sub(shares_needed)
-                .unwrap_or_else(|| {
-                    log!(&env, "Error: Shares protegidos insuficientes");
-                    0
-                });
-            
-            log!(&env, "Collected from Protected: {} shares", shares_needed);
-            PaymentSource::protected() 
-            
+        events::notice(&env, (symbol_short!("blacklist"), account, blocked));
+        
+        Ok(())
+    }
+    
+    /// Whether an address is blacklisted
+    pub fn is_blacklisted(env: Env, account: Address) -> bool {
+        env.storage().persistent().has(&DataKey::Blacklisted(account))
+    }
+    
+    /// Whether the contract is paused
+    pub fn is_paused(env: Env) -> bool {
+        env.storage().instance().get(&DataKey::Paused).unwrap_or(false)
+    }
+    
+    /// Authorize or revoke an installment collector (operator role)
+    /// 
+    /// Collectors are the off-chain workers: their signature is accepted
+    /// by `collect_installment` for due installments, so automatic
+    /// collection doesn't need the user to sign every payment.
+    pub fn set_collector(
+        env: Env,
+        caller: Address,
+        collector: Address,
+        enabled: bool,
+    ) -> Result<(), ContractError> {
+        Self::require_role(&env, &caller, Role::Operator)?;
+        
+        if enabled {
+            env.storage().instance().set(&DataKey::Collector(collector.clone()), &true);
         } else {
-            
-            // CASE 3: Insufficient funds - Mark as failed
-            log!(&env, "Error: Insufficient funds for installment {}", installment_number);
-            installment.status = InstallmentStatus::Failed;
-            plan.status = PlanStatus::Defaulted;
-            
-            plan.installments.set(installment_index, installment);
-            env.storage().persistent().set(&DataKey::Plan(plan_id), &plan);
-            
-            return Err(ContractError::InsufficientFunds);
+            env.storage().instance().remove(&DataKey::Collector(collector.clone()));
+        }
+        
+        events::notice(&env, (symbol_short!("collector"), collector, enabled));
+        
+        Ok(())
+    }
+    
+    /// Whether an address may collect due installments
+    pub fn is_collector(env: Env, collector: Address) -> bool {
+        env.storage().instance().has(&DataKey::Collector(collector))
+    }
+    
+    /// Register a merchant profile for onboarding
+    /// 
+    /// The merchant signs and starts as `Pending`; `create_plan` only
+    /// accepts the merchant once the admin approves it.
+    pub fn register_merchant(
+        env: Env,
+        merchant: Address,
+        name_hash: BytesN<32>,
+        payout: Address,
+        fee_tier: u32,
+    ) -> Result<(), ContractError> {
+        merchant.require_auth();
+        
+        let key = DataKey::Merchant(merchant.clone());
+        if env.storage().persistent().has(&key) {
+            log!(&env, "Error: Merchant already registered");
+            return Err(ContractError::MerchantExists);
+        }
+        
+        let profile = MerchantProfile {
+            name_hash,
+            payout,
+            fee_tier,
+            status: MerchantStatus::Pending,
         };
+        env.storage().persistent().set(&key, &profile);
         
-        // ===== UPDATE INSTALLMENT STATUS =====
+        events::notice(&env, (symbol_short!("merchant"), merchant, MerchantStatus::Pending));
         
-        installment.paid_at = Some(current_time);
-        installment.payment_source = Some(payment_source);
-        installment.status = InstallmentStatus::Paid;
+        Ok(())
+    }
+    
+    /// Approve a registered merchant (operator role)
+    pub fn approve_merchant(env: Env, caller: Address, merchant: Address) -> Result<(), ContractError> {
+        Self::require_role(&env, &caller, Role::Operator)?;
+        Self::set_merchant_status(&env, merchant, MerchantStatus::Approved)
+    }
+    
+    /// Suspend a merchant (operator role); existing plans keep being collected
+    pub fn suspend_merchant(env: Env, caller: Address, merchant: Address) -> Result<(), ContractError> {
+        Self::require_role(&env, &caller, Role::Operator)?;
+        Self::set_merchant_status(&env, merchant, MerchantStatus::Suspended)
+    }
+    
+    /// Get a merchant's profile
+    pub fn get_merchant(env: Env, merchant: Address) -> Result<MerchantProfile, ContractError> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Merchant(merchant))
+            .ok_or(ContractError::MerchantNotFound)
+    }
+    
+    /// Cap the outstanding amount a merchant may originate (admin only)
+    /// 
+    /// `None` removes the cap. Existing plans are unaffected; `create_plan`
+    /// rejects plans that would push the merchant's exposure above it.
+    pub fn set_merchant_cap(env: Env, merchant: Address, cap: Option<i128>) -> Result<(), ContractError> {
+        Self::require_admin(&env)?;
+        
+        let key = DataKey::MerchantCap(merchant.clone());
+        match cap {
+            Some(amount) if amount < 0 => {
+                log!(&env, "Error: Invalid merchant cap {}", amount);
+                return Err(ContractError::InvalidAmount);
+            }
+            Some(amount) => env.storage().persistent().set(&key, &amount),
+            None => env.storage().persistent().remove(&key),
+        }
         
-        plan.installments.set(installment_index, installment);
+        events::notice(&env, (symbol_short!("mcap"), merchant, cap));
         
-        // ===== CHECK IF PLAN IS COMPLETE =====
+        Ok(())
+    }
+    
+    /// Outstanding amount of a merchant's plans (active and defaulted)
+    pub fn get_merchant_exposure(env: Env, merchant: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::MerchantExposure(merchant))
+            .unwrap_or(0)
+    }
+    
+    /// What a merchant's finance team expects to receive
+    /// 
+    /// `expected` is still to be collected from active plans and
+    /// `in_default` is held on defaulted plans until their auction settles.
+    /// `disputed` is the part of either held on plans an arbiter froze.
+    /// There is no escrow figure: a collection moves the shares from the
+    /// user to the payout address in the same call, so the bridge never
+    /// holds a merchant's funds awaiting settlement.
+    pub fn get_merchant_receivables(env: Env, merchant: Address) -> MerchantReceivables {
+        let outstanding = Self::get_merchant_exposure(env.clone(), merchant.clone());
+        let in_default: i128 = env.storage()
+            .persistent()
+            .get(&DataKey::MerchantDefaulted(merchant.clone()))
+            .unwrap_or(0);
+        let in_default = in_default.min(outstanding);
+        let disputed: i128 = env.storage()
+            .persistent()
+            .get(&UserKey::MerchantDisputed(merchant))
+            .unwrap_or(0);
         
-        let all_paid = (0..plan.installments.len()).all(|i| {
-            plan.installments.get(i).unwrap().status == InstallmentStatus::Paid
+        MerchantReceivables {
+            expected: outstanding - in_default,
+            in_default,
+            outstanding,
+            disputed: disputed.min(outstanding),
+        }
+    }
+    
+    /// Refund, dispute and default rates of a merchant's plans
+    /// 
+    /// Rates are over every plan the merchant originated, so the protocol
+    /// can adjust its fee tier or suspend it. Disputes count plan freezes.
+    pub fn get_merchant_risk(env: Env, merchant: Address) -> MerchantRisk {
+        let plans_originated = Self::get_merchant_plan_count(env.clone(), merchant.clone());
+        let incidents: MerchantIncidents = env.storage()
+            .persistent()
+            .get(&UserKey::MerchantIncidents(merchant))
+            .unwrap_or_default();
+        let rate = |count: u32| Self::ratio_bps(count as i128, plans_originated as i128);
+        
+        MerchantRisk {
+            plans_originated,
+            refund_rate_bps: rate(incidents.refunds),
+            dispute_rate_bps: rate(incidents.disputes),
+            default_rate_bps: rate(incidents.defaults),
+            incidents,
+        }
+    }
+    
+    /// Page of the payments settled to a merchant, oldest first
+    /// 
+    /// One entry per collected installment (net of the protocol fee) or
+    /// auction payout, 20 per page, for reconciliation against bank or
+    /// exchange records.
+    pub fn get_settlement_history(env: Env, merchant: Address, page: u32) -> Vec<Settlement> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Settlements(merchant, page))
+            .unwrap_or(Vec::new(&env))
+    }
+    
+    /// Number of settlements paid to a merchant
+    pub fn get_settlement_count(env: Env, merchant: Address) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::SettlementCount(merchant))
+            .unwrap_or(0)
+    }
+    
+    /// Attach an anchor transaction reference to a settlement (merchant only)
+    /// 
+    /// Lets merchants settling to fiat through an anchor match the on-chain
+    /// payout with the off-ramp leg. A new reference replaces the previous
+    /// one, e.g. when the anchor transaction was retried.
+    pub fn attach_anchor_ref(
+        env: Env,
+        merchant: Address,
+        plan_id: u64,
+        installment_number: u32,     // Settled installment, 0 for auction proceeds
+        anchor_ref: BytesN<32>,
+    ) -> Result<(), ContractError> {
+        merchant.require_auth();
+        let plan = Self::load_header(&env, plan_id)?;
+        
+        if plan.merchant != merchant {
+            log!(&env, "Error: Not the plan's merchant");
+            return Err(ContractError::Unauthorized);
+        }
+        
+        // Only settled payments can be referenced
+        if installment_number == 0 {
+            if plan.status != PlanStatus::Liquidated {
+                log!(&env, "Error: No auction proceeds for plan {}", plan_id);
+                return Err(ContractError::AuctionNotFound);
+            }
+        } else if installment_number > plan.installments_count || !plan.is_paid(installment_number) {
+            log!(&env, "Error: Installment {} not settled", installment_number);
+            return Err(ContractError::InstallmentNotFound);
+        }
+        
+        let key = PlanKey::AnchorRef(plan_id, installment_number);
+        env.storage().persistent().set(&key, &anchor_ref);
+        Self::extend_persistent(&env, &key);
+        
+        events::settlement_anchored(&env, SettlementAnchored {
+            plan_id,
+            installment_number,
+            merchant,
+            anchor_ref,
+        });
+        
+        Ok(())
+    }
+    
+    /// Anchor transaction reference attached to a settlement, if any
+    pub fn get_anchor_ref(env: Env, plan_id: u64, installment_number: u32) -> Option<BytesN<32>> {
+        env.storage()
+            .persistent()
+            .get(&PlanKey::AnchorRef(plan_id, installment_number))
+    }
+    
+    /// Amount a merchant may still originate, `None` when uncapped
+    pub fn get_merchant_headroom(env: Env, merchant: Address) -> Option<i128> {
+        let cap: i128 = env.storage()
+            .persistent()
+            .get(&DataKey::MerchantCap(merchant.clone()))?;
+        let exposure = Self::get_merchant_exposure(env, merchant);
+        Some(if cap > exposure { cap - exposure } else { 0 })
+    }
+    
+    /// Cap the outstanding amount across all plans (admin only)
+    /// 
+    /// Raise it as the protocol matures; `None` removes the cap. Existing
+    /// plans are unaffected.
+    pub fn set_global_cap(env: Env, cap: Option<i128>) -> Result<(), ContractError> {
+        Self::require_admin(&env)?;
+        
+        match cap {
+            Some(amount) if amount < 0 => {
+                log!(&env, "Error: Invalid global cap {}", amount);
+                return Err(ContractError::InvalidAmount);
+            }
+            Some(amount) => env.storage().instance().set(&DataKey::GlobalCap, &amount),
+            None => env.storage().instance().remove(&DataKey::GlobalCap),
+        }
+        
+        events::notice(&env, (symbol_short!("gcap"), cap));
+        
+        Ok(())
+    }
+    
+    /// Protocol-wide outstanding cap, if any
+    pub fn get_global_cap(env: Env) -> Option<i128> {
+        env.storage().instance().get(&DataKey::GlobalCap)
+    }
+    
+    /// Set the read-model contract notified of plan transitions (admin only)
+    /// 
+    /// `None` stops the notifications. The read model serves the heavy
+    /// queries (leaderboards, delinquency lists) off the core storage.
+    pub fn set_read_model(env: Env, read_model: Option<Address>) -> Result<(), ContractError> {
+        Self::require_admin(&env)?;
+        
+        match &read_model {
+            Some(address) => env.storage().instance().set(&DataKey::ReadModel, address),
+            None => env.storage().instance().remove(&DataKey::ReadModel),
+        }
+        
+        events::notice(&env, (symbol_short!("readmdl"), read_model));
+        
+        Ok(())
+    }
+    
+    /// Read-model contract notified of plan transitions, if any
+    pub fn get_read_model(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::ReadModel)
+    }
+    
+    /// Outstanding amount across all plans (active and defaulted)
+    pub fn get_total_outstanding(env: Env) -> i128 {
+        env.storage().persistent().get(&DataKey::TotalOutstanding).unwrap_or(0)
+    }
+    
+    /// Outstanding volume against the protocol cap
+    pub fn get_utilization(env: Env) -> Utilization {
+        let outstanding = Self::get_total_outstanding(env.clone());
+        let cap = Self::get_global_cap(env);
+        let utilization_bps = cap.map(|cap| {
+            if cap > 0 { Self::ratio_bps(outstanding, cap) } else { BPS_DENOMINATOR }
+        });
+        
+        Utilization { outstanding, cap, utilization_bps }
+    }
+    
+    /// Set the ceiling on a user's outstanding obligations (admin only)
+    /// 
+    /// Counts every active and defaulted plan, so concurrent plans can't
+    /// over-commit a single buffer. `None` removes the limit.
+    pub fn set_user_limit(env: Env, user: Address, limit: Option<i128>) -> Result<(), ContractError> {
+        Self::require_admin(&env)?;
+        
+        let key = DataKey::UserLimit(user.clone());
+        match limit {
+            Some(amount) if amount < 0 => {
+                log!(&env, "Error: Invalid user limit {}", amount);
+                return Err(ContractError::InvalidAmount);
+            }
+            Some(amount) => env.storage().persistent().set(&key, &amount),
+            None => env.storage().persistent().remove(&key),
+        }
+        
+        events::notice(&env, (symbol_short!("ulimit"), user, limit));
+        
+        Ok(())
+    }
+    
+    /// Exposure limit of a user, `None` when unlimited
+    pub fn get_user_limit(env: Env, user: Address) -> Option<i128> {
+        env.storage().persistent().get(&DataKey::UserLimit(user))
+    }
+    
+    /// Exposure the config's credit curve allows a user, `None` when it is off
+    /// 
+    /// `base_limit`, plus `volume_bps` of the amount repaid so far and
+    /// `tenure_amount` per full `tenure_period_secs` since the user's first
+    /// plan, cut by `default_penalty_bps` per default not yet forgiven and
+    /// capped at `max_limit`.
+    pub fn get_credit_limit(env: Env, user: Address) -> Result<Option<i128>, ContractError> {
+        let curve = Self::get_config(env.clone())?.credit_curve;
+        if curve.max_limit == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self::credit_limit(&env, &curve, &user)?))
+    }
+    
+    /// Outstanding amount across a user's plans (active and defaulted)
+    pub fn get_user_exposure(env: Env, user: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::UserExposure(user))
+            .unwrap_or(0)
+    }
+    
+    /// Throttle plan creation per user (admin only)
+    /// 
+    /// Each user may create `max_plans` plans per `window_secs`, damping
+    /// abuse of the plan counter and indexes. `None` lifts the throttle.
+    pub fn set_rate_limit(env: Env, limit: Option<RateLimit>) -> Result<(), ContractError> {
+        Self::require_admin(&env)?;
+        
+        match limit {
+            Some(limit) => {
+                if limit.max_plans == 0 || limit.window_secs == 0 {
+                    log!(&env, "Error: Invalid rate limit");
+                    return Err(ContractError::InvalidConfig);
+                }
+                events::notice(&env, (symbol_short!("ratelimit"), limit.max_plans, limit.window_secs));
+                env.storage().instance().set(&DataKey::RateLimit, &limit);
+            }
+            None => {
+                env.storage().instance().remove(&DataKey::RateLimit);
+                events::notice(&env, (symbol_short!("ratelimit"), 0u32, 0u64));
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Plan creation throttle, if any
+    pub fn get_rate_limit(env: Env) -> Option<RateLimit> {
+        env.storage().instance().get(&DataKey::RateLimit)
+    }
+    
+    /// Whether a merchant is approved to receive new plans
+    pub fn is_merchant(env: Env, merchant: Address) -> bool {
+        Self::get_merchant(env, merchant)
+            .map(|profile| profile.status == MerchantStatus::Approved)
+            .unwrap_or(false)
+    }
+    
+    /// Queue a collateral factor for a Buffer asset (treasurer role)
+    /// 
+    /// The factor is the collateral required per unit financed, in basis
+    /// points: 10000 locks 100% of the plan (stablecoins), 15000 locks 150%
+    /// (volatile assets such as XLM). Returns the queued change ID.
+    pub fn set_collateral_factor(
+        env: Env,
+        caller: Address,             // Treasurer (or admin) setting the factor
+        asset: Address,              // Asset held by the Buffer vault
+        factor_bps: i128,            // Required collateral in bps (10000-50000)
+    ) -> Result<u32, ContractError> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::queue_change(&env, ParamChange::CollateralFactor(asset, factor_bps))
+    }
+    
+    /// Share of a plan's collateral (bps) the user's credit score tier locks
+    /// 
+    /// The band with the highest `min_score` the user's score reaches in
+    /// the config's `collateral_tiers`, or 100% when none does.
+    pub fn get_collateral_ratio(env: Env, user: Address) -> Result<i128, ContractError> {
+        let config = Self::get_config(env.clone())?;
+        Ok(Self::tier_ratio(&env, &config, &user))
+    }
+    
+    /// Get the collateral factor (bps) applied to a Buffer asset
+    pub fn get_collateral_factor(env: Env, asset: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::CollateralFactor(asset))
+            .unwrap_or(DEFAULT_COLLATERAL_FACTOR_BPS)
+    }
+    
+    /// Queue a credit-score underwriting configuration (treasurer role)
+    /// 
+    /// Users whose score reaches `min_score` only lock `lock_ratio_bps` of
+    /// their plan; the remaining shortfall is underwritten by the insurance
+    /// fund, up to `fund_capacity` across all outstanding plans.
+    pub fn set_underwriting(
+        env: Env,
+        caller: Address,
+        config: UnderwritingConfig,
+    ) -> Result<u32, ContractError> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::queue_change(&env, ParamChange::Underwriting(config))
+    }
+    
+    /// Get the underwriting configuration, if any
+    pub fn get_underwriting(env: Env) -> Option<UnderwritingConfig> {
+        env.storage().instance().get(&DataKey::Underwriting)
+    }
+    
+    /// Total shortfall currently underwritten by the insurance fund
+    pub fn get_insured_outstanding(env: Env) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::InsuredOutstanding)
+            .unwrap_or(0)
+    }
+    
+    /// Queue the delay between plan completion and collateral release (arbiter role)
+    /// 
+    /// While the delay runs, merchants can still dispute the final payment
+    /// before the remaining protected shares go back to the user.
+    pub fn set_release_delay(env: Env, caller: Address, delay_secs: u64) -> Result<u32, ContractError> {
+        Self::require_role(&env, &caller, Role::Arbiter)?;
+        Self::queue_change(&env, ParamChange::ReleaseDelay(delay_secs))
+    }
+    
+    /// Get the delay (seconds) between completion and collateral release
+    pub fn get_release_delay(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&DataKey::ReleaseDelay)
+            .unwrap_or(0)
+    }
+    
+    /// Release the remaining collateral of a completed plan
+    /// 
+    /// Second step of the unlock once the dispute window after completion
+    /// has elapsed. Anyone can call it; the shares always return to the user.
+    pub fn release_collateral(
+        env: Env,
+        plan_id: u64,             // Completed plan
+    ) -> Result<i128, ContractError> {
+        Self::when_not_frozen(&env, plan_id)?;
+        let mut plan = Self::load_header(&env, plan_id)?;
+        
+        if plan.status != PlanStatus::Completed || plan.release_at == 0 {
+            log!(&env, "Error: No collateral release pending");
+            return Err(ContractError::NothingToRelease);
+        }
+        
+        if env.ledger().timestamp() < plan.release_at {
+            log!(&env, "Error: Collateral locked until {}", plan.release_at);
+            return Err(ContractError::ReleaseLocked);
+        }
+        
+        Self::accrue_rebate(&env, &mut plan);
+        
+        let released = plan.protected_shares;
+        if released > 0 {
+            let buffer_client = Self::buffer_client(&env)?;
+            buffer_client.unlock_shares(&plan.user, &released);
+        }
+        
+        plan.protected_shares = 0;
+        plan.release_at = 0;
+        Self::save_plan(&env, &plan);
+        
+        if released > 0 {
+            events::collateral_unlocked(&env, CollateralChanged {
+                plan_id,
+                user: plan.user.clone(),
+                shares: released,
+                locked_before: released,
+                locked_after: 0,
+            });
+        }
+        events::collateral_released(&env, CollateralReleased { plan_id, user: plan.user, shares: released });
+        
+        Ok(released)
+    }
+    
+    /// Release protected shares no plan accounts for
+    /// 
+    /// Recomputes how many shares the user's plans still need locked
+    /// (active, defaulted, or completed with a pending release) and unlocks
+    /// any excess held by the Buffer, e.g. after a failed transaction left
+    /// both contracts inconsistent. Returns the shares released.
+    pub fn reconcile_locks(
+        env: Env,
+        user: Address,               // User whose locks are reconciled
+    ) -> Result<i128, ContractError> {
+        user.require_auth();
+        
+        let buffer_client = Self::buffer_client(&env)?;
+        let required = Self::required_locks(&env, &buffer_client, &user, &Self::live_plans(&env, &user))?;
+        
+        let balance = buffer_client.get_balance(&user);
+        let orphaned = balance.protected_shares - required;
+        
+        if orphaned <= 0 {
+            return Ok(0);
+        }
+        
+        buffer_client.unlock_shares(&user, &orphaned);
+        
+        // The released excess includes any cross-margin lock its exposure
+        // no longer needs
+        let cross_required = Self::cross_required_shares(&env, &buffer_client, &user)?;
+        let cross_locked = Self::get_cross_locked(env.clone(), user.clone());
+        if cross_locked > cross_required {
+            Self::add_cross_locked(&env, &user, cross_required - cross_locked);
+        }
+        
+        events::notice(&env, (symbol_short!("reconcile"), user, orphaned));
+        log!(&env, "Released {} orphaned shares", orphaned);
+        
+        Ok(orphaned)
+    }
+    
+    /// Check a user's plans against the Buffer, for audits and monitoring
+    /// 
+    /// Read-only and permissionless. Verifies that the shares the user's
+    /// live plans (and cross-margin exposure) account for equal the
+    /// protected balance the Buffer reports, and that each plan's
+    /// installments add up to its total. `reconcile_locks` releases any
+    /// excess the first check finds.
+    pub fn check_invariants(env: Env, user: Address) -> Result<InvariantReport, ContractError> {
+        let buffer_client = Self::buffer_client(&env)?;
+        let plans = Self::live_plans(&env, &user);
+        let required_shares = Self::required_locks(&env, &buffer_client, &user, &plans)?;
+        let locked_shares = buffer_client.get_balance(&user).protected_shares;
+        
+        let mut unbalanced_plans = Vec::new(&env);
+        for plan in plans.iter() {
+            let mut scheduled: i128 = 0;
+            for number in 1..=plan.installments_count {
+                let amount = Self::load_installment(&env, &plan, number)?.amount;
+                scheduled = Self::add_amounts(&env, scheduled, amount)?;
+            }
+            if scheduled != plan.total_amount {
+                unbalanced_plans.push_back(plan.plan_id);
+            }
+        }
+        
+        let locks_balanced = required_shares == locked_shares;
+        Ok(InvariantReport {
+            user,
+            plans_checked: plans.len(),
+            required_shares,
+            locked_shares,
+            locks_balanced,
+            ok: locks_balanced && unbalanced_plans.is_empty(),
+            unbalanced_plans,
+            checked_at: env.ledger().timestamp(),
+        })
+    }
+    
+    /// Prune the installments of a closed plan (callable by anyone)
+    /// 
+    /// Completed plans (collateral released, rebate claimed) and liquidated
+    /// plans can be archived 90 days after they closed. The full plan is
+    /// emitted in an `archived` event, then replaced by its `PlanHeader`,
+    /// which `get_plan_header` and `get_plans` keep serving.
+    pub fn archive_plan(env: Env, plan_id: u64) -> Result<PlanHeader, ContractError> {
+        Self::when_not_frozen(&env, plan_id)?;
+        let plan = Self::get_plan(env.clone(), plan_id)?;
+        
+        match plan.status {
+            PlanStatus::Completed => {
+                if plan.protected_shares > 0 || plan.rebate_accrued > 0 {
+                    log!(&env, "Error: Collateral or rebate still pending");
+                    return Err(ContractError::ReleaseLocked);
+                }
+            }
+            PlanStatus::Liquidated => {}
+            _ => {
+                log!(&env, "Error: Only closed plans can be archived");
+                return Err(ContractError::PlanNotClosed);
+            }
+        }
+        
+        let closed_at: u64 = env.storage()
+            .persistent()
+            .get(&PlanKey::ClosedAt(plan_id))
+            .unwrap_or(plan.created_at);
+        if env.ledger().timestamp() < closed_at.saturating_add(ARCHIVE_RETENTION_SECS) {
+            log!(&env, "Error: Retention period has not elapsed");
+            return Err(ContractError::ReleaseLocked);
+        }
+        
+        events::plan_archived(&env, &plan);
+        
+        let header: PlanHeader = plan.into();
+        let key = PlanKey::ArchivedPlan(plan_id);
+        env.storage().persistent().set(&key, &header);
+        Self::extend_persistent(&env, &key);
+        
+        env.storage().persistent().remove(&PlanKey::Plan(plan_id));
+        for number in 1..=header.installments_count {
+            env.storage().persistent().remove(&PlanKey::Installment(plan_id, number));
+        }
+        env.storage().persistent().remove(&PlanKey::DueDates(plan_id));
+        env.storage().persistent().remove(&PlanKey::Auction(plan_id));
+        env.storage().persistent().remove(&PlanKey::ClosedAt(plan_id));
+        index::plan_archived(&env, plan_id, &header.status);
+        
+        Self::record(&env, plan_id, PlanAction::Archived, 0, None);
+        
+        Ok(header)
+    }
+    
+    /// Reclaim stale storage and pay the caller a bounty (callable by anyone)
+    /// 
+    /// Inspects at most `limit` entries (capped at 50): expired parameter
+    /// changes are purged, closed plans past their retention period are
+    /// archived, and archived plans still listed under their status are
+    /// dropped from that index. Each reclaimed entry earns the cleanup
+    /// bounty, paid in the rebate token out of the rebate pool as far as
+    /// the pool allows.
+    pub fn cleanup(env: Env, caller: Address, limit: u32) -> Result<CleanupReport, ContractError> {
+        Self::when_not_paused(&env)?;
+        
+        let limit = limit.min(MAX_PAGE_SIZE);
+        let now = env.ledger().timestamp();
+        let mut examined: u32 = 0;
+        let mut reclaimed: u32 = 0;
+        
+        // Parameter changes, oldest first: stop at the first one still executable
+        let last_change: u32 = env.storage().instance().get(&DataKey::ChangeCounter).unwrap_or(0);
+        let mut change_id: u32 = env.storage().instance().get(&DataKey::CleanupCursor).unwrap_or(1);
+        while examined < limit && change_id <= last_change {
+            examined += 1;
+            let key = DataKey::PendingChange(change_id);
+            let pending: Option<PendingChange> = env.storage().instance().get(&key);
+            if let Some(pending) = pending {
+                if now < pending.eta.saturating_add(CHANGE_EXPIRY_SECS) {
+                    break;
+                }
+                env.storage().instance().remove(&key);
+                events::notice(&env, (symbol_short!("chg_expd"), change_id));
+                reclaimed += 1;
+            }
+            change_id += 1;
+        }
+        env.storage().instance().set(&DataKey::CleanupCursor, &change_id);
+        
+        // Closed plans, from the front of their status lists. Archiving
+        // moves the last plan of the list into the freed position, so the
+        // position only advances past plans that stay
+        for status in [PlanStatus::Completed, PlanStatus::Liquidated] {
+            let mut position = 0;
+            while examined < limit {
+                let plan_id = match index::status_plan_at(&env, &status, position) {
+                    Some(plan_id) => plan_id,
+                    None => break,
+                };
+                examined += 1;
+                
+                if env.storage().persistent().has(&PlanKey::ArchivedPlan(plan_id)) {
+                    index::plan_archived(&env, plan_id, &status);
+                    reclaimed += 1;
+                } else if Self::archive_plan(env.clone(), plan_id).is_ok() {
+                    reclaimed += 1;
+                } else {
+                    position += 1;
+                }
+            }
+        }
+        
+        let bounty = Self::pay_cleanup_bounty(&env, &caller, reclaimed);
+        
+        events::notice(&env, (symbol_short!("cleanup"), caller, reclaimed, bounty));
+        
+        Ok(CleanupReport { reclaimed, bounty })
+    }
+    
+    /// Set the bounty paid per entry reclaimed by `cleanup` (treasurer role)
+    /// 
+    /// Paid in the rebate token out of the rebate pool; `None` disables it.
+    pub fn set_cleanup_bounty(
+        env: Env,
+        caller: Address,
+        bounty: Option<i128>,
+    ) -> Result<(), ContractError> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        
+        match bounty {
+            Some(amount) if amount < 0 => {
+                log!(&env, "Error: Invalid cleanup bounty {}", amount);
+                return Err(ContractError::InvalidAmount);
+            }
+            Some(amount) => env.storage().instance().set(&DataKey::CleanupBounty, &amount),
+            None => env.storage().instance().remove(&DataKey::CleanupBounty),
+        }
+        
+        events::notice(&env, (symbol_short!("bounty"), bounty));
+        
+        Ok(())
+    }
+    
+    /// Bounty paid per entry reclaimed by `cleanup`, if any
+    pub fn get_cleanup_bounty(env: Env) -> Option<i128> {
+        env.storage().instance().get(&DataKey::CleanupBounty)
+    }
+    
+    /// Opt a user in or out of cross-margin for new plans
+    /// 
+    /// Cross-margin plans don't lock collateral individually: the user's
+    /// single protected balance backs the sum of all their cross-margin
+    /// obligations, and `create_plan` only tops up the lock when that
+    /// aggregate exposure requires it. Existing plans keep their mode.
+    pub fn set_cross_margin(env: Env, user: Address, enabled: bool) -> Result<(), ContractError> {
+        user.require_auth();
+        
+        env.storage()
+            .persistent()
+            .set(&DataKey::CrossMargin(user.clone()), &enabled);
+        
+        events::notice(&env, (symbol_short!("xmargin"), user, enabled));
+        
+        Ok(())
+    }
+    
+    /// Whether new plans of a user use cross-margin
+    pub fn is_cross_margin(env: Env, user: Address) -> bool {
+        env.storage()
+            .persistent()
+            .get(&DataKey::CrossMargin(user))
+            .unwrap_or(false)
+    }
+    
+    /// Outstanding amount of a user's cross-margin plans
+    pub fn get_cross_exposure(env: Env, user: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::CrossExposure(user))
+            .unwrap_or(0)
+    }
+    
+    /// Protected shares locked for a user's cross-margin plans
+    /// 
+    /// Part of the user's protected balance, kept apart from the locks of
+    /// their isolated plans so one never backs the other.
+    pub fn get_cross_locked(env: Env, user: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&UserKey::CrossLocked(user))
+            .unwrap_or(0)
+    }
+    
+    /// Queue a collateral rebate configuration (treasurer role)
+    /// 
+    /// Locked shares earn `rate_bps` per year, paid in `token` from the
+    /// rebate pool. Yield on protected funds or merchant fees can be
+    /// routed back to users this way.
+    pub fn set_rebate_config(
+        env: Env,
+        caller: Address,
+        config: RebateConfig,
+    ) -> Result<u32, ContractError> {
+        Self::require_role(&env, &caller, Role::Treasurer)?;
+        Self::queue_change(&env, ParamChange::Rebate(config))
+    }
+    
+    /// Get the rebate configuration, if any
+    pub fn get_rebate_config(env: Env) -> Option<RebateConfig> {
+        env.storage().instance().get(&DataKey::RebateConfig)
+    }
+    
+    /// Add rebate tokens to the pool (anyone, e.g. treasury or merchants)
+    pub fn fund_rebates(env: Env, from: Address, amount: i128) -> Result<i128, ContractError> {
+        from.require_auth();
+        
+        if amount <= 0 {
+            log!(&env, "Error: Invalid amount {}", amount);
+            return Err(ContractError::InvalidAmount);
+        }
+        
+        let config = Self::get_rebate_config(env.clone())
+            .ok_or(ContractError::RebatesNotConfigured)?;
+        
+        let pool = Self::add_amounts(&env, Self::get_rebate_pool(env.clone()), amount)?;
+        TokenClient::new(&env, &config.token)
+            .transfer(&from, &env.current_contract_address(), &amount);
+        Self::set_counter(&env, &DataKey::RebatePool, &pool);
+        
+        events::notice(&env, (symbol_short!("rebate_in"), from, amount));
+        
+        Ok(pool)
+    }
+    
+    /// Rebate tokens available for claims
+    pub fn get_rebate_pool(env: Env) -> i128 {
+        env.storage().persistent().get(&DataKey::RebatePool).unwrap_or(0)
+    }
+    
+    /// Reconcile the rebate pool with the rebate tokens held (admin only)
+    /// 
+    /// The rebate pool is the only balance the bridge books: collections
+    /// and auction proceeds move straight to the merchant. A balance below
+    /// the pool (e.g. a Stellar clawback) writes the pool down to what is
+    /// held, so claims can't overdraw the contract; a surplus is only
+    /// reported. Both publish an `escrow` event with the booked and held
+    /// amounts, and the call returns held - booked.
+    pub fn reconcile_escrow(env: Env) -> Result<i128, ContractError> {
+        Self::require_admin(&env)?;
+        
+        let asset = Self::get_rebate_config(env.clone())
+            .ok_or(ContractError::RebatesNotConfigured)?
+            .token;
+        let booked = Self::get_rebate_pool(env.clone());
+        let held = TokenClient::new(&env, &asset).balance(&env.current_contract_address());
+        
+        let discrepancy = held - booked;
+        if discrepancy < 0 {
+            log!(&env, "Writing the rebate pool down by {}", -discrepancy);
+            Self::set_counter(&env, &DataKey::RebatePool, &held);
+        }
+        if discrepancy != 0 {
+            events::notice(&env, (symbol_short!("escrow"), asset, booked, held));
+        }
+        
+        Ok(discrepancy)
+    }
+    
+    /// Rebate earned by a plan up to now
+    pub fn get_rebate(env: Env, plan_id: u64) -> Result<i128, ContractError> {
+        let mut plan = Self::load_header(&env, plan_id)?;
+        Self::accrue_rebate(&env, &mut plan);
+        Ok(plan.rebate_accrued)
+    }
+    
+    /// Claim the rebate of a completed plan
+    /// 
+    /// Only possible once every installment is paid and the plan's
+    /// collateral was released back to the user.
+    pub fn claim_rebate(env: Env, plan_id: u64) -> Result<i128, ContractError> {
+        Self::when_not_frozen(&env, plan_id)?;
+        let mut plan = Self::load_header(&env, plan_id)?;
+        
+        plan.user.require_auth();
+        
+        if plan.status != PlanStatus::Completed || plan.protected_shares > 0 {
+            log!(&env, "Error: Rebate not claimable yet");
+            return Err(ContractError::RebateNotClaimable);
+        }
+        
+        let config = Self::get_rebate_config(env.clone())
+            .ok_or(ContractError::RebatesNotConfigured)?;
+        
+        Self::accrue_rebate(&env, &mut plan);
+        let amount = plan.rebate_accrued;
+        
+        let pool = Self::get_rebate_pool(env.clone());
+        if amount > pool {
+            log!(&env, "Error: Rebate pool {} below claim {}", pool, amount);
+            return Err(ContractError::InsufficientRebatePool);
+        }
+        
+        if amount > 0 {
+            TokenClient::new(&env, &config.token)
+                .transfer(&env.current_contract_address(), &plan.user, &amount);
+        }
+        
+        plan.rebate_accrued = 0;
+        Self::set_counter(&env, &DataKey::RebatePool, &(pool - amount));
+        Self::save_plan(&env, &plan);
+        
+        events::rebate_claimed(&env, RebateClaimed { plan_id, user: plan.user, amount });
+        
+        Ok(amount)
+    }
+    
+    /// Set the minimum amount kept in a user's available buffer
+    /// 
+    /// `collect_installment` never debits Available below this floor
+    /// (rent, essentials); when only reserve funds remain it falls back to
+    /// protected shares, and otherwise the installment becomes defaultable.
+    pub fn set_reserve(env: Env, user: Address, amount: i128) -> Result<(), ContractError> {
+        user.require_auth();
+        
+        if amount < 0 {
+            log!(&env, "Error: Reserve cannot be negative");
+            return Err(ContractError::InvalidAmount);
+        }
+        
+        env.storage()
+            .persistent()
+            .set(&DataKey::Reserve(user.clone()), &amount);
+        
+        events::notice(&env, (symbol_short!("reserve"), user, amount));
+        
+        Ok(())
+    }
+    
+    /// Minimum amount kept in a user's available buffer
+    pub fn get_reserve(env: Env, user: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Reserve(user))
+            .unwrap_or(0)
+    }
+    
+    /// Create an installment plan
+    /// 
+    /// Creates a new installment financing plan, locking Buffer shares
+    /// as collateral. Validates that the user has sufficient collateral
+    /// and locks the necessary shares. Integrations that may retry a
+    /// creation use `create_plan_with_nonce` instead.
+    pub fn create_plan(
+        env: Env,
+        user: Address,               // User who creates the plan
+        merchant: Address,           // Merchant who will receive payments
+        total_amount: i128,          // Total amount to finance
+        installments_count: u32,     // Number of installments (1-12)
+        due_dates: Vec<u64>,         // Due dates of each installment
+    ) -> Result<u64, ContractError> {
+        Self::open_plan(env, user, merchant, total_amount, installments_count, due_dates, None)
+    }
+    
+    /// Create an installment plan whose ID is derived from (user, merchant, nonce)
+    /// 
+    /// Lets point-of-sale systems retry a creation whose acknowledgement was
+    /// lost: resubmitting the same nonce with the same terms returns the
+    /// existing plan ID without locking collateral again. Reusing a nonce
+    /// with different terms fails with `NonceReused`.
+    pub fn create_plan_with_nonce(
+        env: Env,
+        user: Address,
+        merchant: Address,
+        total_amount: i128,
+        installments_count: u32,
+        due_dates: Vec<u64>,
+        nonce: u64,                  // Caller-chosen, unique per (user, merchant)
+    ) -> Result<u64, ContractError> {
+        Self::open_plan(env, user, merchant, total_amount, installments_count, due_dates, Some(nonce))
+    }
+    
+    /// Plan ID `create_plan_with_nonce` assigns for (user, merchant, nonce)
+    pub fn get_keyed_plan_id(env: Env, user: Address, merchant: Address, nonce: u64) -> u64 {
+        Self::keyed_plan_id(&env, &user, &merchant, nonce)
+    }
+    
+    fn open_plan(
+        env: Env,
+        user: Address,
+        merchant: Address,
+        total_amount: i128,
+        installments_count: u32,
+        due_dates: Vec<u64>,
+        nonce: Option<u64>,
+    ) -> Result<u64, ContractError> {
+        
+        // Verify that user signed the transaction
+        user.require_auth();
+        Self::when_not_paused(&env)?;
+        
+        // ===== IDEMPOTENT RETRY OF A KEYED CREATION =====
+        
+        let keyed_id = nonce.map(|nonce| Self::keyed_plan_id(&env, &user, &merchant, nonce));
+        if let Some(plan_id) = keyed_id {
+            if let Ok(existing) = Self::get_plan_header(env.clone(), plan_id) {
+                if existing.user != user
+                    || existing.merchant != merchant
+                    || existing.total_amount != total_amount
+                    || existing.installments_count != installments_count
+                    || !Self::same_schedule(&env, &existing, &due_dates) {
+                    log!(&env, "Error: Nonce already used for plan {}", plan_id);
+                    return Err(ContractError::NonceReused);
+                }
+                return Ok(plan_id);
+            }
+        }
+        
+        let config = Self::get_config(env.clone())?;
+        
+        // ===== BASIC VALIDATIONS =====
+        
+        if total_amount <= 0 {
+            log!(&env, "Error: Invalid amount {}", total_amount);
+            return Err(ContractError::InvalidAmount);
+        }
+        
+        // Self-financing would cycle the user's own collateral through
+        // collections to mint repayment history
+        if user == merchant {
+            log!(&env, "Error: User can't be the plan's merchant");
+            return Err(ContractError::SelfFinancing);
+        }
+        
+        if installments_count == 0 || installments_count > config.max_installments {
+            log!(&env, "Error: Invalid installment quantity {}", installments_count);
+            return Err(ContractError::InvalidInstallments);
+        }
+        
+        if due_dates.len() != installments_count {
+            log!(&env, "Error: Number of dates {} does not match installments {}", 
+                due_dates.len(), installments_count);
+            return Err(ContractError::DatesMismatch);
+        }
+        
+        // Dust installments cost more in fees than they collect. The last
+        // one carries the remainder, so the equal share is the smallest
+        let installment_amount = total_amount / installments_count as i128;
+        if installment_amount < config.min_installment_amount {
+            log!(&env, "Error: Installment {} below minimum {}", 
+                installment_amount, config.min_installment_amount);
+            return Err(ContractError::InvalidAmount);
+        }
+        
+        // Validate that all dates are in the future and strictly increasing,
+        // so installments fall due in the order they are collected, and at
+        // least `min_due_spacing_secs` apart so they can't all fall due at once
+        let current_time = env.ledger().timestamp();
+        let mut previous = current_time;
+        for i in 0..due_dates.len() {
+            let date = due_dates.get(i).unwrap();
+            if date <= current_time {
+                log!(&env, "Error: Due date in the past {}", date);
+                return Err(ContractError::InvalidDueDate);
+            }
+            if i > 0 && date == previous {
+                log!(&env, "Error: Duplicate due date {}", date);
+                return Err(ContractError::DuplicateDueDate);
+            }
+            if date < previous {
+                log!(&env, "Error: Due date {} before the previous one {}", date, previous);
+                return Err(ContractError::DueDatesOutOfOrder);
+            }
+            if i > 0 && date - previous < config.min_due_spacing_secs {
+                log!(&env, "Error: Due date {} less than {}s after {}", date, config.min_due_spacing_secs, previous);
+                return Err(ContractError::InvalidDueDate);
+            }
+            previous = date;
+        }
+        
+        // Bound how long collateral stays locked
+        let last_due = due_dates.last().unwrap_or(current_time);
+        if config.max_horizon_secs > 0 && last_due - current_time > config.max_horizon_secs {
+            log!(&env, "Error: Last due date {} past the {}s horizon", last_due, config.max_horizon_secs);
+            return Err(ContractError::InvalidDueDate);
+        }
+        
+        // ===== QUERY BUFFER AND VALIDATE COLLATERALIZATION =====
+        
+        let buffer_client = BufferContractClient::new(&env, &config.buffer);
+        
+        if Self::is_blacklisted(env.clone(), user.clone())
+            || Self::is_blacklisted(env.clone(), merchant.clone()) {
+            log!(&env, "Error: Counterparty is blacklisted");
+            return Err(ContractError::Blacklisted);
+        }
+        
+        if !Self::is_merchant(env.clone(), merchant.clone()) {
+            log!(&env, "Error: Merchant not approved");
+            return Err(ContractError::MerchantNotApproved);
+        }
+        
+        if let Some(headroom) = Self::get_merchant_headroom(env.clone(), merchant.clone()) {
+            if total_amount > headroom {
+                log!(&env, "Error: Merchant cap exceeded {} > {}", total_amount, headroom);
+                return Err(ContractError::MerchantCapExceeded);
+            }
+        }
+        
+        if let Some(cap) = Self::get_global_cap(env.clone()) {
+            let outstanding = Self::add_amounts(&env, Self::get_total_outstanding(env.clone()), total_amount)?;
+            if outstanding > cap {
+                log!(&env, "Error: Protocol cap exceeded {} > {}", outstanding, cap);
+                return Err(ContractError::GlobalCapExceeded);
+            }
+        }
+        
+        if let Some(limit) = Self::get_user_limit(env.clone(), user.clone()) {
+            let user_exposure = Self::add_amounts(&env, Self::get_user_exposure(env.clone(), user.clone()), total_amount)?;
+            if user_exposure > limit {
+                log!(&env, "Error: User limit exceeded {} > {}", user_exposure, limit);
+                return Err(ContractError::UserLimitExceeded);
+            }
+        }
+        
+        // Grows with the user's repayment record, on top of the collateral checks
+        if config.credit_curve.max_limit > 0 {
+            let limit = Self::credit_limit(&env, &config.credit_curve, &user)?;
+            let user_exposure = Self::add_amounts(&env, Self::get_user_exposure(env.clone(), user.clone()), total_amount)?;
+            if user_exposure > limit {
+                log!(&env, "Error: Credit limit exceeded {} > {}", user_exposure, limit);
+                return Err(ContractError::CreditLimitExceeded);
+            }
+        }
+        
+        Self::consume_creation_slot(&env, &user)?;
+        
+        if let Some(compliance) = config.compliance.clone() {
+            if !ComplianceClient::new(&env, &compliance).is_eligible(&user) {
+                log!(&env, "Error: User not eligible");
+                return Err(ContractError::UserNotEligible);
+            }
+        }
+        
+        // Get values in tokens for validation
+        let (available_value, _, total_value) = buffer_client.get_values(&user);
+        
+        let cross_margin = Self::is_cross_margin(env.clone(), user.clone());
+        
+        // ===== CREDIT UNDERWRITING: reduced lock for strong histories =====
+        // Only per-plan locks can be partially underwritten
+        let insured_amount = if cross_margin {
+            0
+        } else {
+            Self::insured_amount_for(&env, &user, total_amount)?
+        };
+        let collateralized_amount = total_amount - insured_amount;
+        
+        // Cross-margin plans are validated against the user's whole exposure
+        let cross_exposure = if cross_margin {
+            Self::get_cross_exposure(env.clone(), user.clone())
+        } else {
+            0
+        };
+        let exposure = Self::add_amounts(&env, cross_exposure, collateralized_amount)?;
+        
+        // ===== LTV VALIDATION: Calculate maximum allowed amount =====
+        // max_ltv_bps = 8000 (default) means 80%
+        // max_bridge_amount = total_value * 80 / 100 = total_value * 0.8
+        let max_bridge_amount = Self::mul_div(&env, total_value, config.max_ltv_bps, BPS_DENOMINATOR)?;
+        
+        log!(&env, "Total Buffer: {}, Max allowed (LTV 80%): {}, Collateralized: {}", 
+            total_value, max_bridge_amount, exposure);
+        
+        // Validate the collateralized exposure doesn't exceed maximum LTV
+        if exposure > max_bridge_amount {
+            log!(&env, "Error: Plan exceeds max LTV {} > {}", exposure, max_bridge_amount);
+            return Err(ContractError::ExceedsMaxLTV);
+        }
+        
+        // ===== COLLATERAL FACTOR: over-collateralize volatile assets =====
+        let asset = buffer_client.get_asset();
+        let collateral_factor = Self::get_collateral_factor(env.clone(), asset);
+        let required_collateral = Self::mul_div(&env, exposure, collateral_factor, BPS_DENOMINATOR)?;
+        
+        // ===== RISK TIER: proven users lock a smaller share =====
+        // Only per-plan locks are tiered, as with underwriting
+        let required_collateral = if cross_margin {
+            required_collateral
+        } else {
+            let ratio_bps = Self::tier_ratio(&env, &config, &user);
+            Self::mul_div(&env, required_collateral, ratio_bps, BPS_DENOMINATOR)?
+        };
+        
+        let shares_needed = if cross_margin {
+            // Only lock what the shared cross-margin lock is missing. Shares
+            // the user's isolated plans hold don't count towards it. The
+            // Buffer checks and tops up the lock in one call, and returns
+            // the balance it checked; it locked nothing if that fails below
+            let required_shares = buffer_client.shares_for_amount(&required_collateral);
+            let cross_locked = Self::get_cross_locked(env.clone(), user.clone());
+            let top_up = required_shares - cross_locked;
+            
+            if top_up > 0 {
+                let locked = buffer_client.get_balance(&user).protected_shares;
+                let balance = buffer_client.check_and_lock(&user, &(locked + top_up));
+                
+                if top_up > balance.available_shares {
+                    log!(&env, "Error: Insufficient available balance {} > {}", 
+                        top_up, balance.available_shares);
+                    return Err(ContractError::InsufficientAvailable);
+                }
+                Self::add_cross_locked(&env, &user, top_up);
+                top_up
+            } else {
+                0
+            }
+        } else {
+            // Validate that there is sufficient available to lock
+            if required_collateral > available_value {
+                log!(&env, "Error: Insufficient available balance {} > {}", 
+                    required_collateral, available_value);
+                return Err(ContractError::InsufficientAvailable);
+            }
+            
+            // Calculate how many shares need to be locked
+            let shares_needed = buffer_client.shares_for_amount(&required_collateral);
+            
+            if shares_needed <= 0 {
+                log!(&env, "Error: Invalid shares calculation");
+                return Err(ContractError::InvalidAmount);
+            }
+            
+            shares_needed
+        };
+        
+        // ===== LOCK SHARES IN BUFFER =====
+        
+        // Cross-margin top-ups were already locked by check_and_lock
+        if shares_needed > 0 && !cross_margin {
+            buffer_client.lock_shares(&user, &shares_needed);
+        }
+        
+        // ===== GRANT SPENDING ALLOWANCE =====
+        // Collections can only debit what the user approved here, which
+        // bounds the damage a compromised bridge could do to the Buffer
+        let installment_shares = buffer_client.shares_for_amount(&total_amount);
+        let allowance_shares = if installment_shares > shares_needed {
+            installment_shares
+        } else {
+            shares_needed
+        };
+        buffer_client.increase_bridge_allowance(&user, &allowance_shares);
+        
+        // ===== GENERATE UNIQUE PLAN ID =====
+        
+        let counter = Self::get_plan_count(env.clone());
+        
+        // Derived IDs remember their creation index for get_plan_by_index
+        let plan_id = match keyed_id {
+            Some(plan_id) => {
+                let key = PlanKey::ByIndex(counter);
+                env.storage().persistent().set(&key, &plan_id);
+                Self::extend_persistent(&env, &key);
+                plan_id
+            }
+            None => counter,
+        };
+        
+        // Increment counter for next plan
+        Self::set_counter(&env, &DataKey::PlanCounter, &(counter + 1));
+        
+        // ===== STORE SCHEDULE =====
+        // Installments are not stored until paid or failed: pending ones are
+        // computed from the schedule on read
+        
+        let (first_due, interval) = Self::store_schedule(&env, plan_id, &due_dates);
+        
+        // Clone merchant to use it twice
+        let merchant_for_plan = merchant.clone();
+        
+        // Cross-margin locks belong to the portfolio, not to this plan
+        let plan_shares = if cross_margin { 0 } else { shares_needed };
+        
+        // Large plans lock collateral now but wait for a co-approval
+        let status = match Self::get_approval_threshold(env.clone()) {
+            Some(threshold) if total_amount > threshold => PlanStatus::PendingApproval,
+            _ => PlanStatus::Active,
+        };
+        
+        // ===== CREATE AND SAVE PLAN =====
+        
+        let plan = PlanHeader {
+            plan_id,
+            user: user.clone(),
+            merchant: merchant_for_plan,
+            total_amount,
+            total_shares: plan_shares,
+            installments_count,
+            first_due,
+            interval,
+            protected_shares: plan_shares,  // Initially all shares are protected
+            status,
+            paid_bitmap: 0,
+            failed_bitmap: 0,
+            created_at: current_time,
+            insured_amount,
+            release_at: 0,
+            cross_margin,
+            rebate_accrued: 0,
+            rebate_checkpoint: current_time,
+        };
+        
+        if cross_margin {
+            env.storage()
+                .persistent()
+                .set(&DataKey::CrossExposure(user.clone()), &exposure);
+        }
+        
+        if insured_amount > 0 {
+            let insured = Self::get_insured_outstanding(env.clone());
+            Self::set_counter(&env, &DataKey::InsuredOutstanding, &Self::add_amounts(&env, insured, insured_amount)?);
+        }
+        
+        Self::add_exposure(&env, &user, &merchant, total_amount);
+        
+        // Save plan in persistent storage and add it to the indexes
+        Self::save_plan(&env, &plan);
+        index::plan_created(&env, &plan);
+        
+        Self::update_stats(&env, |stats| {
+            stats.volume_originated = stats.volume_originated.saturating_add(total_amount);
+            if plan.status == PlanStatus::Active {
+                stats.active_plans = stats.active_plans.saturating_add(1);
+            }
+        });
+        
+        Self::record(&env, plan_id, PlanAction::Created, 0, Some(user.clone()));
+        
+        // ===== EMIT EVENT =====
+        
+        events::plan_created(&env, PlanCreated {
+            plan_id,
+            user,
+            merchant,
+            total_amount,
+            installments_count,
+            shares_locked: shares_needed,
+            status: plan.status,
+            created_at: current_time,
+        });
+        if shares_needed > 0 {
+            events::collateral_locked(&env, CollateralChanged {
+                plan_id,
+                user: plan.user,
+                shares: shares_needed,
+                locked_before: 0,
+                locked_after: plan.protected_shares,
+            });
+        }
+        
+        log!(&env, "Bridge plan created with {} shares locked", shares_needed);
+        
+        Ok(plan_id)
+    }
+    
+    /// Query a plan by its ID
+    /// 
+    /// Extends the plan's TTL, so plans that are still being read stay live.
+    pub fn get_plan(env: Env, plan_id: u64) -> Result<BridgePlan, ContractError> {
+        let header = Self::load_header(&env, plan_id)?;
+        
+        let mut installments = Vec::new(&env);
+        for number in 1..=header.installments_count {
+            installments.push_back(Self::load_installment(&env, &header, number)?);
+        }
+        Ok(header.with_installments(installments))
+    }
+    
+    /// Fetch the headers of several plans in one call
+    /// 
+    /// Entries follow the order of `plan_ids`, `None` for unknown IDs. At
+    /// most 50 IDs are read; the rest are ignored.
+    pub fn get_plans(env: Env, plan_ids: Vec<u64>) -> Vec<Option<PlanHeader>> {
+        let mut plans = Vec::new(&env);
+        for plan_id in Self::page(&plan_ids, 0, MAX_PAGE_SIZE).iter() {
+            plans.push_back(Self::get_plan_header(env.clone(), plan_id).ok());
+        }
+        plans
+    }
+    
+    /// Whether a plan exists (archived or not), without deserializing it
+    pub fn has_plan(env: Env, plan_id: u64) -> bool {
+        let storage = env.storage().persistent();
+        storage.has(&PlanKey::Plan(plan_id)) || storage.has(&PlanKey::ArchivedPlan(plan_id))
+    }
+    
+    /// Number of plans ever created (the current `PlanCounter`)
+    /// 
+    /// Plan IDs are the counter value at creation, so indexers can detect
+    /// gaps and backfill without guessing IDs. Plans created with a nonce
+    /// have a derived ID instead; `get_plan_by_index` resolves them.
+    pub fn get_plan_count(env: Env) -> u64 {
+        env.storage().persistent().get(&DataKey::PlanCounter).unwrap_or(0)
+    }
+    
+    /// Plan counter, event sequence and ledger time in one read
+    /// 
+    /// All read in the same invocation, so an indexer checkpointing on
+    /// them gets values consistent with each other.
+    pub fn get_index_cursor(env: Env) -> IndexCursor {
+        IndexCursor {
+            plan_count: Self::get_plan_count(env.clone()),
+            event_seq: env.storage().persistent().get(&DataKey::EventSeq).unwrap_or(0),
+            transition_count: Self::get_transition_count(env.clone()),
+            ledger: env.ledger().sequence(),
+            timestamp: env.ledger().timestamp(),
+        }
+    }
+    
+    /// Query a plan by its creation index (0 to `get_plan_count() - 1`)
+    /// 
+    /// Same as `get_plan` for counter IDs; plans created with a nonce are
+    /// looked up through their recorded index.
+    pub fn get_plan_by_index(env: Env, index: u64) -> Result<BridgePlan, ContractError> {
+        let plan_id = env.storage()
+            .persistent()
+            .get(&PlanKey::ByIndex(index))
+            .unwrap_or(index);
+        Self::get_plan(env, plan_id)
+    }
+    
+    /// Query a plan by the string ID returned before IDs became `u64`
+    /// 
+    /// Legacy IDs are 16 bytes: the big-endian plan counter followed by zero
+    /// padding. Anything else is `PlanNotFound`.
+    pub fn get_plan_by_legacy_id(env: Env, legacy_id: String) -> Result<BridgePlan, ContractError> {
+        let plan_id = Self::legacy_plan_id(&legacy_id).ok_or(ContractError::PlanNotFound)?;
+        Self::get_plan(env, plan_id)
+    }
+    
+    /// Query a plan's status, totals and counterparties without its installments
+    /// 
+    /// Most UI reads need nothing else, and the installments vector is
+    /// the bulk of the returned data.
+    /// 
+    /// Also serves archived plans, whose installments were pruned.
+    pub fn get_plan_header(env: Env, plan_id: u64) -> Result<PlanHeader, ContractError> {
+        match Self::load_header(&env, plan_id) {
+            Ok(header) => Ok(header),
+            Err(err) => env.storage()
+                .persistent()
+                .get(&PlanKey::ArchivedPlan(plan_id))
+                .ok_or(err),
+        }
+    }
+    
+    /// Get all plans for a user
+    /// 
+    /// Reads every bucket of the user's index; prefer
+    /// `get_user_plans_page` for users with many plans. A user who never
+    /// had a plan gets an empty list.
+    pub fn get_user_plans(env: Env, user: Address) -> Result<Vec<u64>, ContractError> {
+        Ok(Self::user_plans(&env, &user))
+    }
+    
+    /// Extend the TTL of a plan, its per-plan entries, the index entries
+    /// that point at it and the contract instance (callable by anyone)
+    /// 
+    /// Accesses extend the header, installments and index entries they
+    /// touch, but the audit trail, fees and schedule are only read now and
+    /// then; workers call this for active plans that go untouched between
+    /// installments.
+    pub fn bump_plan(env: Env, plan_id: u64) -> Result<(), ContractError> {
+        let plan = Self::load_header(&env, plan_id)?;
+        
+        for number in 0..=plan.installments_count {
+            Self::extend_if_present(&env, &PlanKey::Installment(plan_id, number));
+            Self::extend_if_present(&env, &PlanKey::AnchorRef(plan_id, number));
+        }
+        Self::extend_if_present(&env, &PlanKey::PlanHistoryLen(plan_id));
+        for page in 0..=Self::history_len(&env, plan_id) / HISTORY_PAGE_SIZE {
+            Self::extend_if_present(&env, &PlanKey::PlanHistory(plan_id, page));
+        }
+        for key in [
+            PlanKey::DueDates(plan_id),
+            PlanKey::Frozen(plan_id),
+            PlanKey::PlanFees(plan_id),
+            PlanKey::Auction(plan_id),
+            PlanKey::ClosedAt(plan_id),
+            PlanKey::Refunded(plan_id),
+        ] {
+            Self::extend_if_present(&env, &key);
+        }
+        
+        index::plan_bumped(&env, &plan);
+        env.storage().instance().extend_ttl(TTL_THRESHOLD, TTL_EXTEND_TO);
+        Ok(())
+    }
+    
+    /// Soonest pending installment across a user's open plans
+    /// 
+    /// Lets wallets show "next payment: 12 USDC on March 3" without
+    /// fetching every plan. Defaulted plans count, since their remaining
+    /// installments are still collected. `None` when nothing is pending.
+    pub fn get_next_obligation(env: Env, user: Address) -> Option<DueInstallment> {
+        let mut next: Option<DueInstallment> = None;
+        
+        for plan_id in Self::user_plans(&env, &user).iter() {
+            // Closed and archived plans are skipped on their header alone
+            let plan = match Self::load_header(&env, plan_id) {
+                Ok(plan) if plan.status == PlanStatus::Active || plan.status == PlanStatus::Defaulted => plan,
+                _ => continue,
+            };
+            
+            // Installments are scheduled in order: the first pending one is the soonest
+            let pending = (1..=plan.installments_count)
+                .find(|n| plan.is_pending(*n))
+                .and_then(|number| Self::load_installment(&env, &plan, number).ok());
+            if let Some(installment) = pending {
+                if next.as_ref().map(|n| installment.due_date < n.due_date).unwrap_or(true) {
+                    next = Some(DueInstallment {
+                        plan_id,
+                        installment_number: installment.number,
+                        amount: installment.amount,
+                        due_date: installment.due_date,
+                    });
+                }
+            }
+        }
+        
+        next
+    }
+    
+    /// Page through a user's plans, oldest first
+    /// 
+    /// Returns up to `limit` IDs (capped at 50) starting at `offset`, so
+    /// wallets can walk the history of power users within budget. Only the
+    /// (at most two) buckets covering the page are read.
+    pub fn get_user_plans_page(env: Env, user: Address, offset: u32, limit: u32) -> Vec<u64> {
+        let count = Self::get_user_plan_count(env.clone(), user.clone());
+        index::bucketed_page(&env, |bucket| DataKey::UserPlans(user.clone(), bucket), count, offset, limit)
+    }
+    
+    /// Number of plans a user has created
+    pub fn get_user_plan_count(env: Env, user: Address) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::UserPlanCount(user))
+            .unwrap_or(0)
+    }
+    
+    /// Page through the plans where a merchant is the payee, oldest first
+    /// 
+    /// Stored in buckets like the user index, so a page reads at most two.
+    pub fn get_merchant_plans(env: Env, merchant: Address, offset: u32, limit: u32) -> Vec<u64> {
+        let count = Self::get_merchant_plan_count(env.clone(), merchant.clone());
+        index::bucketed_page(&env, |bucket| IndexKey::MerchantPlans(merchant.clone(), bucket), count, offset, limit)
+    }
+    
+    /// Number of plans where a merchant is the payee
+    pub fn get_merchant_plan_count(env: Env, merchant: Address) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&IndexKey::MerchantPlanCount(merchant))
+            .unwrap_or(0)
+    }
+    
+    /// Page through the plans currently in `status`
+    /// 
+    /// Lets risk dashboards and collectors enumerate e.g. defaulted plans
+    /// without scanning everything off-chain. Order is not stable across
+    /// transitions: a plan leaving a status is replaced by the last plan of
+    /// that list, so lists stay bucketed however many plans pass through.
+    pub fn get_plans_by_status(env: Env, status: PlanStatus, offset: u32, limit: u32) -> Vec<u64> {
+        let count = Self::get_status_count(env.clone(), status.clone());
+        index::bucketed_page(&env, |bucket| IndexKey::StatusPlans(status.clone(), bucket), count, offset, limit)
+    }
+    
+    /// Number of plans currently in `status`
+    pub fn get_status_count(env: Env, status: PlanStatus) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&IndexKey::StatusPlanCount(status))
+            .unwrap_or(0)
+    }
+    
+    /// Page of a plan's audit trail, oldest first
+    /// 
+    /// Append-only record of the plan's state transitions with their
+    /// timestamps and actors, 20 entries per page, so auditors don't need
+    /// to rebuild it from raw events. Pages past the end are empty; an
+    /// unknown plan fails with `PlanNotFound`.
+    pub fn get_plan_history(env: Env, plan_id: u64, page: u32) -> Result<Vec<HistoryEntry>, ContractError> {
+        Self::get_plan_history_len(env.clone(), plan_id)?;
+        Ok(Self::history_page(&env, plan_id, page))
+    }
+    
+    /// Number of entries in a plan's audit trail
+    pub fn get_plan_history_len(env: Env, plan_id: u64) -> Result<u32, ContractError> {
+        // Every plan records its creation, so an empty trail means no plan
+        match Self::history_len(&env, plan_id) {
+            0 => Err(ContractError::PlanNotFound),
+            len => Ok(len),
+        }
+    }
+    
+    /// Transitions of every plan from number `cursor` onwards, oldest first
+    /// 
+    /// Only the last `RECENT_TRANSITIONS` are kept: an indexer that was
+    /// offline catches up from its last number plus one, and a first entry
+    /// above its cursor means older ones were overwritten.
+    pub fn get_recent_transitions(env: Env, cursor: u64, limit: u32) -> Vec<Transition> {
+        let count = Self::get_transition_count(env.clone());
+        let oldest = count.saturating_sub(RECENT_TRANSITIONS as u64);
+        let start = cursor.max(oldest);
+        let end = count.min(start.saturating_add(limit.min(MAX_PAGE_SIZE) as u64));
+        
+        let mut page = Vec::new(&env);
+        for number in start..end {
+            let slot = (number % RECENT_TRANSITIONS as u64) as u32;
+            if let Some(transition) = env.storage().persistent().get(&FeedKey::Transition(slot)) {
+                page.push_back(transition);
+            }
+        }
+        page
+    }
+    
+    /// Number of transitions recorded so far (the next cursor of a caught-up indexer)
+    pub fn get_transition_count(env: Env) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&FeedKey::TransitionCount)
+            .unwrap_or(0)
+    }
+    
+    /// Repayment record of a user, for underwriting by lenders and merchants
+    pub fn get_user_credit(env: Env, user: Address) -> UserCredit {
+        let history = Self::credit_history(&env, &user);
+        let on_time_bps = if history.installments_paid > 0 {
+            history.on_time_payments as i128 * BPS_DENOMINATOR / history.installments_paid as i128
+        } else {
+            0
+        };
+        
+        UserCredit {
+            score: Self::credit_score(&history, &Self::recovery(&env, &user)),
+            history,
+            on_time_bps,
+            outstanding: Self::get_user_exposure(env, user),
+        }
+    }
+    
+    /// On-chain credit score of a user, from 0 to 1000
+    /// 
+    /// Recomputed from the repayment counters, which every collection,
+    /// completed plan and default updates: 500, plus 5 per on-time
+    /// installment and 20 per completed plan, minus 10 per late installment
+    /// and 100 per default not yet forgiven, clamped to 0-1000. Every 6
+    /// consecutive on-time installments forgive one default; a late
+    /// installment or a new default restarts the streak.
+    pub fn get_credit_score(env: Env, user: Address) -> u32 {
+        Self::credit_score(&Self::credit_history(&env, &user), &Self::recovery(&env, &user))
+    }
+    
+    /// How a user's score would move, and how far they are from having a
+    /// default forgiven
+    pub fn get_credit_standing(env: Env, user: Address) -> CreditStanding {
+        let history = Self::credit_history(&env, &user);
+        let recovery = Self::recovery(&env, &user);
+        let outstanding_defaults = history.defaults.saturating_sub(recovery.forgiven_defaults);
+        let payments_to_recover = if outstanding_defaults > 0 {
+            RECOVERY_STREAK - recovery.on_time_streak
+        } else {
+            0
+        };
+        
+        let after = |payments: u32, on_time: bool| {
+            let (mut history, mut recovery) = (history.clone(), recovery.clone());
+            for _ in 0..payments {
+                history.installments_paid += 1;
+                if on_time {
+                    history.on_time_payments += 1;
+                }
+                Self::advance_recovery(&mut recovery, history.defaults, on_time);
+            }
+            Self::credit_score(&history, &recovery)
+        };
+        
+        CreditStanding {
+            score: Self::credit_score(&history, &recovery),
+            outstanding_defaults,
+            on_time_streak: recovery.on_time_streak,
+            payments_to_recover,
+            score_after_on_time: after(1, true),
+            score_after_late: after(1, false),
+            score_after_recovery: after(payments_to_recover, true),
+        }
+    }
+    
+    /// Protocol-level counters for analytics and dashboards
+    pub fn get_stats(env: Env) -> ProtocolStats {
+        env.storage().persistent().get(&DataKey::Stats).unwrap_or_default()
+    }
+    
+    /// Pending installments past their due date, across all plans
+    /// 
+    /// Oldest first. Skips the first `cursor` overdue installments and
+    /// returns up to `limit` (capped at 50), so the collection worker can
+    /// find its work without an off-chain database. Collected and failed
+    /// installments drop out of the list.
+    pub fn get_overdue(env: Env, limit: u32, cursor: u32) -> Vec<DueInstallment> {
+        let now = env.ledger().timestamp();
+        index::due_between(&env, 0, now, cursor, limit)
+    }
+    
+    /// Pending installments due between `from_ts` and `to_ts` (inclusive)
+    /// 
+    /// Same ordering and paging as `get_overdue`, so the worker can fetch
+    /// e.g. next week's workload and pre-schedule its transactions.
+    pub fn get_due_between(
+        env: Env,
+        from_ts: u64,
+        to_ts: u64,
+        cursor: u32,
+        limit: u32,
+    ) -> Vec<DueInstallment> {
+        index::due_between(&env, from_ts, to_ts, cursor, limit)
+    }
+    
+    /// Collect an installment (called by automatic worker)
+    /// 
+    /// Attempts to collect an overdue installment. First tries from available shares,
+    /// if insufficient falls back to protected shares. If still insufficient,
+    /// marks the installment failed and plan as defaulted.
+    /// 
+    /// `caller` is either a registered collector, who may only collect due
+    /// installments, or the plan user, who may also pay early. Payment
+    /// always goes to the plan's merchant.
+    pub fn collect_installment(
+        env: Env,
+        plan_id: u64,             // Plan ID
+        installment_number: u32,     // Installment number to collect
+        caller: Address,             // Collector or plan user (must sign)
+    ) -> Result<PaymentSource, ContractError> {
+        
+        Self::when_not_paused(&env)?;
+        
+        // ===== GET AND VALIDATE PLAN =====
+        
+        let mut plan = Self::load_header(&env, plan_id)?;
+        Self::when_not_frozen(&env, plan_id)?;
+        Self::when_approved(&env, &plan)?;
+        
+        // Verify the caller: the user, or an authorized collector
+        let is_user = caller == plan.user;
+        if !is_user && !Self::is_collector(env.clone(), caller.clone()) {
+            log!(&env, "Error: Caller is not a collector");
+            return Err(ContractError::NotCollector);
+        }
+        caller.require_auth();
+        
+        let Collection {
+            mut installment,
+            config,
+            shares_needed,
+            source: payment_source,
+            payout,
+        } = Self::check_collect(&env, &plan, installment_number, is_user)?;
+        
+        // Every amount that could still fail is computed before any write
+        let fee = Self::protocol_fee(&env, &config, installment.amount)?;
+        let fee_shares = Self::protocol_fee(&env, &config, shares_needed)?;
+        
+        // Settle the rebate before the locked amount changes
+        Self::accrue_rebate(&env, &mut plan);
+        let locked_before = plan.protected_shares;
+        
+        let current_time = env.ledger().timestamp();
+        let buffer_client = BufferContractClient::new(&env, &config.buffer);
+        
+        // ===== EFFECTS: record the collection before calling the Buffer =====
+        // Checks, then effects, then interactions: the installment is Paid
+        // before any shares move, so no later refactor can leave a debited
+        // installment unpaid (a failure still rolls back the whole call)
+        
+        if payment_source.is_available() {
+            // Update protected shares proportionally
+            if plan.total_amount > 0 {
+                let shares_to_unlock = shares_needed
+                    .checked_mul(plan.total_shares)
+                    .unwrap_or(0)
+                    .checked_div(plan.total_amount)
+                    .unwrap_or(0);
+                
+                plan.protected_shares = plan.protected_shares.checked_sub(shares_to_unlock)
+                    .unwrap_or(0);
+            }
+        } else if !plan.cross_margin {
+            // Reduce plan's protected shares (cross-margin plans hold none)
+            plan.protected_shares = plan.protected_shares.checked_sub(shares_needed)
+                .unwrap_or_else(|| {
+                    log!(&env, "Error: Shares protegidos insuficientes");
+                    0
+                });
+        } else {
+            Self::add_cross_locked(&env, &plan.user, -shares_needed);
+        }
+        
+        installment.paid_at = Some(current_time);
+        installment.payment_source = Some(payment_source.to_u32());
+        installment.status = InstallmentStatus::Paid;
+        index::installment_closed(&env, plan_id, &installment);
+        
+        Self::update_stats(&env, |stats| {
+            stats.volume_collected = stats.volume_collected.saturating_add(installment.amount);
+        });
+        // A deadline past the end of time is never missed
+        let on_time = current_time <= installment.due_date.saturating_add(config.grace_period_secs);
+        Self::update_credit(&env, &plan.user, |history| {
+            history.installments_paid += 1;
+            history.total_repaid = history.total_repaid.saturating_add(installment.amount);
+            if on_time {
+                history.on_time_payments += 1;
+            }
+        });
+        Self::track_recovery(&env, &plan.user, on_time);
+        if plan.status == PlanStatus::Defaulted {
+            Self::add_merchant_defaulted(&env, &plan.merchant, -installment.amount);
+        }
+        
+        if fee > 0 {
+            let key = PlanKey::PlanFees(plan_id);
+            let fees: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+            env.storage().persistent().set(&key, &Self::add_amounts(&env, fees, fee)?);
+            Self::extend_persistent(&env, &key);
+        }
+        Self::record_settlement(&env, &plan.merchant, Settlement {
+            plan_id,
+            installment_number,
+            amount: installment.amount - fee,
+            fee,
+            timestamp: current_time,
+        });
+        
+        if plan.cross_margin {
+            Self::reduce_cross_exposure(&env, &plan.user, installment.amount);
+        }
+        Self::add_exposure(&env, &plan.user, &plan.merchant, -installment.amount);
+        
+        Self::save_installment(&env, plan_id, &installment);
+        plan.mark_paid(installment_number);
+        
+        Self::record(&env, plan_id, PlanAction::Collected, installment_number, Some(caller.clone()));
+        
+        // Plan complete: release remaining protected shares (if any), or
+        // hold them through the dispute window when a release delay is set
+        let mut unlocked_shares: i128 = 0;
+        if plan.all_paid() {
+            Self::set_status(&env, &mut plan, PlanStatus::Completed);
+            Self::record(&env, plan_id, PlanAction::Completed, 0, Some(caller.clone()));
+            Self::update_credit(&env, &plan.user, |history| history.plans_completed += 1);
+            
+            // Fully repaid: the fund no longer underwrites this plan
+            Self::release_insured(&env, &plan)?;
+            
+            let release_delay = Self::get_release_delay(env.clone());
+            if plan.protected_shares > 0 && release_delay > 0 {
+                plan.release_at = current_time.saturating_add(release_delay);
+                log!(&env, "Holding {} shares until {}", plan.protected_shares, plan.release_at);
+            } else if plan.protected_shares > 0 {
+                unlocked_shares = plan.protected_shares;
+                plan.protected_shares = 0;
+            }
+        }
+        
+        Self::save_plan(&env, &plan);
+        
+        // ===== INTERACTIONS: move the shares =====
+        
+        let from_protected = !payment_source.is_available();
+        Self::debit_installment(&buffer_client, &config, &plan.user, shares_needed, fee_shares, &payout, from_protected);
+        log!(&env, "Collected {} shares (protected: {})", shares_needed, from_protected);
+        
+        if unlocked_shares > 0 {
+            buffer_client.unlock_shares(&plan.user, &unlocked_shares);
+            log!(&env, "Released {} remaining shares", unlocked_shares);
+        }
+        
+        // ===== EMITIR EVENTO =====
+        
+        events::installment_paid(&env, InstallmentPaid {
+            plan_id,
+            installment_number,
+            amount: installment.amount,
+            fee,
+            payment_source,
+            shares_used: shares_needed,
+            collector: caller,
+            paid_at: current_time,
+        });
+        events::merchant_settled(&env, MerchantSettled {
+            plan_id,
+            merchant: plan.merchant.clone(),
+            payout,
+            installments: Vec::from_array(&env, [installment_number]),
+            gross: installment.amount,
+            fee,
+            amount: installment.amount - fee,
+            settled_at: current_time,
+        });
+        if plan.protected_shares < locked_before {
+            events::collateral_unlocked(&env, CollateralChanged {
+                plan_id,
+                user: plan.user.clone(),
+                shares: locked_before - plan.protected_shares,
+                locked_before,
+                locked_after: plan.protected_shares,
+            });
+        }
+        if plan.status == PlanStatus::Completed {
+            events::plan_completed(&env, PlanCompleted {
+                plan_id,
+                user: plan.user,
+                merchant: plan.merchant,
+                total_paid: plan.total_amount,
+                completed_at: current_time,
+                unlocked_shares,
+                release_at: plan.release_at,
+            });
+        }
+        
+        Ok(payment_source)
+    }
+    
+    /// Dry-run `collect_installment` as a collector would call it
+    /// 
+    /// Runs every validation and balance check without debiting or
+    /// writing anything, and returns the source the collection would use
+    /// or the error it would fail with, so workers can skip doomed
+    /// transactions.
+    pub fn simulate_collect(
+        env: Env,
+        plan_id: u64,
+        installment_number: u32,
+    ) -> Result<PaymentSource, ContractError> {
+        Self::when_not_paused(&env)?;
+        
+        let plan = Self::load_header(&env, plan_id)?;
+        Self::when_not_frozen(&env, plan_id)?;
+        Self::when_approved(&env, &plan)?;
+        
+        Ok(Self::check_collect(&env, &plan, installment_number, false)?.source)
+    }
+    
+    /// Flag a past-due installment as overdue (called by a keeper)
+    /// 
+    /// Gives wallets a push signal to nudge the user before the debit or
+    /// the default. The installment stays collectable. Permissionless, since
+    /// the due date is checked here.
+    pub fn mark_overdue(
+        env: Env,
+        plan_id: u64,
+        installment_number: u32,
+    ) -> Result<(), ContractError> {
+        let plan = Self::load_header(&env, plan_id)?;
+        Self::when_approved(&env, &plan)?;
+        
+        if installment_number == 0 || installment_number > plan.installments_count {
+            log!(&env, "Error: Installment not found {}", installment_number);
+            return Err(ContractError::InstallmentNotFound);
+        }
+        
+        let mut installment = Self::load_installment(&env, &plan, installment_number)?;
+        if installment.status != InstallmentStatus::Pending {
+            log!(&env, "Error: Installment already processed {}", installment_number);
+            return Err(ContractError::AlreadyPaid);
+        }
+        
+        let current_time = env.ledger().timestamp();
+        if current_time <= installment.due_date {
+            log!(&env, "Error: Installment not yet due {}", installment_number);
+            return Err(ContractError::NotDueYet);
+        }
+        
+        installment.status = InstallmentStatus::Overdue;
+        Self::save_installment(&env, plan_id, &installment);
+        
+        events::installment_overdue(&env, InstallmentOverdue {
+            plan_id,
+            installment_number,
+            user: plan.user,
+            amount: installment.amount,
+            due_date: installment.due_date,
+            marked_at: current_time,
+        });
+        
+        Ok(())
+    }
+    
+    /// Declare a due installment as failed (called by automatic worker)
+    /// 
+    /// Re-checks on-chain that the installment is due and that neither
+    /// available nor protected shares can cover it, then marks the
+    /// installment failed and the plan as defaulted. Permissionless, since
+    /// the default condition is fully verified here.
+    pub fn declare_default(
+        env: Env,
+        plan_id: u64,             // Plan ID
+        installment_number: u32,     // Installment that cannot be collected
+    ) -> Result<(), ContractError> {
+        let mut plan = Self::load_header(&env, plan_id)?;
+        Self::when_not_frozen(&env, plan_id)?;
+        Self::when_approved(&env, &plan)?;
+        
+        if installment_number == 0 || installment_number > plan.installments_count {
+            log!(&env, "Error: Installment not found {}", installment_number);
+            return Err(ContractError::InstallmentNotFound);
+        }
+        
+        if !plan.is_pending(installment_number) {
+            log!(&env, "Error: Installment already processed {}", installment_number);
+            return Err(ContractError::AlreadyPaid);
+        }
+        
+        let mut installment = Self::load_installment(&env, &plan, installment_number)?;
+        
+        // The grace period gives the user time to top up before a default
+        let config = Self::get_config(env.clone())?;
+        if env.ledger().timestamp() < installment.due_date.saturating_add(config.grace_period_secs) {
+            log!(&env, "Error: Installment not yet due {}", installment_number);
+            return Err(ContractError::NotDueYet);
+        }
+        
+        let buffer_client = BufferContractClient::new(&env, &config.buffer);
+        let shares_needed = buffer_client.shares_for_amount(&installment.amount);
+        let balance = buffer_client.get_balance(&plan.user);
+        
+        let spendable = Self::spendable_available(&env, &buffer_client, &plan.user, &balance);
+        
+        // Collection would still succeed: not a default
+        if spendable >= shares_needed || balance.protected_shares >= shares_needed {
+            log!(&env, "Error: Installment {} is still collectable", installment_number);
+            return Err(ContractError::StillCollectable);
+        }
+        
+        // Best single source falls short by this share of the installment
+        let covered = if spendable > balance.protected_shares { spendable } else { balance.protected_shares };
+        let shortfall = if shares_needed > 0 {
+            installment.amount * (shares_needed - covered.max(0)) / shares_needed
+        } else {
+            0
+        };
+        
+        installment.status = InstallmentStatus::Failed;
+        index::installment_closed(&env, plan_id, &installment);
+        Self::save_installment(&env, plan_id, &installment);
+        plan.mark_failed(installment_number);
+        
+        Self::record(&env, plan_id, PlanAction::Failed, installment_number, None);
+        events::installment_failed(&env, InstallmentFailed {
+            plan_id,
+            installment_number,
+            amount: installment.amount,
+            due_date: installment.due_date,
+        });
+        
+        if plan.status != PlanStatus::Defaulted {
+            let unpaid = Self::unpaid_amount(&env, &plan)?;
+            Self::add_merchant_defaulted(&env, &plan.merchant, unpaid);
+            Self::set_status(&env, &mut plan, PlanStatus::Defaulted);
+            Self::record(&env, plan_id, PlanAction::Defaulted, installment_number, None);
+            Self::update_credit(&env, &plan.user, |history| history.defaults += 1);
+            Self::track_recovery(&env, &plan.user, false);
+            Self::update_incidents(&env, &plan.merchant, |incidents| incidents.defaults += 1);
+            events::plan_defaulted(&env, PlanDefaulted {
+                plan_id,
+                user: plan.user.clone(),
+                merchant: plan.merchant.clone(),
+                installment_number,
+                shortfall,
+                unpaid_amount: unpaid,
+                defaulted_at: env.ledger().timestamp(),
+            });
+        }
+        
+        Self::save_plan(&env, &plan);
+        
+        log!(&env, "Plan defaulted on installment {}", installment_number);
+        
+        Ok(())
+    }
+    
+    /// Start a Dutch auction for the collateral of a defaulted plan
+    /// 
+    /// Called by the merchant. Bids are paid in the Buffer's underlying
+    /// asset, the one plan amounts are denominated in. The whole locked lot
+    /// is offered at a price that starts above the outstanding debt and
+    /// decays linearly to a floor, so a volatile collateral asset gets
+    /// priced by the market instead of at a fixed rate.
+    pub fn start_auction(
+        env: Env,
+        plan_id: u64,             // Defaulted plan
+    ) -> Result<Auction, ContractError> {
+        let plan = Self::load_header(&env, plan_id)?;
+        Self::when_not_frozen(&env, plan_id)?;
+        
+        plan.merchant.require_auth();
+        
+        if plan.status != PlanStatus::Defaulted {
+            log!(&env, "Error: Plan is not defaulted");
+            return Err(ContractError::PlanNotDefaulted);
+        }
+        
+        if env.storage().persistent().has(&PlanKey::Auction(plan_id)) {
+            log!(&env, "Error: Auction already started");
+            return Err(ContractError::AuctionExists);
+        }
+        
+        let debt_amount = Self::unpaid_amount(&env, &plan)?;
+        
+        let buffer_client = Self::buffer_client(&env)?;
+        let collateral_shares = Self::auction_lot(&env, &buffer_client, &plan, debt_amount);
+        
+        if collateral_shares <= 0 {
+            log!(&env, "Error: No collateral to auction");
+            return Err(ContractError::NoCollateral);
+        }
+        
+        let auction = Auction {
+            plan_id,
+            debt_token: buffer_client.get_asset(),
+            collateral_shares,
+            debt_amount,
+            start_price: Self::mul_div(&env, debt_amount, AUCTION_START_PRICE_BPS, BPS_DENOMINATOR)?,
+            floor_price: Self::mul_div(&env, debt_amount, AUCTION_FLOOR_PRICE_BPS, BPS_DENOMINATOR)?,
+            started_at: env.ledger().timestamp(),
+            duration: AUCTION_DURATION_SECS,
+            settled: false,
+        };
+        
+        let key = PlanKey::Auction(plan_id);
+        env.storage().persistent().set(&key, &auction);
+        Self::extend_persistent(&env, &key);
+        
+        events::auction_started(&env, AuctionStarted {
+            plan_id,
+            debt_token: auction.debt_token.clone(),
+            collateral_shares,
+            debt_amount,
+            start_price: auction.start_price,
+            floor_price: auction.floor_price,
+            ends_at: auction.started_at.saturating_add(auction.duration),
+        });
+        
+        Ok(auction)
+    }
+    
+    /// Query the auction of a plan
+    pub fn get_auction(env: Env, plan_id: u64) -> Result<Auction, ContractError> {
+        env.storage()
+            .persistent()
+            .get(&PlanKey::Auction(plan_id))
+            .ok_or(ContractError::AuctionNotFound)
+    }
+    
+    /// Current price of an auction lot, in debt tokens
+    pub fn get_auction_price(env: Env, plan_id: u64) -> Result<i128, ContractError> {
+        let auction = Self::get_auction(env.clone(), plan_id)?;
+        Self::auction_price(&env, &auction, env.ledger().timestamp())
+    }
+    
+    /// Buy the collateral of an auction at the current price
+    /// 
+    /// The bidder pays the current price in the debt token: the merchant
+    /// receives up to the debt still unpaid at bid time (the user may have
+    /// repaid part of it since the auction started) and any surplus goes
+    /// back to the user. If the price falls short of the debt on an insured
+    /// plan, the insurance fund pays the rest up to the insured amount, out
+    /// of what it has approved the bridge to spend. The seized shares, what
+    /// the plan still has locked at bid time, are debited from the user's
+    /// protected balance straight to the bidder.
+    pub fn bid(
+        env: Env,
+        plan_id: u64,             // Plan being auctioned
+        bidder: Address,             // Liquidator buying the lot
+        max_price: i128,             // Highest price the bidder accepts
+    ) -> Result<i128, ContractError> {
+        bidder.require_auth();
+        Self::when_not_frozen(&env, plan_id)?;
+        
+        let mut auction = Self::get_auction(env.clone(), plan_id)?;
+        let mut plan = Self::load_header(&env, plan_id)?;
+        
+        // Also covers settled auctions, whose plan is already liquidated
+        if plan.status != PlanStatus::Defaulted {
+            log!(&env, "Error: Plan is not defaulted");
+            return Err(ContractError::PlanNotDefaulted);
+        }
+        
+        let price = Self::auction_price(&env, &auction, env.ledger().timestamp())?;
+        
+        if price > max_price {
+            log!(&env, "Error: Price {} above bidder max {}", price, max_price);
+            return Err(ContractError::PriceAboveMax);
+        }
+        
+        Self::accrue_rebate(&env, &mut plan);
+        
+        // Collections keep running on a defaulted plan, so the debt and the
+        // locked lot may have shrunk since the auction started
+        let debt = Self::unpaid_amount(&env, &plan)?;
+        let buffer_client = Self::buffer_client(&env)?;
+        auction.collateral_shares = Self::auction_lot(&env, &buffer_client, &plan, debt);
+        
+        if auction.collateral_shares <= 0 {
+            log!(&env, "Error: No collateral left to auction");
+            return Err(ContractError::NoCollateral);
+        }
+        
+        // ===== PAY THE DEBT =====
+        
+        let token_client = TokenClient::new(&env, &auction.debt_token);
+        let to_merchant = if price < debt { price } else { debt };
+        let surplus = price - to_merchant;
+        let cover = Self::insured_cover(&env, &token_client, &plan, debt - to_merchant);
+        let from_fund = cover.as_ref().map(|(_, amount)| *amount).unwrap_or(0);
+        let settled = to_merchant + from_fund;
+        
+        if settled > 0 {
+            let payout = Self::merchant_payout(&env, &plan.merchant)?;
+            if to_merchant > 0 {
+                token_client.transfer(&bidder, &payout, &to_merchant);
+            }
+            if let Some((fund, amount)) = &cover {
+                token_client.transfer_from(&env.current_contract_address(), fund, &payout, amount);
+                events::notice(&env, (symbol_short!("ins_paid"), plan_id, fund.clone(), *amount));
+            }
+            Self::record_settlement(&env, &plan.merchant, Settlement {
+                plan_id,
+                installment_number: 0,
+                amount: settled,
+                fee: 0,
+                timestamp: env.ledger().timestamp(),
+            });
+            
+            let mut installments = Vec::new(&env);
+            for number in 1..=plan.installments_count {
+                if !plan.is_paid(number) {
+                    installments.push_back(number);
+                }
+            }
+            events::merchant_settled(&env, MerchantSettled {
+                plan_id,
+                merchant: plan.merchant.clone(),
+                payout,
+                installments,
+                gross: debt,
+                fee: 0,
+                amount: settled,
+                settled_at: env.ledger().timestamp(),
+            });
+        }
+        if surplus > 0 {
+            token_client.transfer(&bidder, &plan.user, &surplus);
+        }
+        
+        // ===== HAND OVER THE COLLATERAL =====
+        
+        buffer_client.debit_protected(&plan.user, &auction.collateral_shares, &bidder);
+        let locked_before = plan.protected_shares;
+        
+        if plan.cross_margin {
+            Self::reduce_cross_exposure(&env, &plan.user, debt);
+            Self::add_cross_locked(&env, &plan.user, -auction.collateral_shares);
+        } else {
+            plan.protected_shares -= auction.collateral_shares;
+        }
+        Self::add_exposure(&env, &plan.user, &plan.merchant, -debt);
+        Self::add_merchant_defaulted(&env, &plan.merchant, -debt);
+        Self::release_insured(&env, &plan)?;
+        Self::set_status(&env, &mut plan, PlanStatus::Liquidated);
+        Self::record(&env, plan_id, PlanAction::Liquidated, 0, Some(bidder.clone()));
+        
+        // The auction settled the debt: nothing is left to collect
+        for number in 1..=plan.installments_count {
+            if plan.is_pending(number) {
+                let mut installment = Self::load_installment(&env, &plan, number)?;
+                installment.status = InstallmentStatus::Failed;
+                index::installment_closed(&env, plan_id, &installment);
+                Self::save_installment(&env, plan_id, &installment);
+                plan.mark_failed(number);
+            }
+        }
+        auction.settled = true;
+        
+        Self::save_plan(&env, &plan);
+        env.storage().persistent().set(&PlanKey::Auction(plan_id), &auction);
+        Self::extend_persistent(&env, &PlanKey::Auction(plan_id));
+        
+        events::plan_liquidated(&env, PlanLiquidated {
+            plan_id,
+            bidder,
+            price,
+            debt_repaid: settled,
+            collateral_shares: auction.collateral_shares,
+        });
+        events::collateral_unlocked(&env, CollateralChanged {
+            plan_id,
+            user: plan.user,
+            shares: auction.collateral_shares,
+            locked_before,
+            locked_after: plan.protected_shares,
+        });
+        
+        Ok(price)
+    }
+    
+    /// Page through a plan's installments in schedule order
+    pub fn get_installments(
+        env: Env,
+        plan_id: u64,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<Installment>, ContractError> {
+        let plan = Self::load_header(&env, plan_id)?;
+        
+        // Only the rows of the page are read
+        let start = offset.min(plan.installments_count);
+        let end = start.saturating_add(limit.min(MAX_PAGE_SIZE)).min(plan.installments_count);
+        let mut installments = Vec::new(&env);
+        for number in start + 1..=end {
+            installments.push_back(Self::load_installment(&env, &plan, number)?);
+        }
+        Ok(installments)
+    }
+    
+    /// Get the installments of a plan in a given status, in schedule order
+    pub fn get_installments_by_status(
+        env: Env,
+        plan_id: u64,
+        status: InstallmentStatus,
+    ) -> Result<Vec<Installment>, ContractError> {
+        let plan = Self::load_header(&env, plan_id)?;
+        
+        // The bitmaps narrow the scan; only candidate rows are read
+        let mut installments = Vec::new(&env);
+        for number in 1..=plan.installments_count {
+            let candidate = match status {
+                InstallmentStatus::Paid => plan.is_paid(number),
+                InstallmentStatus::Failed => plan.is_failed(number),
+                InstallmentStatus::Pending | InstallmentStatus::Overdue => plan.is_pending(number),
+            };
+            if !candidate {
+                continue;
+            }
+            let installment = Self::load_installment(&env, &plan, number)?;
+            if installment.status == status {
+                installments.push_back(installment);
+            }
+        }
+        Ok(installments)
+    }
+    
+    /// Get a single installment by number (1-based)
+    pub fn get_installment(env: Env, plan_id: u64, number: u32) -> Result<Installment, ContractError> {
+        let header = Self::load_header(&env, plan_id)?;
+        
+        if number == 0 || number > header.installments_count {
+            log!(&env, "Error: Installment not found {}", number);
+            return Err(ContractError::InstallmentNotFound);
+        }
+        Self::load_installment(&env, &header, number)
+    }
+    
+    /// Get the next due installment of a plan
+    /// 
+    /// Searches for the first installment that is pending and already due.
+    /// Useful for automatic workers that process collections.
+    pub fn get_next_due(env: Env, plan_id: u64) -> Result<Option<Installment>, ContractError> {
+        let plan = Self::load_header(&env, plan_id)?;
+        
+        let current_time = env.ledger().timestamp();
+        
+        // Search for first pending and due installment; due dates come from
+        // the schedule, so only the match is read
+        for number in 1..=plan.installments_count {
+            if plan.is_pending(number) && Self::due_date(&env, &plan, number) <= current_time {
+                return Ok(Some(Self::load_installment(&env, &plan, number)?));
+            }
+        }
+        
+        // No due installments
+        Ok(None)
+    }
+    
+    /// Get plan summary with derived fields and updated Buffer values
+    /// 
+    /// Outstanding balance, paid count, next pending installment, fees
+    /// withheld and progress in one small struct, along with the current
+    /// token values of the user's Buffer (available and protected), so
+    /// wallets don't have to fetch and recompute the installments.
+    pub fn get_plan_summary(
+        env: Env, 
+        plan_id: u64
+    ) -> Result<PlanSummary, ContractError> {
+        let plan = Self::load_header(&env, plan_id)?;
+        
+        // Counts come from the bitmaps; only unpaid installments are read
+        let outstanding = Self::unpaid_amount(&env, &plan)?;
+        let paid_amount = plan.total_amount - outstanding;
+        let paid_count = plan.paid_count();
+        let next_due = match (1..=plan.installments_count).find(|n| plan.is_pending(*n)) {
+            Some(number) => Some(Self::load_installment(&env, &plan, number)?),
+            None => None,
+        };
+        
+        let progress_bps = if plan.total_amount > 0 {
+            Self::ratio_bps(paid_amount, plan.total_amount)
+        } else {
+            0
+        };
+        
+        let buffer_client = Self::buffer_client(&env)?;
+        let (available_value, protected_value, _total_value) = buffer_client.get_values(&plan.user);
+        
+        Ok(PlanSummary {
+            status: plan.status,
+            total_amount: plan.total_amount,
+            outstanding,
+            paid_count,
+            installments_count: plan.installments_count,
+            next_due_number: next_due.as_ref().map(|i| i.number),
+            next_due_date: next_due.as_ref().map(|i| i.due_date),
+            next_due_amount: next_due.as_ref().map(|i| i.amount),
+            fees_paid: env.storage().persistent().get(&PlanKey::PlanFees(plan_id)).unwrap_or(0),
+            progress_bps,
+            available_value,
+            protected_value,
+        })
+    }
+
+    /// Get the health of a plan against the user's current Buffer value
+    /// 
+    /// Compares the outstanding balance with the Buffer value backing it,
+    /// using the same LTV model as `create_plan`. Collateral is priced by
+    /// the Buffer's own share value (single-asset vault), so no oracle is needed.
+    /// `at_risk` flips once the LTV crosses the liquidation threshold, giving
+    /// wallets a chance to warn the user before a debit fails.
+    pub fn get_health(
+        env: Env,
+        plan_id: u64
+    ) -> Result<PlanHealth, ContractError> {
+        let plan = Self::load_header(&env, plan_id)?;
+        
+        // Outstanding = every installment not yet paid (pending or failed)
+        let outstanding_amount = Self::unpaid_amount(&env, &plan)?;
+        
+        let config = Self::get_config(env.clone())?;
+        let buffer_client = BufferContractClient::new(&env, &config.buffer);
+        let (_, protected_value, collateral_value) = buffer_client.get_values(&plan.user);
+        let balance = buffer_client.get_balance(&plan.user);
+        
+        // Value of this plan's share of the user's protected balance
+        let locked_value = if balance.protected_shares > 0 {
+            protected_value
+                .checked_mul(plan.protected_shares)
+                .unwrap_or(0)
+                .checked_div(balance.protected_shares)
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        
+        let (ltv_bps, health_factor_bps) = if outstanding_amount == 0 {
+            // Nothing left to collect: the plan can no longer be unhealthy
+            (0, i128::MAX)
+        } else if collateral_value <= 0 {
+            (i128::MAX, 0)
+        } else {
+            (
+                Self::ratio_bps(outstanding_amount, collateral_value),
+                collateral_value
+                    .checked_mul(config.liquidation_threshold_bps)
+                    .map_or(i128::MAX, |weighted| weighted / outstanding_amount),
+            )
+        };
+        
+        Ok(PlanHealth {
+            outstanding_amount,
+            locked_shares: plan.protected_shares,
+            locked_value,
+            collateral_value,
+            ltv_bps,
+            health_factor_bps,
+            at_risk: health_factor_bps < BPS_DENOMINATOR,
+        })
+    }
+
+    }
+
+impl BridgeContract {
+    fn buffer_client(env: &Env) -> Result<BufferContractClient<'_>, ContractError> {
+        let buffer = Self::get_buffer(env.clone())?;
+        Ok(BufferContractClient::new(env, &buffer))
+    }
+    
+    fn require_admin(env: &Env) -> Result<Address, ContractError> {
+        let admin = Self::admin(env.clone())?;
+        admin.require_auth();
+        Ok(admin)
+    }
+    
+    /// Validate a parameter change and queue it behind the current timelock
+    fn queue_change(env: &Env, change: ParamChange) -> Result<u32, ContractError> {
+        Self::validate_change(env, &change)?;
+        
+        let change_id: u32 = env.storage()
+            .instance()
+            .get(&DataKey::ChangeCounter)
+            .unwrap_or(0) + 1;
+        let delay = Self::get_timelock_delay(env.clone());
+        let eta = env.ledger().timestamp().checked_add(delay).ok_or_else(|| {
+            log!(env, "Error: Timelock of {} seconds overflows", delay);
+            ContractError::InvalidConfig
+        })?;
+        
+        env.storage().instance().set(&DataKey::ChangeCounter, &change_id);
+        env.storage()
+            .instance()
+            .set(&DataKey::PendingChange(change_id), &PendingChange { change, eta });
+        
+        events::notice(env, (symbol_short!("chg_queue"), change_id, eta));
+        
+        Ok(change_id)
+    }
+    
+    fn validate_change(env: &Env, change: &ParamChange) -> Result<(), ContractError> {
+        match change {
+            ParamChange::CollateralFactor(_, factor_bps) => {
+                if !(DEFAULT_COLLATERAL_FACTOR_BPS..=MAX_COLLATERAL_FACTOR_BPS).contains(factor_bps) {
+                    log!(env, "Error: Invalid collateral factor {}", *factor_bps);
+                    return Err(ContractError::InvalidConfig);
+                }
+            }
+            ParamChange::Underwriting(config) => {
+                if config.lock_ratio_bps <= 0 
+                    || config.lock_ratio_bps > BPS_DENOMINATOR 
+                    || config.fund_capacity < 0 {
+                    log!(env, "Error: Invalid underwriting config");
+                    return Err(ContractError::InvalidConfig);
+                }
+            }
+            ParamChange::ReleaseDelay(delay_secs) => {
+                if *delay_secs > MAX_RELEASE_DELAY_SECS {
+                    log!(env, "Error: Release delay too long {}", *delay_secs);
+                    return Err(ContractError::InvalidConfig);
+                }
+            }
+            ParamChange::Rebate(config) => {
+                if config.rate_bps < 0 || config.rate_bps > BPS_DENOMINATOR {
+                    log!(env, "Error: Invalid rebate rate {}", config.rate_bps);
+                    return Err(ContractError::InvalidAmount);
+                }
+            }
+            ParamChange::Config(config) => {
+                if config.max_installments == 0
+                    || config.max_installments > MAX_INSTALLMENTS_LIMIT
+                    || config.min_due_spacing_secs > MAX_DUE_SPACING_SECS
+                    || config.due_tolerance_secs > MAX_DUE_TOLERANCE_SECS
+                    || config.min_installment_amount < 0 {
+                    log!(env, "Error: Invalid config");
+                    return Err(ContractError::InvalidConfig);
+                }
+                let curve = &config.credit_curve;
+                if curve.base_limit < 0
+                    || curve.volume_bps < 0
+                    || curve.tenure_amount < 0
+                    || curve.default_penalty_bps < 0
+                    || curve.default_penalty_bps > BPS_DENOMINATOR
+                    || curve.max_limit < curve.base_limit {
+                    log!(env, "Error: Invalid credit curve");
+                    return Err(ContractError::InvalidConfig);
+                }
+                Self::validate_tiers(env, &config.collateral_tiers)?;
+                Self::validate_fee(env, config.fee_bps, &config.fee_recipient)?;
+                Self::validate_risk_params(env, config.max_ltv_bps, config.liquidation_threshold_bps)?;
+            }
+            ParamChange::TimelockDelay(delay_secs) => {
+                if *delay_secs > MAX_TIMELOCK_DELAY_SECS {
+                    log!(env, "Error: Timelock delay too long {}", *delay_secs);
+                    return Err(ContractError::InvalidConfig);
+                }
+            }
+            ParamChange::Fee(fee_bps, fee_recipient) => {
+                Self::validate_fee(env, *fee_bps, fee_recipient)?;
+            }
+            ParamChange::RiskParams(max_ltv_bps, liquidation_threshold_bps) => {
+                Self::validate_risk_params(env, *max_ltv_bps, *liquidation_threshold_bps)?;
+            }
+        }
+        Ok(())
+    }
+    
+    fn validate_fee(env: &Env, fee_bps: i128, fee_recipient: &Option<Address>) -> Result<(), ContractError> {
+        if !(0..=MAX_FEE_BPS).contains(&fee_bps) || (fee_bps > 0 && fee_recipient.is_none()) {
+            log!(env, "Error: Invalid fee {}", fee_bps);
+            return Err(ContractError::InvalidConfig);
+        }
+        Ok(())
+    }
+    
+    fn validate_risk_params(
+        env: &Env,
+        max_ltv_bps: i128,
+        liquidation_threshold_bps: i128,
+    ) -> Result<(), ContractError> {
+        if max_ltv_bps <= 0
+            || max_ltv_bps > BPS_DENOMINATOR
+            || liquidation_threshold_bps < max_ltv_bps
+            || liquidation_threshold_bps > BPS_DENOMINATOR {
+            log!(env, "Error: Invalid risk params {} / {}", max_ltv_bps, liquidation_threshold_bps);
+            return Err(ContractError::InvalidConfig);
+        }
+        Ok(())
+    }
+    
+    /// Role allowed to queue (and cancel) a parameter change
+    fn change_role(change: &ParamChange) -> Role {
+        match change {
+            ParamChange::CollateralFactor(..)
+            | ParamChange::Underwriting(_)
+            | ParamChange::Rebate(_)
+            | ParamChange::Fee(..)
+            | ParamChange::RiskParams(..) => Role::Treasurer,
+            ParamChange::ReleaseDelay(_) => Role::Arbiter,
+            ParamChange::Config(_) | ParamChange::TimelockDelay(_) => Role::Admin,
+        }
+    }
+    
+    fn apply_change(env: &Env, change: ParamChange) -> Result<(), ContractError> {
+        match change {
+            ParamChange::CollateralFactor(asset, factor_bps) => {
+                env.storage()
+                    .persistent()
+                    .set(&DataKey::CollateralFactor(asset.clone()), &factor_bps);
+                events::notice(env, (symbol_short!("cfactor"), asset, factor_bps));
+            }
+            ParamChange::Underwriting(config) => {
+                env.storage().instance().set(&DataKey::Underwriting, &config);
+                events::notice(env, (
+                    symbol_short!("uw_set"),
+                    config.insurance_fund,
+                    config.min_score,
+                    config.lock_ratio_bps,
+                    config.fund_capacity,
+                ));
+            }
+            ParamChange::ReleaseDelay(delay_secs) => {
+                env.storage().instance().set(&DataKey::ReleaseDelay, &delay_secs);
+                events::notice(env, (symbol_short!("rel_delay"), delay_secs));
+            }
+            ParamChange::Rebate(config) => {
+                env.storage().instance().set(&DataKey::RebateConfig, &config);
+                events::notice(env, (symbol_short!("rebate_cf"), config.token, config.rate_bps));
+            }
+            ParamChange::Config(config) => {
+                env.storage().instance().set(&DataKey::Config, &config);
+                events::notice(env, (symbol_short!("config"), config.buffer));
+            }
+            ParamChange::TimelockDelay(delay_secs) => {
+                env.storage().instance().set(&DataKey::TimelockDelay, &delay_secs);
+                events::notice(env, (symbol_short!("timelock"), delay_secs));
+            }
+            ParamChange::Fee(fee_bps, fee_recipient) => {
+                let mut config = Self::get_config(env.clone())?;
+                config.fee_bps = fee_bps;
+                config.fee_recipient = fee_recipient.clone();
+                env.storage().instance().set(&DataKey::Config, &config);
+                events::notice(env, (symbol_short!("fee_set"), fee_bps, fee_recipient));
+            }
+            ParamChange::RiskParams(max_ltv_bps, liquidation_threshold_bps) => {
+                let mut config = Self::get_config(env.clone())?;
+                config.max_ltv_bps = max_ltv_bps;
+                config.liquidation_threshold_bps = liquidation_threshold_bps;
+                env.storage().instance().set(&DataKey::Config, &config);
+                events::notice(env, (symbol_short!("risk_set"), max_ltv_bps, liquidation_threshold_bps));
+            }
+        }
+        Ok(())
+    }
+    
+    fn require_role(env: &Env, caller: &Address, role: Role) -> Result<(), ContractError> {
+        Self::admin(env.clone())?;
+        caller.require_auth();
+        
+        if !Self::has_role(env.clone(), role, caller.clone()) {
+            log!(env, "Error: Caller lacks the required role");
+            return Err(ContractError::Unauthorized);
+        }
+        Ok(())
+    }
+    
+    fn set_merchant_status(
+        env: &Env,
+        merchant: Address,
+        status: MerchantStatus,
+    ) -> Result<(), ContractError> {
+        let mut profile = Self::get_merchant(env.clone(), merchant.clone())?;
+        profile.status = status.clone();
+        env.storage()
+            .persistent()
+            .set(&DataKey::Merchant(merchant.clone()), &profile);
+        
+        events::notice(env, (symbol_short!("merchant"), merchant, status));
+        
+        Ok(())
+    }
+    
+    /// Address receiving a merchant's payments (the merchant itself if unregistered)
+    /// 
+    /// Settlements to a blacklisted merchant or payout address are refused.
+    fn merchant_payout(env: &Env, merchant: &Address) -> Result<Address, ContractError> {
+        let payout = Self::get_merchant(env.clone(), merchant.clone())
+            .map(|profile| profile.payout)
+            .unwrap_or(merchant.clone());
+        
+        if Self::is_blacklisted(env.clone(), merchant.clone())
+            || Self::is_blacklisted(env.clone(), payout.clone()) {
+            log!(env, "Error: Merchant is blacklisted");
+            return Err(ContractError::Blacklisted);
+        }
+        Ok(payout)
+    }
+    
+    fn when_not_frozen(env: &Env, plan_id: u64) -> Result<(), ContractError> {
+        if Self::is_frozen(env.clone(), plan_id) {
+            log!(env, "Error: Plan is frozen");
+            return Err(ContractError::PlanFrozen);
+        }
+        Ok(())
+    }
+    
+    /// Move a plan to `status`, keeping the status index in sync
+    fn set_status(env: &Env, plan: &mut PlanHeader, status: PlanStatus) {
+        index::status_changed(env, plan.plan_id, &plan.status, &status);
+        events::status_changed(env, plan.plan_id, &plan.status, &status);
+        
+        if status == PlanStatus::Completed || status == PlanStatus::Liquidated {
+            let key = PlanKey::ClosedAt(plan.plan_id);
+            env.storage().persistent().set(&key, &env.ledger().timestamp());
+            Self::extend_persistent(env, &key);
+        }
+        
+        // Counters saturate: plans migrated from older layouts were never
+        // counted, and a reporting figure must not block their collections
+        let previous = plan.status.clone();
+        Self::update_stats(env, |stats| {
+            if previous == PlanStatus::Active {
+                stats.active_plans = stats.active_plans.saturating_sub(1);
+            }
+            match status {
+                PlanStatus::Active => stats.active_plans = stats.active_plans.saturating_add(1),
+                PlanStatus::Completed => stats.completed_plans = stats.completed_plans.saturating_add(1),
+                PlanStatus::Defaulted => stats.defaulted_plans = stats.defaulted_plans.saturating_add(1),
+                _ => {}
+            }
+        });
+        
+        plan.status = status;
+    }
+    
+    /// Append a transition to the plan's audit trail
+    fn history_page(env: &Env, plan_id: u64, page: u32) -> Vec<HistoryEntry> {
+        env.storage()
+            .persistent()
+            .get(&PlanKey::PlanHistory(plan_id, page))
+            .unwrap_or(Vec::new(env))
+    }
+    
+    fn history_len(env: &Env, plan_id: u64) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&PlanKey::PlanHistoryLen(plan_id))
+            .unwrap_or(0)
+    }
+    
+    /// Headers of a user's plans that are not archived
+    fn live_plans(env: &Env, user: &Address) -> Vec<PlanHeader> {
+        let mut plans = Vec::new(env);
+        for plan_id in Self::user_plans(env, user).iter() {
+            if let Ok(plan) = Self::load_header(env, plan_id) {
+                plans.push_back(plan);
+            }
+        }
+        plans
+    }
+    
+    /// Protected shares `plans` and the user's cross-margin exposure need
+    /// locked (active, defaulted, or completed with a pending release)
+    fn required_locks(
+        env: &Env,
+        buffer_client: &BufferContractClient,
+        user: &Address,
+        plans: &Vec<PlanHeader>,
+    ) -> Result<i128, ContractError> {
+        let mut required: i128 = 0;
+        for plan in plans.iter() {
+            if plan.status != PlanStatus::Liquidated {
+                required += plan.protected_shares;
+            }
+        }
+        
+        // The shared cross-margin lock must keep covering its exposure
+        required += Self::cross_required_shares(env, buffer_client, user)?;
+        Ok(required)
+    }
+    
+    /// Shares the shared cross-margin lock needs for the user's exposure
+    fn cross_required_shares(
+        env: &Env,
+        buffer_client: &BufferContractClient,
+        user: &Address,
+    ) -> Result<i128, ContractError> {
+        let cross_exposure = Self::get_cross_exposure(env.clone(), user.clone());
+        if cross_exposure <= 0 {
+            return Ok(0);
+        }
+        let asset = buffer_client.get_asset();
+        let collateral_factor = Self::get_collateral_factor(env.clone(), asset);
+        let cross_collateral = Self::mul_div(env, cross_exposure, collateral_factor, BPS_DENOMINATOR)?;
+        Ok(buffer_client.shares_for_amount(&cross_collateral))
+    }
+    
+    /// Every plan ID in a user's index, across its buckets
+    fn user_plans(env: &Env, user: &Address) -> Vec<u64> {
+        let count = Self::get_user_plan_count(env.clone(), user.clone());
+        let mut plans = Vec::new(env);
+        for bucket in 0..count.div_ceil(INDEX_BUCKET_SIZE) {
+            plans.append(&index::user_plan_bucket(env, user, bucket));
+        }
+        plans
+    }
+    
+    fn record(
+        env: &Env,
+        plan_id: u64,
+        action: PlanAction,
+        installment_number: u32,
+        actor: Option<Address>,
+    ) {
+        let len = Self::history_len(env, plan_id);
+        let page = len / HISTORY_PAGE_SIZE;
+        
+        let mut entries = Self::history_page(env, plan_id, page);
+        entries.push_back(HistoryEntry {
+            action: action.clone(),
+            installment_number,
+            actor: actor.clone(),
+            timestamp: env.ledger().timestamp(),
+        });
+        Self::push_transition(env, Transition {
+            number: Self::get_transition_count(env.clone()),
+            plan_id,
+            action: action.clone(),
+            installment_number,
+            actor,
+            timestamp: env.ledger().timestamp(),
+        });
+        
+        let page_key = PlanKey::PlanHistory(plan_id, page);
+        env.storage().persistent().set(&page_key, &entries);
+        Self::extend_persistent(env, &page_key);
+        let len_key = PlanKey::PlanHistoryLen(plan_id);
+        env.storage().persistent().set(&len_key, &(len + 1));
+        Self::extend_persistent(env, &len_key);
+        
+        Self::notify_read_model(env, plan_id, action, installment_number);
+    }
+    
+    /// Write a transition over the oldest slot of the ring buffer
+    fn push_transition(env: &Env, transition: Transition) {
+        let slot = FeedKey::Transition((transition.number % RECENT_TRANSITIONS as u64) as u32);
+        env.storage().persistent().set(&slot, &transition);
+        Self::extend_persistent(env, &slot);
+        
+        env.storage()
+            .persistent()
+            .set(&FeedKey::TransitionCount, &(transition.number + 1));
+        Self::extend_persistent(env, &FeedKey::TransitionCount);
+    }
+    
+    /// Pay the cleanup bounty for `reclaimed` entries, capped by the rebate pool
+    fn pay_cleanup_bounty(env: &Env, caller: &Address, reclaimed: u32) -> i128 {
+        let (Some(bounty), Some(config)) = (
+            Self::get_cleanup_bounty(env.clone()),
+            Self::get_rebate_config(env.clone()),
+        ) else {
+            return 0;
+        };
+        
+        let pool = Self::get_rebate_pool(env.clone());
+        let amount = bounty.saturating_mul(reclaimed as i128).min(pool);
+        if amount <= 0 {
+            return 0;
+        }
+        
+        TokenClient::new(env, &config.token)
+            .transfer(&env.current_contract_address(), caller, &amount);
+        Self::set_counter(env, &DataKey::RebatePool, &(pool - amount));
+        amount
+    }
+    
+    /// Push a transition to the read model, if one is configured
+    /// 
+    /// Failures are ignored: a broken read model must never block
+    /// collections or defaults.
+    fn notify_read_model(env: &Env, plan_id: u64, action: PlanAction, installment_number: u32) {
+        let read_model: Option<Address> = env.storage().instance().get(&DataKey::ReadModel);
+        let Some(read_model) = read_model else {
+            return;
+        };
+        let Ok(plan) = Self::get_plan_header(env.clone(), plan_id) else {
+            return;
+        };
+        
+        let amount = match action {
+            PlanAction::Created => plan.total_amount,
+            PlanAction::Collected | PlanAction::Failed => {
+                Self::scheduled_installment(env, &plan, installment_number).amount
+            }
+            _ => 0,
+        };
+        let update = PlanUpdate {
+            plan_id,
+            action,
+            installment_number,
+            user: plan.user,
+            merchant: plan.merchant,
+            amount,
+            timestamp: env.ledger().timestamp(),
+        };
+        let _ = ReadModelClient::new(env, &read_model).try_on_plan_update(&update);
+    }
+    
+    fn record_settlement(env: &Env, merchant: &Address, settlement: Settlement) {
+        let count = Self::get_settlement_count(env.clone(), merchant.clone());
+        let page = count / HISTORY_PAGE_SIZE;
+        
+        let mut entries = Self::get_settlement_history(env.clone(), merchant.clone(), page);
+        entries.push_back(settlement);
+        
+        env.storage()
+            .persistent()
+            .set(&DataKey::Settlements(merchant.clone(), page), &entries);
+        env.storage()
+            .persistent()
+            .set(&DataKey::SettlementCount(merchant.clone()), &(count + 1));
+    }
+    
+    fn credit_history(env: &Env, user: &Address) -> CreditHistory {
+        env.storage()
+            .persistent()
+            .get(&DataKey::CreditHistory(user.clone()))
+            .unwrap_or_default()
+    }
+    
+    fn recovery(env: &Env, user: &Address) -> Recovery {
+        env.storage()
+            .persistent()
+            .get(&UserKey::Recovery(user.clone()))
+            .unwrap_or_default()
+    }
+    
+    /// Extend or restart the on-time streak, forgiving a default once it is
+    /// long enough
+    fn track_recovery(env: &Env, user: &Address, on_time: bool) {
+        let defaults = Self::credit_history(env, user).defaults;
+        let mut recovery = Self::recovery(env, user);
+        Self::advance_recovery(&mut recovery, defaults, on_time);
+        env.storage().persistent().set(&UserKey::Recovery(user.clone()), &recovery);
+    }
+    
+    fn advance_recovery(recovery: &mut Recovery, defaults: u32, on_time: bool) {
+        if !on_time {
+            recovery.on_time_streak = 0;
+            return;
+        }
+        recovery.on_time_streak += 1;
+        if recovery.on_time_streak >= RECOVERY_STREAK && recovery.forgiven_defaults < defaults {
+            recovery.forgiven_defaults += 1;
+            recovery.on_time_streak = 0;
+        }
+    }
+    
+    fn credit_score(history: &CreditHistory, recovery: &Recovery) -> u32 {
+        let late = history.installments_paid.saturating_sub(history.on_time_payments);
+        let defaults = history.defaults.saturating_sub(recovery.forgiven_defaults);
+        let score = CREDIT_SCORE_BASE
+            + history.on_time_payments as i64 * CREDIT_SCORE_ON_TIME
+            + history.plans_completed as i64 * CREDIT_SCORE_COMPLETED
+            - late as i64 * CREDIT_SCORE_LATE
+            - defaults as i64 * CREDIT_SCORE_DEFAULT;
+        score.clamp(0, CREDIT_SCORE_MAX) as u32
+    }
+    
+    fn credit_limit(env: &Env, curve: &CreditCurve, user: &Address) -> Result<i128, ContractError> {
+        let history = Self::credit_history(env, user);
+        let repaid = Self::mul_div(env, history.total_repaid, curve.volume_bps, BPS_DENOMINATOR)?;
+        let mut limit = Self::add_amounts(env, curve.base_limit, repaid)?;
+        
+        // Tenure runs from the first plan, archived or not
+        let first_plan = index::user_plan_bucket(env, user, 0)
+            .first()
+            .and_then(|plan_id| Self::get_plan_header(env.clone(), plan_id).ok());
+        if let (Some(plan), true) = (first_plan, curve.tenure_period_secs > 0) {
+            let periods = env.ledger().timestamp().saturating_sub(plan.created_at) / curve.tenure_period_secs;
+            let tenure = Self::mul_div(env, curve.tenure_amount, periods as i128, 1)?;
+            limit = Self::add_amounts(env, limit, tenure)?;
+        }
+        
+        let defaults = history.defaults.saturating_sub(Self::recovery(env, user).forgiven_defaults);
+        let penalty_bps = (defaults as i128 * curve.default_penalty_bps).min(BPS_DENOMINATOR);
+        let limit = Self::mul_div(env, limit, BPS_DENOMINATOR - penalty_bps, BPS_DENOMINATOR)?;
+        Ok(limit.min(curve.max_limit))
+    }
+    
+    fn update_credit(env: &Env, user: &Address, update: impl FnOnce(&mut CreditHistory)) {
+        let mut history = Self::credit_history(env, user);
+        update(&mut history);
+        env.storage().persistent().set(&DataKey::CreditHistory(user.clone()), &history);
+    }
+    
+    fn update_stats(env: &Env, update: impl FnOnce(&mut ProtocolStats)) {
+        let mut stats = Self::get_stats(env.clone());
+        update(&mut stats);
+        Self::set_counter(env, &DataKey::Stats, &stats);
+    }
+    
+    /// Store a plan's header and extend its TTL
+    /// 
+    /// Installments are not written: callers that change one store it with
+    /// `save_installment`.
+    fn save_plan(env: &Env, plan: &PlanHeader) {
+        let key = PlanKey::Plan(plan.plan_id);
+        env.storage().persistent().set(&key, plan);
+        Self::extend_persistent(env, &key);
+    }
+    
+    /// Store one installment of a plan and extend its TTL
+    fn save_installment(env: &Env, plan_id: u64, installment: &Installment) {
+        let key = PlanKey::Installment(plan_id, installment.number);
+        let record = InstallmentRecord {
+            amount: installment.amount,
+            paid_at: installment.paid_at,
+            payment_source: installment.payment_source,
+            status: installment.status.clone(),
+        };
+        env.storage().persistent().set(&key, &record);
+        Self::extend_persistent(env, &key);
+    }
+    
+    /// Load a plan's header, extending its TTL
+    fn load_header(env: &Env, plan_id: u64) -> Result<PlanHeader, ContractError> {
+        let key = PlanKey::Plan(plan_id);
+        let header = env.storage()
+            .persistent()
+            .get(&key)
+            .ok_or(ContractError::PlanNotFound)?;
+        Self::extend_persistent(env, &key);
+        Ok(header)
+    }
+    
+    /// Sum of the installments not yet paid (pending or failed)
+    /// 
+    /// Only the unpaid installments are read, as flagged by the bitmap.
+    fn unpaid_amount(env: &Env, plan: &PlanHeader) -> Result<i128, ContractError> {
+        let mut unpaid: i128 = 0;
+        for number in 1..=plan.installments_count {
+            if !plan.is_paid(number) {
+                unpaid = Self::add_amounts(env, unpaid, Self::load_installment(env, plan, number)?.amount)?;
+            }
+        }
+        Ok(unpaid)
+    }
+    
+    /// Load one installment of a plan, extending its TTL
+    /// 
+    /// Pending installments have no record and are computed from the schedule.
+    fn load_installment(env: &Env, plan: &PlanHeader, number: u32) -> Result<Installment, ContractError> {
+        if number == 0 || number > plan.installments_count {
+            return Err(ContractError::InstallmentNotFound);
+        }
+        
+        let key = PlanKey::Installment(plan.plan_id, number);
+        let record: InstallmentRecord = match env.storage().persistent().get(&key) {
+            Some(record) => record,
+            None if plan.is_pending(number) => return Ok(Self::scheduled_installment(env, plan, number)),
+            None => return Err(ContractError::InstallmentNotFound),
+        };
+        Self::extend_persistent(env, &key);
+        Ok(Installment {
+            number,
+            amount: record.amount,
+            due_date: Self::due_date(env, plan, number),
+            paid_at: record.paid_at,
+            payment_source: record.payment_source,
+            status: record.status,
+        })
+    }
+    
+    /// Pending installment `number` as derived from the plan's schedule
+    fn scheduled_installment(env: &Env, plan: &PlanHeader, number: u32) -> Installment {
+        // Equal installments; the last one carries the remainder to complete the exact total
+        let count = plan.installments_count as i128;
+        let mut amount = plan.total_amount / count;
+        if number == plan.installments_count {
+            // Can't overflow: the quotient plus the remainder never exceeds the total
+            amount += plan.total_amount % count;
+        }
+        
+        Installment {
+            number,
+            amount,
+            due_date: Self::due_date(env, plan, number),
+            paid_at: None,
+            payment_source: None,
+            status: InstallmentStatus::Pending,
+        }
+    }
+    
+    /// Store a plan's due dates, returning its (`first_due`, `interval`)
+    /// 
+    /// Evenly spaced schedules, the common "monthly for N months" case, are
+    /// fully described by the first date and the spacing. Only irregular
+    /// ones keep the explicit vector under `PlanKey::DueDates`.
+    fn store_schedule(env: &Env, plan_id: u64, due_dates: &Vec<u64>) -> (u64, u64) {
+        let first_due = due_dates.get(0).unwrap_or(0);
+        if due_dates.len() < 2 {
+            return (first_due, 0);
+        }
+        
+        let second_due = due_dates.get(1).unwrap();
+        let interval = second_due.saturating_sub(first_due);
+        let regular = interval > 0 && (1..due_dates.len()).all(|i| {
+            let expected = interval.checked_mul(i as u64).and_then(|step| first_due.checked_add(step));
+            expected == Some(due_dates.get(i).unwrap())
+        });
+        if regular {
+            return (first_due, interval);
+        }
+        
+        let key = PlanKey::DueDates(plan_id);
+        env.storage().persistent().set(&key, due_dates);
+        Self::extend_persistent(env, &key);
+        (first_due, 0)
+    }
+    
+    /// Whether `due_dates` is the stored schedule of `plan`
+    fn same_schedule(env: &Env, plan: &PlanHeader, due_dates: &Vec<u64>) -> bool {
+        due_dates.len() == plan.installments_count
+            && (1..=plan.installments_count).all(|n| due_dates.get(n - 1) == Some(Self::due_date(env, plan, n)))
+    }
+    
+    /// Due date of installment `number`, rebuilt from the plan's schedule
+    fn due_date(env: &Env, plan: &PlanHeader, number: u32) -> u64 {
+        if number == 1 {
+            return plan.first_due;
+        }
+        if plan.interval > 0 {
+            return plan.first_due + plan.interval * (number - 1) as u64;
+        }
+        
+        let key = PlanKey::DueDates(plan.plan_id);
+        let due_dates: Vec<u64> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+        Self::extend_persistent(env, &key);
+        due_dates.get(number - 1).unwrap_or(0)
+    }
+    
+    /// Protocol-wide counters, each kept in its own persistent entry
+    fn counter_keys(env: &Env) -> Vec<DataKey> {
+        Vec::from_array(env, [
+            DataKey::PlanCounter,
+            DataKey::TotalOutstanding,
+            DataKey::InsuredOutstanding,
+            DataKey::RebatePool,
+            DataKey::Stats,
+            DataKey::EventSeq,
+        ])
+    }
+    
+    /// Write a protocol-wide counter to its own persistent entry
+    /// 
+    /// Counters change with every plan, so they stay out of instance
+    /// storage: a write there would conflict with every other transaction
+    /// on the contract.
+    fn set_counter<V: IntoVal<Env, Val>>(env: &Env, key: &DataKey, value: &V) {
+        env.storage().persistent().set(key, value);
+        Self::extend_persistent(env, key);
+    }
+    
+    /// Extend a persistent entry to `TTL_EXTEND_TO` once it drops below
+    /// `TTL_THRESHOLD` ledgers
+    fn extend_persistent<K: IntoVal<Env, Val>>(env: &Env, key: &K) {
+        env.storage().persistent().extend_ttl(key, TTL_THRESHOLD, TTL_EXTEND_TO);
+    }
+    
+    /// Extend a persistent entry that may not exist
+    fn extend_if_present<K: IntoVal<Env, Val>>(env: &Env, key: &K) {
+        if env.storage().persistent().has(key) {
+            Self::extend_persistent(env, key);
+        }
+    }
+    
+    /// Derived plan ID: first 8 bytes of sha256(user || merchant || nonce)
+    /// with `KEYED_ID_FLAG` set
+    fn keyed_plan_id(env: &Env, user: &Address, merchant: &Address, nonce: u64) -> u64 {
+        let mut preimage = user.clone().to_xdr(env);
+        preimage.append(&merchant.clone().to_xdr(env));
+        preimage.extend_from_array(&nonce.to_be_bytes());
+        
+        let digest = env.crypto().sha256(&preimage).to_array();
+        let mut id_bytes = [0u8; 8];
+        id_bytes.copy_from_slice(&digest[0..8]);
+        u64::from_be_bytes(id_bytes) | KEYED_ID_FLAG
+    }
+    
+    /// Upgrade the plan created at `index` from plan layout 0
+    /// 
+    /// Rewrites the record and its per-plan entries under the u64 ID and
+    /// rebuilds the user, merchant, status and due-date indexes, whose
+    /// layout 0 entries held string IDs.
+    fn migrate_plan_v0(env: &Env, index: u64) -> Result<(), ContractError> {
+        let storage = env.storage().persistent();
+        
+        // The due-date index can only be enumerated before anything is re-added
+        if index == 0 {
+            for day in index::due_days(env).iter() {
+                storage.remove(&DataKey::DueBucket(day));
+            }
+            storage.remove(&DataKey::DueDays);
+        }
+        
+        let legacy_id = Self::legacy_id_for(env, index);
+        let old: BridgePlanV0 = match storage.get(&PlanKeyV0::Plan(legacy_id.clone())) {
+            Some(old) => old,
+            None => return Ok(()),
+        };
+        
+        let mut due_dates = Vec::new(env);
+        for installment in old.installments.iter() {
+            due_dates.push_back(installment.due_date);
+        }
+        let (first_due, interval) = Self::store_schedule(env, index, &due_dates);
+        
+        let mut plan = PlanHeader {
+            plan_id: index,
+            user: old.user.clone(),
+            merchant: old.merchant.clone(),
+            total_amount: old.total_amount,
+            total_shares: old.total_shares,
+            installments_count: old.installments_count,
+            first_due,
+            interval,
+            protected_shares: old.protected_shares,
+            status: old.status.clone(),
+            paid_bitmap: 0,
+            failed_bitmap: 0,
+            created_at: old.created_at,
+            insured_amount: old.insured_amount,
+            release_at: old.release_at,
+            cross_margin: old.cross_margin,
+            rebate_accrued: old.rebate_accrued,
+            rebate_checkpoint: old.rebate_checkpoint,
+        };
+        for installment in old.installments.iter() {
+            match installment.status {
+                InstallmentStatus::Paid => plan.mark_paid(installment.number),
+                InstallmentStatus::Failed => plan.mark_failed(installment.number),
+                InstallmentStatus::Pending | InstallmentStatus::Overdue => continue,
+            }
+            Self::save_installment(env, index, &installment);
+        }
+        Self::save_plan(env, &plan);
+        storage.remove(&PlanKeyV0::Plan(legacy_id.clone()));
+        
+        // Per-plan entries keep their values under the new key
+        Self::move_entry(env, &PlanKeyV0::Frozen(legacy_id.clone()), &PlanKey::Frozen(index));
+        Self::move_entry(env, &PlanKeyV0::PlanFees(legacy_id.clone()), &PlanKey::PlanFees(index));
+        let history_len: u32 = storage
+            .get(&PlanKeyV0::PlanHistoryLen(legacy_id.clone()))
+            .unwrap_or(0);
+        for page in 0..history_len.div_ceil(HISTORY_PAGE_SIZE) {
+            Self::move_entry(
+                env,
+                &PlanKeyV0::PlanHistory(legacy_id.clone(), page),
+                &PlanKey::PlanHistory(index, page),
+            );
+        }
+        Self::move_entry(env, &PlanKeyV0::PlanHistoryLen(legacy_id.clone()), &PlanKey::PlanHistoryLen(index));
+        
+        if let Some(auction) = storage.get::<_, AuctionV0>(&PlanKeyV0::Auction(legacy_id.clone())) {
+            let auction = Auction {
+                plan_id: index,
+                debt_token: auction.debt_token,
+                collateral_shares: auction.collateral_shares,
+                debt_amount: auction.debt_amount,
+                start_price: auction.start_price,
+                floor_price: auction.floor_price,
+                started_at: auction.started_at,
+                duration: auction.duration,
+                settled: auction.settled,
+            };
+            storage.set(&PlanKey::Auction(index), &auction);
+            Self::extend_persistent(env, &PlanKey::Auction(index));
+            storage.remove(&PlanKeyV0::Auction(legacy_id));
+        }
+        
+        // ===== REBUILD INDEXES =====
+        
+        // Drop the layout 0 lists before the first plan is re-added to them
+        storage.remove(&PlanKeyV0::UserPlans(plan.user.clone()));
+        let merchant_key = PlanKeyV0::MerchantPlans(plan.merchant.clone());
+        if Self::is_legacy_list(env, &merchant_key) {
+            storage.remove(&merchant_key);
+            Self::migrate_settlements_v0(env, &plan.merchant);
+        }
+        let status_key = PlanKeyV0::StatusPlans(plan.status.clone());
+        if Self::is_legacy_list(env, &status_key) {
+            storage.remove(&status_key);
+        }
+        index::plan_created(env, &plan);
+        
+        Ok(())
+    }
+    
+    /// Rewrite a merchant's layout 0 settlement pages with u64 plan IDs
+    fn migrate_settlements_v0(env: &Env, merchant: &Address) {
+        let count = Self::get_settlement_count(env.clone(), merchant.clone());
+        for page in 0..count.div_ceil(HISTORY_PAGE_SIZE) {
+            let key = DataKey::Settlements(merchant.clone(), page);
+            let old: Vec<SettlementV0> = env.storage().persistent().get(&key).unwrap_or(Vec::new(env));
+            let mut entries = Vec::new(env);
+            for settlement in old.iter() {
+                entries.push_back(Settlement {
+                    plan_id: Self::legacy_plan_id(&settlement.plan_id).unwrap_or(0),
+                    installment_number: settlement.installment_number,
+                    amount: settlement.amount,
+                    fee: settlement.fee,
+                    timestamp: settlement.timestamp,
+                });
+            }
+            env.storage().persistent().set(&key, &entries);
+        }
+    }
+    
+    /// Whether a stored plan ID list still holds layout 0 string IDs
+    fn is_legacy_list<K: IntoVal<Env, Val>>(env: &Env, key: &K) -> bool {
+        let ids: Option<Vec<Val>> = env.storage().persistent().get(key);
+        ids.and_then(|ids| ids.first())
+            .is_some_and(|id| String::try_from_val(env, &id).is_ok())
+    }
+    
+    /// Move a persistent entry to a new key, keeping its value
+    fn move_entry<K1, K2>(env: &Env, from: &K1, to: &K2)
+    where
+        K1: IntoVal<Env, Val>,
+        K2: IntoVal<Env, Val>,
+    {
+        let storage = env.storage().persistent();
+        if let Some(value) = storage.get::<_, Val>(from) {
+            storage.set(to, &value);
+            Self::extend_persistent(env, to);
+            storage.remove(from);
+        }
+    }
+    
+    /// Legacy 16-byte string ID of the plan created at `counter`
+    fn legacy_id_for(env: &Env, counter: u64) -> String {
+        let mut id_bytes = [0u8; LEGACY_ID_LEN];
+        id_bytes[0..8].copy_from_slice(&counter.to_be_bytes());
+        String::from_bytes(env, &id_bytes)
+    }
+    
+    /// Plan ID encoded in a legacy 16-byte string ID (big-endian counter
+    /// followed by zero padding)
+    fn legacy_plan_id(legacy_id: &String) -> Option<u64> {
+        if legacy_id.len() != LEGACY_ID_LEN as u32 {
+            return None;
+        }
+        let mut id_bytes = [0u8; LEGACY_ID_LEN];
+        legacy_id.copy_into_slice(&mut id_bytes);
+        if id_bytes[8..].iter().any(|b| *b != 0) {
+            return None;
+        }
+        let mut counter = [0u8; 8];
+        counter.copy_from_slice(&id_bytes[0..8]);
+        Some(u64::from_be_bytes(counter))
+    }
+    
+    /// Slice of `items` starting at `offset`, at most `limit` (capped) long
+    fn page<T>(items: &Vec<T>, offset: u32, limit: u32) -> Vec<T> {
+        let start = offset.min(items.len());
+        let end = start.saturating_add(limit.min(MAX_PAGE_SIZE)).min(items.len());
+        items.slice(start..end)
+    }
+    
+    fn when_approved(env: &Env, plan: &PlanHeader) -> Result<(), ContractError> {
+        if plan.status == PlanStatus::PendingApproval {
+            log!(env, "Error: Plan awaiting co-approval");
+            return Err(ContractError::AwaitingApproval);
+        }
+        Ok(())
+    }
+    
+    fn when_not_paused(env: &Env) -> Result<(), ContractError> {
+        if Self::is_paused(env.clone()) {
+            log!(env, "Error: Contract is paused");
+            return Err(ContractError::Paused);
+        }
+        if Self::plans_pending_migration(env) {
+            log!(env, "Error: Plan records awaiting migrate_plans");
+            return Err(ContractError::Paused);
+        }
+        Ok(())
+    }
+    
+    /// Whether an initialized deployment still stores plans in an older layout
+    fn plans_pending_migration(env: &Env) -> bool {
+        env.storage().instance().has(&DataKey::Admin)
+            && Self::get_plan_version(env.clone()) < PLAN_VERSION
+    }
+    
+    /// Debit an installment to the merchant, withholding the protocol fee
+    fn debit_installment(
+        buffer_client: &BufferContractClient,
+        config: &Config,
+        user: &Address,
+        shares: i128,
+        fee_shares: i128,
+        payout: &Address,
+        from_protected: bool,
+    ) {
+        let debit = |amount: i128, to: &Address| {
+            if amount <= 0 {
+                return;
+            }
+            if from_protected {
+                buffer_client.debit_protected(user, &amount, to);
+            } else {
+                buffer_client.debit_available(user, &amount, to);
+            }
+        };
+        
+        debit(shares - fee_shares, payout);
+        if let Some(recipient) = &config.fee_recipient {
+            debit(fee_shares, recipient);
+        }
+    }
+    
+    /// Installment and balance checks shared by `collect_installment` and `simulate_collect`
+    fn check_collect(
+        env: &Env,
+        plan: &PlanHeader,
+        installment_number: u32,
+        is_user: bool,
+    ) -> Result<Collection, ContractError> {
+        if installment_number == 0 || installment_number > plan.installments_count {
+            log!(env, "Error: Installment not found {}", installment_number);
+            return Err(ContractError::InstallmentNotFound);
+        }
+        
+        // Completed and liquidated plans have nothing left to collect
+        if plan.status != PlanStatus::Active && plan.status != PlanStatus::Defaulted {
+            log!(env, "Error: Plan {} is closed", plan.plan_id);
+            return Err(ContractError::AlreadyPaid);
+        }
+        
+        // Validate that installment is pending
+        if !plan.is_pending(installment_number) {
+            log!(env, "Error: Installment already paid {}", installment_number);
+            return Err(ContractError::AlreadyPaid);
+        }
+        
+        let config = Self::get_config(env.clone())?;
+        
+        // Earlier pending installments go first (defaulted ones are skipped)
+        if config.sequential_collection {
+            if let Some(earlier) = (1..installment_number).find(|n| plan.is_pending(*n)) {
+                log!(env, "Error: Installment {} is still pending before {}", earlier, installment_number);
+                return Err(ContractError::InstallmentOutOfOrder);
+            }
+        }
+        
+        let installment = Self::load_installment(env, plan, installment_number)?;
+        
+        // Validate that installment is due (the user may pay early). The
+        // tolerance absorbs workers submitting just ahead of the ledger close
+        let collectable_at = installment.due_date.saturating_sub(config.due_tolerance_secs);
+        if !is_user && env.ledger().timestamp() < collectable_at {
+            log!(env, "Error: Installment not yet due {}", installment_number);
+            return Err(ContractError::NotDueYet);
+        }
+        
+        // ===== CALCULATE NEEDED SHARES AND GET BALANCE =====
+        
+        let buffer_client = BufferContractClient::new(env, &config.buffer);
+        let shares_needed = buffer_client.shares_for_amount(&installment.amount);
+        let balance = buffer_client.get_balance(&plan.user);
+        let spendable = Self::spendable_available(env, &buffer_client, &plan.user, &balance);
+        let payout = Self::merchant_payout(env, &plan.merchant)?;
+        
+        // Available first unless the routing policy prefers protected funds
+        let from_available = spendable >= shares_needed
+            && (config.routing == RoutingPolicy::AvailableFirst
+                || balance.protected_shares < shares_needed);
+        
+        let source = if from_available {
+            PaymentSource::available()
+        } else if balance.protected_shares >= shares_needed {
+            PaymentSource::protected()
+        } else {
+            // Returning an error rolls back every write of this invocation,
+            // so the default itself is recorded through `declare_default`
+            log!(env, "Error: Insufficient funds for installment {}", installment_number);
+            return Err(ContractError::InsufficientFunds);
+        };
+        
+        Ok(Collection {
+            installment,
+            config,
+            shares_needed,
+            source,
+            payout,
+        })
+    }
+    
+    /// Protocol fee withheld from `amount` (shares or tokens)
+    fn protocol_fee(env: &Env, config: &Config, amount: i128) -> Result<i128, ContractError> {
+        match &config.fee_recipient {
+            Some(_) => Self::mul_div(env, amount, config.fee_bps, BPS_DENOMINATOR),
+            None => Ok(0),
+        }
+    }
+    
+    /// `a + b`, or `InvalidAmount` if the sum overflows
+    fn add_amounts(env: &Env, a: i128, b: i128) -> Result<i128, ContractError> {
+        a.checked_add(b).ok_or_else(|| {
+            log!(env, "Error: Amount overflow {} + {}", a, b);
+            ContractError::InvalidAmount
+        })
+    }
+    
+    /// `value * numerator / denominator`, or `InvalidAmount` if the product overflows
+    fn mul_div(env: &Env, value: i128, numerator: i128, denominator: i128) -> Result<i128, ContractError> {
+        value.checked_mul(numerator).map(|product| product / denominator).ok_or_else(|| {
+            log!(env, "Error: Amount overflow {} * {}", value, numerator);
+            ContractError::InvalidAmount
+        })
+    }
+    
+    /// `part` as basis points of `whole`, for views that must not fail
+    /// 
+    /// Near `i128::MAX` it divides first, which only loses precision far
+    /// below one basis point.
+    fn ratio_bps(part: i128, whole: i128) -> i128 {
+        match part.checked_mul(BPS_DENOMINATOR) {
+            Some(scaled) => scaled / whole,
+            None => part / (whole / BPS_DENOMINATOR).max(1),
+        }
+    }
+    
+    /// Available shares a collection may take without breaching the user's reserve
+    fn spendable_available(
+        env: &Env,
+        buffer_client: &BufferContractClient,
+        user: &Address,
+        balance: &BufferBalance,
+    ) -> i128 {
+        let reserve = Self::get_reserve(env.clone(), user.clone());
+        if reserve == 0 {
+            return balance.available_shares;
+        }
+        
+        let reserve_shares = buffer_client.shares_for_amount(&reserve);
+        (balance.available_shares - reserve_shares).max(0)
+    }
+    
+    /// Accrue the rebate on the plan's locked shares since the last checkpoint
+    fn accrue_rebate(env: &Env, plan: &mut PlanHeader) {
+        let now = env.ledger().timestamp();
+        let elapsed = now.saturating_sub(plan.rebate_checkpoint);
+        plan.rebate_checkpoint = now;
+        
+        let config: RebateConfig = match env.storage().instance().get(&DataKey::RebateConfig) {
+            Some(config) => config,
+            None => return,
+        };
+        
+        if plan.protected_shares <= 0 || elapsed == 0 {
+            return;
+        }
+        
+        // Saturates rather than wraps: claims are bounded by the rebate pool anyway
+        let accrued = plan.protected_shares
+            .saturating_mul(config.rate_bps)
+            .saturating_mul(elapsed as i128)
+            / (BPS_DENOMINATOR * SECONDS_PER_YEAR as i128);
+        plan.rebate_accrued = plan.rebate_accrued.saturating_add(accrued);
+    }
+    
+    /// Count a plan creation against the user's rate limit window
+    fn consume_creation_slot(env: &Env, user: &Address) -> Result<(), ContractError> {
+        let limit = match Self::get_rate_limit(env.clone()) {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        
+        let now = env.ledger().timestamp();
+        let key = DataKey::CreationWindow(user.clone());
+        let mut window: CreationWindow = env.storage()
+            .persistent()
+            .get(&key)
+            .unwrap_or(CreationWindow { started_at: now, count: 0 });
+        
+        if now >= window.started_at.saturating_add(limit.window_secs) {
+            window = CreationWindow { started_at: now, count: 0 };
+        }
+        
+        if window.count >= limit.max_plans {
+            log!(env, "Error: Rate limited until {}", window.started_at.saturating_add(limit.window_secs));
+            return Err(ContractError::RateLimitExceeded);
+        }
+        
+        window.count += 1;
+        env.storage().persistent().set(&key, &window);
+        Ok(())
+    }
+    
+    /// Move the outstanding amount tracked for a plan's user and merchant
+    fn add_exposure(env: &Env, user: &Address, merchant: &Address, delta: i128) {
+        // Saturating: create_plan already rejected sums that overflow
+        let exposure = Self::get_merchant_exposure(env.clone(), merchant.clone()).saturating_add(delta);
+        env.storage().persistent().set(
+            &DataKey::MerchantExposure(merchant.clone()),
+            &(if exposure > 0 { exposure } else { 0 }),
+        );
+        
+        let exposure = Self::get_user_exposure(env.clone(), user.clone()).saturating_add(delta);
+        env.storage().persistent().set(
+            &DataKey::UserExposure(user.clone()),
+            &(if exposure > 0 { exposure } else { 0 }),
+        );
+        
+        let total = Self::get_total_outstanding(env.clone()).saturating_add(delta);
+        Self::set_counter(env, &DataKey::TotalOutstanding, &(if total > 0 { total } else { 0 }));
+    }
+    
+    fn update_incidents(env: &Env, merchant: &Address, update: impl FnOnce(&mut MerchantIncidents)) {
+        let key = UserKey::MerchantIncidents(merchant.clone());
+        let mut incidents = env.storage().persistent().get(&key).unwrap_or_default();
+        update(&mut incidents);
+        env.storage().persistent().set(&key, &incidents);
+    }
+    
+    fn add_merchant_defaulted(env: &Env, merchant: &Address, delta: i128) {
+        let key = DataKey::MerchantDefaulted(merchant.clone());
+        let amount = env.storage().persistent().get::<_, i128>(&key).unwrap_or(0).saturating_add(delta);
+        env.storage().persistent().set(&key, &(if amount > 0 { amount } else { 0 }));
+    }
+    
+    fn reduce_cross_exposure(env: &Env, user: &Address, amount: i128) {
+        let exposure = Self::get_cross_exposure(env.clone(), user.clone()) - amount;
+        env.storage().persistent().set(
+            &DataKey::CrossExposure(user.clone()),
+            &(if exposure > 0 { exposure } else { 0 }),
+        );
+    }
+    
+    fn add_merchant_disputed(env: &Env, merchant: &Address, delta: i128) {
+        let key = UserKey::MerchantDisputed(merchant.clone());
+        let amount = env.storage().persistent().get::<_, i128>(&key).unwrap_or(0).saturating_add(delta);
+        env.storage().persistent().set(&key, &(if amount > 0 { amount } else { 0 }));
+    }
+    
+    fn add_cross_locked(env: &Env, user: &Address, delta: i128) {
+        let locked = Self::get_cross_locked(env.clone(), user.clone()).saturating_add(delta);
+        env.storage().persistent().set(
+            &UserKey::CrossLocked(user.clone()),
+            &(if locked > 0 { locked } else { 0 }),
+        );
+    }
+    
+    fn tier_ratio(env: &Env, config: &Config, user: &Address) -> i128 {
+        let score = Self::get_credit_score(env.clone(), user.clone());
+        let mut ratio_bps = BPS_DENOMINATOR;
+        for tier in config.collateral_tiers.iter() {
+            if score >= tier.min_score {
+                ratio_bps = tier.ratio_bps;
+            }
+        }
+        ratio_bps
+    }
+    
+    /// Tiers must be sorted by strictly increasing score, each locking
+    /// between 0 (exclusive) and 100%
+    fn validate_tiers(env: &Env, tiers: &Vec<CollateralTier>) -> Result<(), ContractError> {
+        if tiers.len() > MAX_COLLATERAL_TIERS {
+            log!(env, "Error: Too many collateral tiers {}", tiers.len());
+            return Err(ContractError::InvalidConfig);
+        }
+        for (i, tier) in tiers.iter().enumerate() {
+            let unordered = i > 0 && tiers.get_unchecked(i as u32 - 1).min_score >= tier.min_score;
+            if unordered || tier.ratio_bps <= 0 || tier.ratio_bps > BPS_DENOMINATOR {
+                log!(env, "Error: Invalid collateral tier {}", tier.min_score);
+                return Err(ContractError::InvalidConfig);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Portion of `amount` the insurance fund underwrites for `user`
+    /// 
+    /// Zero unless underwriting is configured, the user's score reaches the
+    /// minimum and the fund still has capacity for the whole shortfall.
+    fn insured_amount_for(env: &Env, user: &Address, amount: i128) -> Result<i128, ContractError> {
+        let config: UnderwritingConfig = match env.storage().instance().get(&DataKey::Underwriting) {
+            Some(config) => config,
+            None => return Ok(0),
+        };
+        
+        let score = CreditScoreClient::new(env, &config.score_source).get_score(user);
+        if score < config.min_score {
+            return Ok(0);
+        }
+        
+        let locked = Self::mul_div(env, amount, config.lock_ratio_bps, BPS_DENOMINATOR)?;
+        let shortfall = amount - locked;
+        let insured = Self::add_amounts(env, Self::get_insured_outstanding(env.clone()), shortfall)?;
+        
+        if insured > config.fund_capacity {
+            log!(env, "Insurance fund capacity reached, full collateral required");
+            return Ok(0);
+        }
+        
+        Ok(shortfall)
+    }
+    
+    /// Part of a liquidation `shortfall` the insurance fund pays: at most the
+    /// plan's insured amount, and no more than the fund's balance and its
+    /// allowance to the bridge, so an unfunded pool can't block the auction
+    fn insured_cover(
+        env: &Env,
+        token: &TokenClient,
+        plan: &PlanHeader,
+        shortfall: i128,
+    ) -> Option<(Address, i128)> {
+        if plan.insured_amount <= 0 || shortfall <= 0 {
+            return None;
+        }
+        let config: UnderwritingConfig = env.storage().instance().get(&DataKey::Underwriting)?;
+        let fund = config.insurance_fund;
+        
+        let allowance = token.allowance(&fund, &env.current_contract_address());
+        let available = allowance.min(token.balance(&fund));
+        let owed = shortfall.min(plan.insured_amount);
+        if available < owed {
+            log!(env, "Insurance fund covers {} of {}", available.max(0), owed);
+        }
+        
+        let amount = owed.min(available);
+        if amount > 0 { Some((fund, amount)) } else { None }
+    }
+    
+    /// Stop counting a closed plan's insured amount against the fund capacity
+    fn release_insured(env: &Env, plan: &PlanHeader) -> Result<(), ContractError> {
+        if plan.insured_amount > 0 {
+            let insured = Self::add_amounts(env, Self::get_insured_outstanding(env.clone()), -plan.insured_amount)?;
+            Self::set_counter(env, &DataKey::InsuredOutstanding, &insured);
+        }
+        Ok(())
+    }
+    
+    /// Shares an auction of `plan` can seize for `debt`
+    /// 
+    /// Only what is still locked in the Buffer counts. Cross-margin plans
+    /// claim the shares covering their debt from the shared lock, never
+    /// from the locks of the user's isolated plans.
+    fn auction_lot(env: &Env, buffer_client: &BufferContractClient, plan: &PlanHeader, debt: i128) -> i128 {
+        let balance = buffer_client.get_balance(&plan.user);
+        let plan_shares = if plan.cross_margin {
+            let cross_locked = Self::get_cross_locked(env.clone(), plan.user.clone());
+            let wanted = buffer_client.shares_for_amount(&debt);
+            if wanted < cross_locked { wanted } else { cross_locked }
+        } else {
+            plan.protected_shares
+        };
+        let lot = if plan_shares < balance.protected_shares { plan_shares } else { balance.protected_shares };
+        if lot > 0 { lot } else { 0 }
+    }
+    
+    /// Linear price decay from `start_price` to `floor_price` over `duration`
+    fn auction_price(env: &Env, auction: &Auction, now: u64) -> Result<i128, ContractError> {
+        let elapsed = now.saturating_sub(auction.started_at);
+        
+        if elapsed >= auction.duration {
+            return Ok(auction.floor_price);
+        }
+        
+        let decay_range = auction.start_price - auction.floor_price;
+        let decay = Self::mul_div(env, decay_range, elapsed as i128, auction.duration as i128)?;
+        Ok(auction.start_price - decay)
+    }
+}
+
+// ============ SECONDARY INDEXES ============
+
+/// Secondary indexes over plans: user -> plans, merchant -> plans,
+/// status -> plans and the due-date buckets
+/// 
+/// The user, merchant and status lists are split into buckets of
+/// `INDEX_BUCKET_SIZE` IDs under a count: an append rewrites the head
+/// bucket and a page reads at most two. Status lists also lose plans; the
+/// last plan of the list fills the freed position, found through the
+/// `StatusSlot` each listed plan keeps.
+/// 
+/// Entrypoints never write index keys directly; they report each state
+/// transition here, so every index stays consistent with the plan records.
+/// Every read and write extends the entries it touches: a due-date bucket
+/// is written months before its installment is collected.
+mod index {
+    use super::*;
+    
+    /// Add a newly stored plan to every index
+    /// 
+    /// Pending installments enter the due-date index; paid or failed ones
+    /// (only found when migrating) are left out.
+    pub(crate) fn plan_created(env: &Env, plan: &PlanHeader) {
+        push_user_plan(env, &plan.user, plan.plan_id);
+        push_merchant_plan(env, &plan.merchant, plan.plan_id);
+        push_status_plan(env, &plan.status, plan.plan_id);
+        
+        for number in 1..=plan.installments_count {
+            if plan.is_pending(number) {
+                add_due(env, plan.plan_id, &BridgeContract::scheduled_installment(env, plan, number));
+            }
+        }
+    }
+    
+    /// Move a plan between status lists
+    pub(crate) fn status_changed(env: &Env, plan_id: u64, from: &PlanStatus, to: &PlanStatus) {
+        remove_status_plan(env, from, plan_id);
+        push_status_plan(env, to, plan_id);
+    }
+    
+    /// Drop an archived plan from the list of its (closed) status
+    pub(crate) fn plan_archived(env: &Env, plan_id: u64, status: &PlanStatus) {
+        remove_status_plan(env, status, plan_id);
+        env.storage().persistent().remove(&PlanKey::StatusSlot(plan_id));
+    }
+    
+    /// Plan at `position` of the list of `status`
+    pub(crate) fn status_plan_at(env: &Env, status: &PlanStatus, position: u32) -> Option<u64> {
+        let ids: Vec<u64> = load(env, &IndexKey::StatusPlans(status.clone(), position / INDEX_BUCKET_SIZE))?;
+        ids.get(position % INDEX_BUCKET_SIZE)
+    }
+    
+    /// Extend every index entry that points at a plan
+    pub(crate) fn plan_bumped(env: &Env, plan: &PlanHeader) {
+        let count = BridgeContract::get_user_plan_count(env.clone(), plan.user.clone());
+        for bucket in 0..count.div_ceil(INDEX_BUCKET_SIZE) {
+            BridgeContract::extend_if_present(env, &DataKey::UserPlans(plan.user.clone(), bucket));
+        }
+        BridgeContract::extend_if_present(env, &DataKey::UserPlanCount(plan.user.clone()));
+        let merchant_slot: Option<u32> = env.storage().persistent().get(&PlanKey::MerchantSlot(plan.plan_id));
+        if let Some(slot) = merchant_slot {
+            BridgeContract::extend_persistent(env, &PlanKey::MerchantSlot(plan.plan_id));
+            BridgeContract::extend_if_present(env, &IndexKey::MerchantPlans(plan.merchant.clone(), slot / INDEX_BUCKET_SIZE));
+        }
+        BridgeContract::extend_if_present(env, &IndexKey::MerchantPlanCount(plan.merchant.clone()));
+        let status_slot: Option<u32> = env.storage().persistent().get(&PlanKey::StatusSlot(plan.plan_id));
+        if let Some(slot) = status_slot {
+            BridgeContract::extend_persistent(env, &PlanKey::StatusSlot(plan.plan_id));
+            BridgeContract::extend_if_present(env, &IndexKey::StatusPlans(plan.status.clone(), slot / INDEX_BUCKET_SIZE));
+        }
+        BridgeContract::extend_if_present(env, &IndexKey::StatusPlanCount(plan.status.clone()));
+        
+        for number in 1..=plan.installments_count {
+            if plan.is_pending(number) {
+                let day = BridgeContract::due_date(env, plan, number) / DUE_BUCKET_SECS;
+                BridgeContract::extend_if_present(env, &DataKey::DueBucket(day));
+            }
+        }
+        BridgeContract::extend_if_present(env, &DataKey::DueDays);
+    }
+    
+    /// Drop a collected, failed or settled installment from the due-date index
+    pub(crate) fn installment_closed(env: &Env, plan_id: u64, installment: &Installment) {
+        remove_due(env, plan_id, installment);
+    }
+    
+    /// Append a plan to the head bucket of the user's index
+    fn push_user_plan(env: &Env, user: &Address, plan_id: u64) {
+        let count = BridgeContract::get_user_plan_count(env.clone(), user.clone());
+        push_bucketed(env, &DataKey::UserPlans(user.clone(), count / INDEX_BUCKET_SIZE), plan_id);
+        store(env, &DataKey::UserPlanCount(user.clone()), &(count + 1));
+    }
+    
+    /// Append a plan to the head bucket of the merchant's index
+    /// 
+    /// The plan's position is kept so `plan_bumped` can find its bucket.
+    fn push_merchant_plan(env: &Env, merchant: &Address, plan_id: u64) {
+        let count = BridgeContract::get_merchant_plan_count(env.clone(), merchant.clone());
+        push_bucketed(env, &IndexKey::MerchantPlans(merchant.clone(), count / INDEX_BUCKET_SIZE), plan_id);
+        store(env, &IndexKey::MerchantPlanCount(merchant.clone()), &(count + 1));
+        store(env, &PlanKey::MerchantSlot(plan_id), &count);
+    }
+    
+    /// Append a plan to the head bucket of the list of `status`
+    fn push_status_plan(env: &Env, status: &PlanStatus, plan_id: u64) {
+        let count = BridgeContract::get_status_count(env.clone(), status.clone());
+        push_bucketed(env, &IndexKey::StatusPlans(status.clone(), count / INDEX_BUCKET_SIZE), plan_id);
+        store(env, &IndexKey::StatusPlanCount(status.clone()), &(count + 1));
+        store(env, &PlanKey::StatusSlot(plan_id), &count);
+    }
+    
+    /// Drop a plan from the list of `status`, moving the last plan of the
+    /// list into its position
+    /// 
+    /// Reads and rewrites at most two buckets. A plan that is not listed
+    /// under `status` is left alone.
+    fn remove_status_plan(env: &Env, status: &PlanStatus, plan_id: u64) {
+        let slot: u32 = match load(env, &PlanKey::StatusSlot(plan_id)) {
+            Some(slot) => slot,
+            None => return,
+        };
+        if status_plan_at(env, status, slot) != Some(plan_id) {
+            return;
+        }
+        
+        let last = BridgeContract::get_status_count(env.clone(), status.clone()) - 1;
+        let last_key = IndexKey::StatusPlans(status.clone(), last / INDEX_BUCKET_SIZE);
+        let mut last_ids: Vec<u64> = load(env, &last_key).unwrap_or(Vec::new(env));
+        let moved = last_ids.pop_back().unwrap_or(plan_id);
+        
+        if moved != plan_id {
+            if slot / INDEX_BUCKET_SIZE == last / INDEX_BUCKET_SIZE {
+                last_ids.set(slot % INDEX_BUCKET_SIZE, moved);
+            } else {
+                let key = IndexKey::StatusPlans(status.clone(), slot / INDEX_BUCKET_SIZE);
+                let mut ids: Vec<u64> = load(env, &key).unwrap_or(Vec::new(env));
+                ids.set(slot % INDEX_BUCKET_SIZE, moved);
+                store(env, &key, &ids);
+            }
+            store(env, &PlanKey::StatusSlot(moved), &slot);
+        }
+        
+        if last_ids.is_empty() {
+            env.storage().persistent().remove(&last_key);
+        } else {
+            store(env, &last_key, &last_ids);
+        }
+        store(env, &IndexKey::StatusPlanCount(status.clone()), &last);
+    }
+    
+    fn push_bucketed<K: IntoVal<Env, Val>>(env: &Env, key: &K, plan_id: u64) {
+        let mut ids: Vec<u64> = load(env, key).unwrap_or(Vec::new(env));
+        ids.push_back(plan_id);
+        store(env, key, &ids);
+    }
+    
+    /// Page of a bucketed list of `count` IDs; only the buckets covering
+    /// it (at most two) are read
+    pub(crate) fn bucketed_page<K: IntoVal<Env, Val>>(
+        env: &Env,
+        bucket_key: impl Fn(u32) -> K,
+        count: u32,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<u64> {
+        let end = offset.saturating_add(limit.min(MAX_PAGE_SIZE)).min(count);
+        
+        let mut plans = Vec::new(env);
+        let mut index = offset;
+        while index < end {
+            let bucket = index / INDEX_BUCKET_SIZE;
+            let bucket_start = bucket * INDEX_BUCKET_SIZE;
+            let ids: Vec<u64> = load(env, &bucket_key(bucket)).unwrap_or(Vec::new(env));
+            let to = (end - bucket_start).min(ids.len());
+            plans.append(&ids.slice(index - bucket_start..to));
+            index = bucket_start + INDEX_BUCKET_SIZE;
+        }
+        plans
+    }
+    
+    /// One bucket of a user's plan IDs, extending its TTL
+    pub(crate) fn user_plan_bucket(env: &Env, user: &Address, bucket: u32) -> Vec<u64> {
+        load(env, &DataKey::UserPlans(user.clone(), bucket)).unwrap_or(Vec::new(env))
+    }
+    
+    
+    
+    /// Page of the due-date index between two timestamps (inclusive)
+    pub(crate) fn due_between(env: &Env, from_ts: u64, to_ts: u64, cursor: u32, limit: u32) -> Vec<DueInstallment> {
+        let limit = limit.min(MAX_PAGE_SIZE);
+        let first_day = from_ts / DUE_BUCKET_SECS;
+        let mut skipped: u32 = 0;
+        let mut due = Vec::new(env);
+        
+        for day in due_days(env).iter() {
+            if day * DUE_BUCKET_SECS > to_ts || due.len() >= limit {
+                break;
+            }
+            if day < first_day {
+                continue;
+            }
+            for entry in due_bucket(env, day).iter() {
+                if entry.due_date > to_ts || due.len() >= limit {
+                    break;
+                }
+                if entry.due_date < from_ts {
+                    continue;
+                }
+                if skipped < cursor {
+                    skipped += 1;
+                } else {
+                    due.push_back(entry);
+                }
+            }
+        }
+        
+        due
+    }
+    
+    pub(crate) fn due_days(env: &Env) -> Vec<u64> {
+        load(env, &DataKey::DueDays).unwrap_or(Vec::new(env))
+    }
+    
+    /// Add a pending installment to the due-date index
+    fn add_due(env: &Env, plan_id: u64, installment: &Installment) {
+        let day = installment.due_date / DUE_BUCKET_SECS;
+        let mut bucket = due_bucket(env, day);
+        
+        // Keep each bucket sorted by due date
+        let entry = DueInstallment {
+            plan_id,
+            installment_number: installment.number,
+            amount: installment.amount,
+            due_date: installment.due_date,
+        };
+        let position = bucket.iter().position(|e| e.due_date > entry.due_date);
+        match position {
+            Some(index) => bucket.insert(index as u32, entry),
+            None => bucket.push_back(entry),
+        }
+        store(env, &DataKey::DueBucket(day), &bucket);
+        
+        let mut days = due_days(env);
+        if let Err(index) = days.binary_search(day) {
+            days.insert(index, day);
+            store(env, &DataKey::DueDays, &days);
+        }
+    }
+    
+    /// Drop an installment from the due-date index
+    fn remove_due(env: &Env, plan_id: u64, installment: &Installment) {
+        let day = installment.due_date / DUE_BUCKET_SECS;
+        let mut bucket = due_bucket(env, day);
+        let position = bucket.iter().position(|e| {
+            e.plan_id == plan_id && e.installment_number == installment.number
+        });
+        let index = match position {
+            Some(index) => index as u32,
+            None => return,
+        };
+        bucket.remove(index);
+        
+        if !bucket.is_empty() {
+            store(env, &DataKey::DueBucket(day), &bucket);
+            return;
+        }
+        
+        env.storage().persistent().remove(&DataKey::DueBucket(day));
+        let mut days = due_days(env);
+        if let Ok(index) = days.binary_search(day) {
+            days.remove(index);
+            store(env, &DataKey::DueDays, &days);
+        }
+    }
+    
+    fn due_bucket(env: &Env, day: u64) -> Vec<DueInstallment> {
+        load(env, &DataKey::DueBucket(day)).unwrap_or(Vec::new(env))
+    }
+    
+    /// Read an index entry, extending its TTL when it exists
+    fn load<K: IntoVal<Env, Val>, V: TryFromVal<Env, Val>>(env: &Env, key: &K) -> Option<V> {
+        let value = env.storage().persistent().get(key);
+        if value.is_some() {
+            BridgeContract::extend_persistent(env, key);
+        }
+        value
+    }
+    
+    /// Write an index entry and extend its TTL
+    fn store<K: IntoVal<Env, Val>, V: IntoVal<Env, Val>>(env: &Env, key: &K, value: &V) {
+        env.storage().persistent().set(key, value);
+        BridgeContract::extend_persistent(env, key);
+    }
+}
+
+// ============ EVENT EMISSION ============
+
+/// Emission of the plan lifecycle events
+/// 
+/// Every event goes through here: plan events get `(version, name, user,
+/// merchant)` topics, other events carry `seq` as data.
+/// Features that change an existing plan emit a dedicated event with the
+/// values before and after the change; status transitions always emit
+/// `status` on top of the feature's own event.
+mod events {
+    use super::*;
+    use soroban_sdk::{Symbol, Topics};
+    
+    /// Schema version leading the topics of every plan event
+    /// 
+    /// `v2` topics are `(version, name, user, merchant)`, so RPC filters can
+    /// subscribe to one counterparty; the data is `(seq, payload)`.
+    pub(crate) const SCHEMA_VERSION: Symbol = symbol_short!("v2");
+    
+    /// Previous schema version, `(version, name, plan_id, seq)` topics with
+    /// the payload as data, still emitted during its deprecation window
+    pub(crate) const PREVIOUS_VERSION: Symbol = symbol_short!("v1");
+    
+    /// Whether the unversioned `(name, plan_id)` topics are still emitted
+    /// 
+    /// Kept in parallel during the deprecation window so indexers can move
+    /// to the versioned topics without a data gap; a later upgrade drops it.
+    pub(crate) const EMIT_UNVERSIONED: bool = true;
+    
+    /// Next value of the global event sequence
+    /// 
+    /// Every event carries it, so consumers can spot a missed event and
+    /// backfill exactly the missing numbers.
+    fn next_seq(env: &Env) -> u64 {
+        let seq: u64 = env.storage().persistent().get(&DataKey::EventSeq).unwrap_or(0) + 1;
+        BridgeContract::set_counter(env, &DataKey::EventSeq, &seq);
+        seq
+    }
+    
+    fn publish<T: IntoVal<Env, Val>>(env: &Env, name: Symbol, plan_id: u64, data: T) {
+        let data: Val = data.into_val(env);
+        let seq = next_seq(env);
+        
+        // Plans are saved before their events, so the header is always there
+        let plan: Option<PlanHeader> = env.storage().persistent().get(&PlanKey::Plan(plan_id));
+        if let Some(plan) = plan {
+            env.events().publish((SCHEMA_VERSION, name.clone(), plan.user, plan.merchant), (seq, data));
+        }
+        env.events().publish((PREVIOUS_VERSION, name.clone(), plan_id, seq), data);
+        if EMIT_UNVERSIONED {
+            env.events().publish((name, plan_id), data);
+        }
+    }
+    
+    /// Publish a configuration or admin event: `topics` describe it and
+    /// the data is its sequence number
+    pub(crate) fn notice<T: Topics>(env: &Env, topics: T) {
+        let seq = next_seq(env);
+        env.events().publish(topics, seq);
+    }
+    
+    pub(crate) fn plan_created(env: &Env, event: PlanCreated) {
+        publish(env, symbol_short!("plan_new"), event.plan_id, event);
+    }
+    
+    pub(crate) fn plan_approved(env: &Env, event: PlanApproved) {
+        publish(env, symbol_short!("plan_ok"), event.plan_id, event);
+    }
+    
+    pub(crate) fn installment_paid(env: &Env, event: InstallmentPaid) {
+        publish(env, symbol_short!("inst_paid"), event.plan_id, event);
+    }
+    
+    pub(crate) fn plan_completed(env: &Env, event: PlanCompleted) {
+        publish(env, symbol_short!("plan_done"), event.plan_id, event);
+    }
+    
+    pub(crate) fn installment_failed(env: &Env, event: InstallmentFailed) {
+        publish(env, symbol_short!("inst_fail"), event.plan_id, event);
+    }
+    
+    pub(crate) fn installment_overdue(env: &Env, event: InstallmentOverdue) {
+        publish(env, symbol_short!("overdue"), event.plan_id, event);
+    }
+    
+    pub(crate) fn settlement_anchored(env: &Env, event: SettlementAnchored) {
+        publish(env, symbol_short!("anchored"), event.plan_id, event);
+    }
+    
+    pub(crate) fn plan_defaulted(env: &Env, event: PlanDefaulted) {
+        publish(env, symbol_short!("plan_dflt"), event.plan_id, event);
+    }
+    
+    pub(crate) fn auction_started(env: &Env, event: AuctionStarted) {
+        publish(env, symbol_short!("auc_start"), event.plan_id, event);
+    }
+    
+    pub(crate) fn plan_liquidated(env: &Env, event: PlanLiquidated) {
+        publish(env, symbol_short!("auc_bid"), event.plan_id, event);
+    }
+    
+    pub(crate) fn collateral_released(env: &Env, event: CollateralReleased) {
+        publish(env, symbol_short!("released"), event.plan_id, event);
+    }
+    
+    pub(crate) fn collateral_locked(env: &Env, event: CollateralChanged) {
+        publish(env, symbol_short!("locked"), event.plan_id, event);
+    }
+    
+    pub(crate) fn merchant_settled(env: &Env, event: MerchantSettled) {
+        publish(env, symbol_short!("settled"), event.plan_id, event);
+    }
+    
+    /// Partial unlock, or `freed` once the plan holds no locked shares
+    pub(crate) fn collateral_unlocked(env: &Env, event: CollateralChanged) {
+        let name = if event.locked_after == 0 { symbol_short!("freed") } else { symbol_short!("unlocked") };
+        publish(env, name, event.plan_id, event);
+    }
+    
+    pub(crate) fn rebate_claimed(env: &Env, event: RebateClaimed) {
+        publish(env, symbol_short!("rebate"), event.plan_id, event);
+    }
+    
+    /// Final snapshot of a plan (installments included) before it's pruned
+    pub(crate) fn plan_archived(env: &Env, plan: &BridgePlan) {
+        publish(env, symbol_short!("archived"), plan.plan_id, plan.clone());
+    }
+    
+    pub(crate) fn status_changed(env: &Env, plan_id: u64, before: &PlanStatus, after: &PlanStatus) {
+        publish(env, symbol_short!("status"), plan_id, PlanStatusChanged {
+            plan_id,
+            before: before.clone(),
+            after: after.clone(),
+            changed_at: env.ledger().timestamp(),
+        });
+    }
+    
+    pub(crate) fn freeze_changed(env: &Env, plan_id: u64, arbiter: Address, before: bool, after: bool) {
+        let name = if after { symbol_short!("frozen") } else { symbol_short!("unfrozen") };
+        publish(env, name, plan_id, PlanFreezeChanged { plan_id, arbiter, before, after });
+    }
+}
+
+// ============ TESTS WITH MOCK BUFFER ============
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use soroban_sdk::{testutils::Address as _, testutils::Events, testutils::IssuerFlags, testutils::Ledger, Env, Symbol, Vec as SorobanVec};
+    use soroban_sdk::token::StellarAssetClient;
+    
+    // Stateful MOCK buffer: every user starts with 10000 available shares
+    // and shares are priced 1:1 against the underlying token.
+    #[contracttype]
+    #[derive(Clone)]
+    pub enum MockKey {
+        Balance(Address),
+        Allowance(Address),
+        Received(Address),
+        Asset,
+    }
+
+    #[contract]
+    pub struct MockBuffer;
+
+    impl MockBuffer {
+        fn load(env: &Env, user: &Address) -> BufferBalance {
+            env.storage()
+                .instance()
+                .get(&MockKey::Balance(user.clone()))
+                .unwrap_or(BufferBalance {
+                    available_shares: 10000,
+                    protected_shares: 0,
+                    total_deposited: 10000,
+                    last_deposit_ts: 0,
+                    version: 0,
+                })
+        }
+
+        fn spend_allowance(env: &Env, user: &Address, shares: i128) {
+            let key = MockKey::Allowance(user.clone());
+            let allowance: i128 = env.storage().instance().get(&key).unwrap_or(0);
+            if allowance < shares {
+                panic!("Insufficient allowance");
+            }
+            env.storage().instance().set(&key, &(allowance - shares));
+        }
+
+        fn credit(env: &Env, to: &Address, shares: i128) {
+            let key = MockKey::Received(to.clone());
+            let received: i128 = env.storage().instance().get(&key).unwrap_or(0);
+            env.storage().instance().set(&key, &(received + shares));
+        }
+
+        fn store(env: &Env, user: &Address, mut bal: BufferBalance) -> BufferBalance {
+            bal.version += 1;
+            env.storage().instance().set(&MockKey::Balance(user.clone()), &bal);
+            bal
+        }
+    }
+
+    #[contractimpl]
+    impl MockBuffer {
+        pub fn get_received(env: Env, to: Address) -> i128 {
+            env.storage().instance().get(&MockKey::Received(to)).unwrap_or(0)
+        }
+
+        pub fn set_shares(env: Env, user: Address, available: i128, protected: i128) {
+            let mut bal = Self::load(&env, &user);
+            bal.available_shares = available;
+            bal.protected_shares = protected;
+            Self::store(&env, &user, bal);
+        }
+
+        pub fn get_balance(env: Env, user: Address) -> BufferBalance {
+            Self::load(&env, &user)
+        }
+
+        pub fn lock_shares(env: Env, user: Address, shares: i128) -> LockResult {
+            let mut bal = Self::load(&env, &user);
+            if bal.available_shares < shares {
+                panic!("Insufficient available");
+            }
+            bal.available_shares -= shares;
+            bal.protected_shares += shares;
+            let bal = Self::store(&env, &user, bal);
+            LockResult {
+                shares_locked: shares,
+                new_available: bal.available_shares,
+                new_protected: bal.protected_shares,
+            }
+        }
+
+        pub fn check_and_lock(env: Env, user: Address, amount: i128) -> BufferBalance {
+            let bal = Self::load(&env, &user);
+            let top_up = amount - bal.protected_shares;
+            if top_up > 0 && top_up <= bal.available_shares {
+                Self::lock_shares(env, user, top_up);
+            }
+            bal
+        }
+        
+        pub fn unlock_shares(env: Env, user: Address, shares: i128) -> LockResult {
+            let mut bal = Self::load(&env, &user);
+            if bal.protected_shares < shares {
+                panic!("Insufficient protected");
+            }
+            bal.protected_shares -= shares;
+            bal.available_shares += shares;
+            let bal = Self::store(&env, &user, bal);
+            LockResult {
+                shares_locked: shares,
+                new_available: bal.available_shares,
+                new_protected: bal.protected_shares,
+            }
+        }
+
+        pub fn increase_bridge_allowance(env: Env, user: Address, shares: i128) -> i128 {
+            user.require_auth();
+            let allowance = Self::get_bridge_allowance(env.clone(), user.clone()) + shares;
+            env.storage().instance().set(&MockKey::Allowance(user), &allowance);
+            allowance
+        }
+
+        pub fn get_bridge_allowance(env: Env, user: Address) -> i128 {
+            env.storage().instance().get(&MockKey::Allowance(user)).unwrap_or(0)
+        }
+
+        pub fn debit_available(env: Env, user: Address, shares: i128, to: Address) -> WithdrawResult {
+            Self::spend_allowance(&env, &user, shares);
+            Self::credit(&env, &to, shares);
+            let mut bal = Self::load(&env, &user);
+            if bal.available_shares < shares {
+                panic!("Insufficient available");
+            }
+            bal.available_shares -= shares;
+            let bal = Self::store(&env, &user, bal);
+            WithdrawResult {
+                shares_burned: shares,
+                amounts_received: Vec::from_array(&env, [shares]),
+                new_available_balance: bal.available_shares,
+                from_protected: false,
+            }
+        }
+
+        pub fn debit_protected(env: Env, user: Address, shares: i128, to: Address) -> WithdrawResult {
+            Self::spend_allowance(&env, &user, shares);
+            Self::credit(&env, &to, shares);
+            let mut bal = Self::load(&env, &user);
+            if bal.protected_shares < shares {
+                panic!("Insufficient protected");
+            }
+            bal.protected_shares -= shares;
+            let bal = Self::store(&env, &user, bal);
+            WithdrawResult {
+                shares_burned: shares,
+                amounts_received: Vec::from_array(&env, [shares]),
+                new_available_balance: bal.available_shares,
+                from_protected: true,
+            }
+        }
+
+        pub fn get_values(env: Env, user: Address) -> (i128, i128, i128) {
+            let bal = Self::load(&env, &user);
+            (bal.available_shares, bal.protected_shares, bal.available_shares + bal.protected_shares)
+        }
+
+        pub fn shares_for_amount(_env: Env, amount: i128) -> i128 {
+            amount
+        }
+
+        pub fn set_asset(env: Env, asset: Address) {
+            env.storage().instance().set(&MockKey::Asset, &asset);
+        }
+
+        pub fn get_asset(env: Env) -> Address {
+            env.storage()
+                .instance()
+                .get(&MockKey::Asset)
+                .unwrap_or(env.current_contract_address())
+        }
+    }
+
+    #[contract]
+    pub struct MockScore;
+
+    #[contractimpl]
+    impl MockScore {
+        pub fn set_score(env: Env, user: Address, score: u32) {
+            env.storage().instance().set(&user, &score);
+        }
+
+        pub fn get_score(env: Env, user: Address) -> u32 {
+            env.storage().instance().get(&user).unwrap_or(0)
+        }
+    }
+
+    #[contract]
+    pub struct MockCompliance;
+
+    #[contractimpl]
+    impl MockCompliance {
+        pub fn set_eligible(env: Env, user: Address, eligible: bool) {
+            env.storage().instance().set(&user, &eligible);
+        }
+
+        pub fn is_eligible(env: Env, user: Address) -> bool {
+            env.storage().instance().get(&user).unwrap_or(false)
+        }
+    }
+
+    #[contract]
+    pub struct MockReadModel;
+
+    #[contractimpl]
+    impl MockReadModel {
+        pub fn set_broken(env: Env, broken: bool) {
+            env.storage().instance().set(&symbol_short!("broken"), &broken);
+        }
+
+        /// Record what `account` had received from `buffer` at each update
+        pub fn set_probe(env: Env, buffer: Address, account: Address) {
+            env.storage().instance().set(&symbol_short!("probe"), &(buffer, account));
+        }
+
+        pub fn on_plan_update(env: Env, update: PlanUpdate) {
+            if env.storage().instance().get(&symbol_short!("broken")).unwrap_or(false) {
+                panic!("read model unavailable");
+            }
+            let mut updates = Self::updates(env.clone());
+            updates.push_back(update);
+            env.storage().instance().set(&symbol_short!("updates"), &updates);
+
+            let probe: Option<(Address, Address)> = env.storage().instance().get(&symbol_short!("probe"));
+            if let Some((buffer, account)) = probe {
+                let mut received = Self::probed(env.clone());
+                received.push_back(MockBufferClient::new(&env, &buffer).get_received(&account));
+                env.storage().instance().set(&symbol_short!("probed"), &received);
+            }
+        }
+
+        pub fn updates(env: Env) -> Vec<PlanUpdate> {
+            env.storage().instance().get(&symbol_short!("updates")).unwrap_or(Vec::new(&env))
+        }
+
+        pub fn probed(env: Env) -> Vec<i128> {
+            env.storage().instance().get(&symbol_short!("probed")).unwrap_or(Vec::new(&env))
+        }
+    }
+
+    // Governance contract holding the admin role: it authorizes bridge
+    // calls as the direct invoker, without any signer.
+    #[contract]
+    pub struct MockGovernance;
+
+    #[contractimpl]
+    impl MockGovernance {
+        pub fn accept(env: Env, bridge: Address) {
+            BridgeContractClient::new(&env, &bridge).accept_admin();
+        }
+
+        pub fn propose(env: Env, bridge: Address, change: ParamChange) -> u32 {
+            BridgeContractClient::new(&env, &bridge)
+                .propose_change(&env.current_contract_address(), &change)
+        }
+    }
+
+    // Caller written against panicking semantics: it uses the generated
+    // client's non-`try_` methods, or raises with `panic_with_error!`
+    #[contract]
+    pub struct MockLegacyCaller;
+
+    #[contractimpl]
+    impl MockLegacyCaller {
+        pub fn collect(env: Env, bridge: Address, plan_id: u64, installment_number: u32) -> PaymentSource {
+            let caller = env.current_contract_address();
+            BridgeContractClient::new(&env, &bridge).collect_installment(&plan_id, &installment_number, &caller)
+        }
+
+        pub fn raise(env: Env) {
+            soroban_sdk::panic_with_error!(&env, ContractError::AlreadyPaid);
+        }
+    }
+
+    pub struct TestContext {
+        pub env: Env,
+        pub admin: Address,
+        pub user: Address,
+        pub merchant: Address,
+        pub collector: Address,
+        pub buffer: Address,
+        pub bridge: Address,
+    }
+
+    impl TestContext {
+        pub fn new() -> Self {
+            let env = Env::default();
+            env.mock_all_auths();
+            env.ledger().set_timestamp(1000);
+
+            let buffer = env.register(MockBuffer, ());
+            let bridge = env.register(BridgeContract, ());
+
+            let admin = Address::generate(&env);
+            let config = Config::new(buffer.clone());
+            let client = BridgeContractClient::new(&env, &bridge);
+            client.initialize(&admin, &config);
+
+            let collector = Address::generate(&env);
+            client.set_collector(&admin, &collector, &true);
+
+            let merchant = Address::generate(&env);
+            let name_hash = BytesN::from_array(&env, &[7u8; 32]);
+            client.register_merchant(&merchant, &name_hash, &merchant, &0);
+            client.approve_merchant(&admin, &merchant);
+
+            Self {
+                env: env.clone(),
+                admin,
+                user: Address::generate(&env),
+                merchant,
+                collector,
+                buffer,
+                bridge,
+            }
+        }
+
+        pub fn client(&self) -> BridgeContractClient<'_> {
+            BridgeContractClient::new(&self.env, &self.bridge)
+        }
+
+        pub fn buffer_client(&self) -> MockBufferClient<'_> {
+            MockBufferClient::new(&self.env, &self.buffer)
+        }
+
+        pub fn advance_time(&self, seconds: u64) {
+            self.env.ledger().set_timestamp(self.env.ledger().timestamp() + seconds);
+        }
+    }
+
+    #[test]
+    fn test_create_plan_basic() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+        let plan = client.get_plan(&plan_id);
+
+        assert_eq!(plan.user, ctx.user);
+        assert_eq!(plan.merchant, ctx.merchant);
+        assert_eq!(plan.total_amount, 3000);
+        assert_eq!(plan.installments.len(), 3);
+    }
+
+    #[test]
+    fn test_full_lifecycle() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+
+        ctx.advance_time(1500);
+        let source = client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(source.to_u32(), 0);
+
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &2, &ctx.collector);
+
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &3, &ctx.collector);
+
+        let final_plan = client.get_plan(&plan_id);
+        assert_eq!(final_plan.status, PlanStatus::Completed);
+    }
+
+    #[test]
+    fn test_ltv_max_allowed() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        // Buffer total = 10000, LTV 80% = 8000 maximum allowed
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &8000, &3, &due_dates);
+        let plan = client.get_plan(&plan_id);
+
+        assert_eq!(plan.total_amount, 8000);
+        assert_eq!(plan.status, PlanStatus::Active);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #15)")] // ExceedsMaxLTV
+    fn test_ltv_exceeds_maximum() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        // Attempt to create plan for 9000 when maximum is 8000 (80% of 10000)
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        client.create_plan(&ctx.user, &ctx.merchant, &9000, &3, &due_dates);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #15)")] // ExceedsMaxLTV
+    fn test_ltv_at_100_percent_fails() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        // Attempting to use 100% of buffer (10000) should fail
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        client.create_plan(&ctx.user, &ctx.merchant, &10000, &3, &due_dates);
+    }
+
+    #[test]
+    fn test_health_of_new_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &6000, &3, &due_dates);
+        let health = client.get_health(&plan_id);
+
+        assert_eq!(health.outstanding_amount, 6000);
+        assert_eq!(health.locked_shares, 6000);
+        assert_eq!(health.locked_value, 6000);
+        assert_eq!(health.collateral_value, 10000);
+        assert_eq!(health.ltv_bps, 6000);
+        // 10000 * 85% / 6000 = 1.4166
+        assert_eq!(health.health_factor_bps, 14166);
+        assert!(!health.at_risk);
+    }
+
+    #[test]
+    fn test_health_flags_risk_when_buffer_drops() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &6000, &3, &due_dates);
+
+        // User withdraws most of the available balance
+        ctx.buffer_client().set_shares(&ctx.user, &500, &6000);
+        let health = client.get_health(&plan_id);
+
+        assert_eq!(health.collateral_value, 6500);
+        assert_eq!(health.health_factor_bps, 9208);
+        assert!(health.at_risk);
+    }
+
+    #[test]
+    fn test_health_of_completed_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        let health = client.get_health(&plan_id);
+
+        assert_eq!(health.outstanding_amount, 0);
+        assert_eq!(health.ltv_bps, 0);
+        assert!(!health.at_risk);
+    }
+
+    // Creates a 3000 plan and drains the buffer so installment 1 can't be paid
+    fn defaulted_plan(ctx: &TestContext) -> u64 {
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+
+        ctx.buffer_client().set_shares(&ctx.user, &0, &800);
+        ctx.advance_time(1500);
+        client.declare_default(&plan_id, &1);
+        plan_id
+    }
+
+    fn debt_token(ctx: &TestContext, holder: &Address, amount: i128) -> Address {
+        let issuer = Address::generate(&ctx.env);
+        let token = ctx.env.register_stellar_asset_contract_v2(issuer).address();
+        StellarAssetClient::new(&ctx.env, &token).mint(holder, &amount);
+        token
+    }
+
+    /// Buffer asset auction bids are paid in, with `amount` minted to `holder`
+    fn auction_token(ctx: &TestContext, holder: &Address, amount: i128) -> Address {
+        let token = debt_token(ctx, holder, amount);
+        ctx.buffer_client().set_asset(&token);
+        token
+    }
+
+    #[test]
+    fn test_declare_default() {
+        let ctx = TestContext::new();
+        let plan_id = defaulted_plan(&ctx);
+        let plan = ctx.client().get_plan(&plan_id);
+
+        assert_eq!(plan.status, PlanStatus::Defaulted);
+        assert_eq!(plan.installments.get(0).unwrap().status, InstallmentStatus::Failed);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #22)")] // StillCollectable
+    fn test_declare_default_rejects_collectable_installment() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+
+        ctx.advance_time(1500);
+        client.declare_default(&plan_id, &1);
+    }
+
+    #[test]
+    fn test_auction_price_decays_to_floor() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+        let token = auction_token(&ctx, &ctx.merchant, 0);
+
+        // Bids are paid in the Buffer's asset, not one the merchant picks
+        let auction = client.start_auction(&plan_id);
+        assert_eq!(auction.debt_token, token);
+        assert_eq!(auction.collateral_shares, 800);
+        assert_eq!(auction.debt_amount, 3000);
+        assert_eq!(client.get_auction_price(&plan_id), 4500);
+
+        ctx.advance_time(AUCTION_DURATION_SECS / 2);
+        assert_eq!(client.get_auction_price(&plan_id), 3000);
+
+        ctx.advance_time(AUCTION_DURATION_SECS);
+        assert_eq!(client.get_auction_price(&plan_id), 1500);
+    }
+
+    #[test]
+    fn test_auction_bid_settles_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+        let liquidator = Address::generate(&ctx.env);
+        let token = auction_token(&ctx, &liquidator, 5000);
+
+        client.start_auction(&plan_id);
+        ctx.advance_time(AUCTION_DURATION_SECS / 2);
+
+        let paid = client.bid(&plan_id, &liquidator, &3000);
+        assert_eq!(paid, 3000);
+
+        let token_client = TokenClient::new(&ctx.env, &token);
+        assert_eq!(token_client.balance(&ctx.merchant), 3000);
+        assert_eq!(token_client.balance(&liquidator), 2000);
+
+        let plan = client.get_plan(&plan_id);
+        assert_eq!(plan.status, PlanStatus::Liquidated);
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).protected_shares, 0);
+        assert!(client.get_auction(&plan_id).settled);
+    }
+
+    #[test]
+    fn test_auction_surplus_goes_to_user() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+        let liquidator = Address::generate(&ctx.env);
+        let token = auction_token(&ctx, &liquidator, 5000);
+
+        client.start_auction(&plan_id);
+        client.bid(&plan_id, &liquidator, &4500);
+
+        let token_client = TokenClient::new(&ctx.env, &token);
+        assert_eq!(token_client.balance(&ctx.merchant), 3000);
+        assert_eq!(token_client.balance(&ctx.user), 1500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #20)")] // PriceAboveMax
+    fn test_auction_bid_respects_max_price() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+        let liquidator = Address::generate(&ctx.env);
+        auction_token(&ctx, &liquidator, 5000);
+
+        client.start_auction(&plan_id);
+        client.bid(&plan_id, &liquidator, &4000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #16)")] // PlanNotDefaulted
+    fn test_auction_requires_default() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        client.start_auction(&plan_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #23)")] // AlreadyInitialized
+    fn test_initialize_only_once() {
+        let ctx = TestContext::new();
+        let config = Config::new(ctx.buffer.clone());
+        ctx.client().initialize(&ctx.admin, &config);
+    }
+
+    #[test]
+    fn test_collateral_factor_locks_more_shares() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        // Volatile asset: 150% collateral required
+        client.execute_change(&client.set_collateral_factor(&ctx.admin, &ctx.buffer, &15000));
+        assert_eq!(client.get_collateral_factor(&ctx.buffer), 15000);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &6000, &3, &due_dates);
+        let plan = client.get_plan(&plan_id);
+
+        assert_eq!(plan.total_shares, 9000);
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).protected_shares, 9000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #4)")] // InsufficientAvailable
+    fn test_collateral_factor_rejects_undercollateralized_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        client.execute_change(&client.set_collateral_factor(&ctx.admin, &ctx.buffer, &15000));
+
+        // 7000 is within the 80% LTV but needs 10500 locked
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        client.create_plan(&ctx.user, &ctx.merchant, &7000, &3, &due_dates);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #46)")] // InvalidConfig
+    fn test_collateral_factor_below_full_collateral_rejected() {
+        let ctx = TestContext::new();
+        ctx.client().set_collateral_factor(&ctx.admin, &ctx.buffer, &9000);
+    }
+
+    fn setup_underwriting(ctx: &TestContext, fund_capacity: i128) -> MockScoreClient<'_> {
+        let score_source = ctx.env.register(MockScore, ());
+        let client = ctx.client();
+        let change_id = client.set_underwriting(&ctx.admin, &UnderwritingConfig {
+            score_source: score_source.clone(),
+            insurance_fund: Address::generate(&ctx.env),
+            min_score: 700,
+            lock_ratio_bps: 6000,
+            fund_capacity,
+        });
+        client.execute_change(&change_id);
+        MockScoreClient::new(&ctx.env, &score_source)
+    }
+
+    #[test]
+    fn test_strong_score_locks_reduced_collateral() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        setup_underwriting(&ctx, 10000).set_score(&ctx.user, &750);
+
+        // 9000 exceeds the 80% LTV, but only 60% (5400) must be collateralized
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &9000, &3, &due_dates);
+        let plan = client.get_plan(&plan_id);
+
+        assert_eq!(plan.total_shares, 5400);
+        assert_eq!(plan.insured_amount, 3600);
+        assert_eq!(client.get_insured_outstanding(), 3600);
+    }
+
+    #[test]
+    fn test_weak_score_requires_full_collateral() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        setup_underwriting(&ctx, 10000).set_score(&ctx.user, &500);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &6000, &3, &due_dates);
+        let plan = client.get_plan(&plan_id);
+
+        assert_eq!(plan.total_shares, 6000);
+        assert_eq!(plan.insured_amount, 0);
+    }
+
+    #[test]
+    fn test_insurance_capacity_falls_back_to_full_collateral() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        setup_underwriting(&ctx, 1000).set_score(&ctx.user, &750);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &6000, &3, &due_dates);
+
+        assert_eq!(client.get_plan(&plan_id).insured_amount, 0);
+        assert_eq!(client.get_insured_outstanding(), 0);
+    }
+
+    #[test]
+    fn test_completion_releases_insured_exposure() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        setup_underwriting(&ctx, 10000).set_score(&ctx.user, &750);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        assert_eq!(client.get_insured_outstanding(), 400);
+
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(client.get_insured_outstanding(), 0);
+    }
+
+    // 150% collateralized plan paid through Protected: the extra shares
+    // remain locked once every installment is paid
+    fn completed_plan_with_locked_shares(ctx: &TestContext) -> u64 {
+        let client = ctx.client();
+        client.execute_change(&client.set_collateral_factor(&ctx.admin, &ctx.buffer, &15000));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &4000, &2, &due_dates);
+
+        // Remove available funds so both installments are paid from Protected
+        ctx.buffer_client().set_shares(&ctx.user, &0, &6000);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &2, &ctx.collector);
+        plan_id
+    }
+
+    #[test]
+    fn test_release_delay_holds_collateral() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.execute_change(&client.set_release_delay(&ctx.admin, &86400));
+
+        let plan_id = completed_plan_with_locked_shares(&ctx);
+        let plan = client.get_plan(&plan_id);
+
+        assert_eq!(plan.status, PlanStatus::Completed);
+        assert_eq!(plan.protected_shares, 2000);
+        assert_eq!(plan.release_at, 3000 + 86400);
+        assert!(client.try_release_collateral(&plan_id).is_err());
+
+        ctx.advance_time(86400);
+        assert_eq!(client.release_collateral(&plan_id), 2000);
+        assert_eq!(client.get_plan(&plan_id).protected_shares, 0);
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).available_shares, 2000);
+    }
+
+    #[test]
+    fn test_no_release_delay_unlocks_immediately() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let plan_id = completed_plan_with_locked_shares(&ctx);
+        let plan = client.get_plan(&plan_id);
+
+        assert_eq!(plan.protected_shares, 0);
+        assert_eq!(plan.release_at, 0);
+        assert!(client.try_release_collateral(&plan_id).is_err());
+    }
+
+    #[test]
+    fn test_reconcile_releases_orphaned_lock() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+
+        // Buffer holds 500 more protected shares than the plan accounts for
+        ctx.buffer_client().set_shares(&ctx.user, &8000, &2500);
+
+        assert_eq!(client.reconcile_locks(&ctx.user), 500);
+        let bal = ctx.buffer_client().get_balance(&ctx.user);
+        assert_eq!(bal.protected_shares, 2000);
+        assert_eq!(bal.available_shares, 8500);
+    }
+
+    #[test]
+    fn test_reconcile_without_active_plans_releases_everything() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        // Paid from Available: the plan no longer needs its lock
+        assert_eq!(client.reconcile_locks(&ctx.user), 1000);
+        assert_eq!(client.reconcile_locks(&ctx.user), 0);
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).protected_shares, 0);
+    }
+
+    #[test]
+    fn test_create_plan_grants_allowance_consumed_by_collections() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+        assert_eq!(ctx.buffer_client().get_bridge_allowance(&ctx.user), 3000);
+
+        ctx.advance_time(1500);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(ctx.buffer_client().get_bridge_allowance(&ctx.user), 2000);
+    }
+
+    #[test]
+    fn test_insured_plan_allowance_covers_every_installment() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        setup_underwriting(&ctx, 10000).set_score(&ctx.user, &750);
+
+        // Only 600 shares are locked but 1000 worth of installments is due
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        assert_eq!(ctx.buffer_client().get_bridge_allowance(&ctx.user), 1000);
+    }
+
+    #[test]
+    fn test_buffer_address_is_persisted() {
+        let ctx = TestContext::new();
+        assert_eq!(ctx.client().get_buffer(), ctx.buffer);
+    }
+
+    #[test]
+    fn test_admin_is_persisted() {
+        let ctx = TestContext::new();
+        assert_eq!(ctx.client().admin(), ctx.admin);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #24)")] // NotInitialized
+    fn test_create_plan_requires_initialize() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let bridge = env.register(BridgeContract, ());
+        let client = BridgeContractClient::new(&env, &bridge);
+
+        let due_dates = SorobanVec::from_array(&env, [2000u64]);
+        client.create_plan(&Address::generate(&env), &Address::generate(&env), &1000, &1, &due_dates);
+    }
+
+    #[test]
+    fn test_cross_margin_reuses_shared_lock() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_cross_margin(&ctx.user, &true);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let first = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).protected_shares, 2000);
+        assert_eq!(client.get_plan(&first).protected_shares, 0);
+        assert!(client.get_plan(&first).cross_margin);
+
+        // First installment paid from Available: the shared lock now has
+        // 1000 spare shares that back the next plan without a new lock
+        ctx.advance_time(1000);
+        client.collect_installment(&first, &1, &ctx.collector);
+        assert_eq!(client.get_cross_exposure(&ctx.user), 1000);
+
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates.slice(1..));
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).protected_shares, 2000);
+        assert_eq!(client.get_cross_exposure(&ctx.user), 2000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #15)")] // ExceedsMaxLTV
+    fn test_cross_margin_checks_aggregate_exposure() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_cross_margin(&ctx.user, &true);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        client.create_plan(&ctx.user, &ctx.merchant, &5000, &2, &due_dates);
+        client.create_plan(&ctx.user, &ctx.merchant, &4000, &2, &due_dates);
+    }
+
+    #[test]
+    fn test_reconcile_keeps_cross_margin_lock() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_cross_margin(&ctx.user, &true);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        assert_eq!(client.reconcile_locks(&ctx.user), 0);
+
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(client.reconcile_locks(&ctx.user), 1000);
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).protected_shares, 1000);
+        assert_eq!(client.get_cross_locked(&ctx.user), 1000);
+    }
+
+    #[test]
+    fn test_rebate_accrues_on_locked_collateral() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let treasury = Address::generate(&ctx.env);
+        let token = debt_token(&ctx, &treasury, 1_000_000);
+
+        // 10% per year on locked shares
+        let config = RebateConfig { token: token.clone(), rate_bps: 1000 };
+        client.execute_change(&client.set_rebate_config(&ctx.admin, &config));
+        client.fund_rebates(&treasury, &1000);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [1000 + SECONDS_PER_YEAR]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &5000, &1, &due_dates);
+
+        ctx.advance_time(SECONDS_PER_YEAR / 2);
+        assert_eq!(client.get_rebate(&plan_id), 250);
+        assert!(client.try_claim_rebate(&plan_id).is_err());
+
+        ctx.advance_time(SECONDS_PER_YEAR / 2);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        assert_eq!(client.claim_rebate(&plan_id), 500);
+        assert_eq!(TokenClient::new(&ctx.env, &token).balance(&ctx.user), 500);
+        assert_eq!(client.get_rebate_pool(), 500);
+        assert_eq!(client.get_rebate(&plan_id), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #32)")] // InsufficientRebatePool
+    fn test_rebate_claim_limited_by_pool() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let treasury = Address::generate(&ctx.env);
+        let token = debt_token(&ctx, &treasury, 1_000_000);
+
+        let config = RebateConfig { token, rate_bps: 1000 };
+        client.execute_change(&client.set_rebate_config(&ctx.admin, &config));
+        client.fund_rebates(&treasury, &100);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [1000 + SECONDS_PER_YEAR]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &5000, &1, &due_dates);
+        ctx.advance_time(SECONDS_PER_YEAR);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        client.claim_rebate(&plan_id);
+    }
+
+    #[test]
+    fn test_reserve_forces_protected_fallback() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &5000, &2, &due_dates);
+
+        // 5000 available, of which 4000 are reserved
+        client.set_reserve(&ctx.user, &4000);
+        ctx.advance_time(1000);
+
+        let source = client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert!(source.is_protected());
+
+        let balance = ctx.buffer_client().get_balance(&ctx.user);
+        assert_eq!(balance.available_shares, 5000);
+        assert_eq!(balance.protected_shares, 2500);
+    }
+
+    #[test]
+    fn test_reserve_only_funds_allow_default() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+
+        ctx.buffer_client().set_shares(&ctx.user, &1500, &800);
+        client.set_reserve(&ctx.user, &1000);
+        ctx.advance_time(1500);
+
+        assert!(client.try_collect_installment(&plan_id, &1, &ctx.collector).is_err());
+        client.declare_default(&plan_id, &1);
+        assert_eq!(client.get_plan(&plan_id).status, PlanStatus::Defaulted);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #1)")] // InvalidAmount
+    fn test_negative_reserve_rejected() {
+        let ctx = TestContext::new();
+        ctx.client().set_reserve(&ctx.user, &-1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #33)")] // Paused
+    fn test_pause_blocks_create_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.pause(&ctx.admin);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+    }
+
+    #[test]
+    fn test_pause_blocks_collection_until_unpaused() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1000);
+
+        client.pause(&ctx.admin);
+        assert!(client.is_paused());
+        assert!(client.try_collect_installment(&plan_id, &1, &ctx.collector).is_err());
+        // Queries stay available
+        assert_eq!(client.get_plan(&plan_id).status, PlanStatus::Active);
+
+        client.unpause(&ctx.admin);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(client.get_plan(&plan_id).status, PlanStatus::Completed);
+    }
+
+    #[test]
+    fn test_migrate_moves_legacy_buffer_into_config() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        // Recreate a v0 layout: bare Buffer key, no Config, no version
+        ctx.env.as_contract(&ctx.bridge, || {
+            let storage = ctx.env.storage().instance();
+            storage.remove(&DataKey::Config);
+            storage.remove(&DataKey::Version);
+            storage.set(&DataKey::Buffer, &ctx.buffer);
+        });
+        assert_eq!(client.get_version(), 0);
+        assert!(client.try_get_buffer().is_err());
+
+        assert_eq!(client.migrate(), STORAGE_VERSION);
+        assert_eq!(client.get_buffer(), ctx.buffer);
+        assert_eq!(client.get_version(), STORAGE_VERSION);
+    }
+
+    #[test]
+    fn test_migrate_adds_compliance_to_v1_config() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        ctx.env.as_contract(&ctx.bridge, || {
+            let storage = ctx.env.storage().instance();
+            storage.set(&DataKey::Config, &ConfigV1 { buffer: ctx.buffer.clone() });
+            storage.set(&DataKey::Version, &1u32);
+        });
+
+        client.migrate();
+        assert_eq!(client.get_config(), Config::new(ctx.buffer.clone()));
+    }
+
+    /// `config` in the layout of storage versions 3 and 4
+    fn v4_config(config: &Config) -> ConfigV4 {
+        ConfigV4 {
+            buffer: config.buffer.clone(),
+            compliance: config.compliance.clone(),
+            max_installments: config.max_installments,
+            max_ltv_bps: config.max_ltv_bps,
+            liquidation_threshold_bps: config.liquidation_threshold_bps,
+            routing: config.routing,
+            grace_period_secs: config.grace_period_secs,
+            fee_bps: config.fee_bps,
+            fee_recipient: config.fee_recipient.clone(),
+        }
+    }
+
+    #[test]
+    fn test_migrate_moves_counters_out_of_instance() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let config = v4_config(&client.get_config());
+        ctx.env.as_contract(&ctx.bridge, || {
+            let storage = ctx.env.storage().instance();
+            storage.set(&DataKey::Config, &config);
+            storage.set(&DataKey::PlanCounter, &7u64);
+            storage.set(&DataKey::TotalOutstanding, &500i128);
+            storage.set(&DataKey::Version, &3u32);
+        });
+
+        client.migrate();
+        assert_eq!(client.get_plan_count(), 7);
+        assert_eq!(client.get_total_outstanding(), 500);
+        ctx.env.as_contract(&ctx.bridge, || {
+            assert!(!ctx.env.storage().instance().has(&DataKey::PlanCounter));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #34)")] // AlreadyMigrated
+    fn test_migrate_rejects_current_version() {
+        let ctx = TestContext::new();
+        ctx.client().migrate();
+    }
+
+    #[test]
+    fn test_collector_pays_merchant_without_user_signature() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1000);
+
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(
+            ctx.env.auths().first().map(|(signer, _)| signer.clone()),
+            Some(ctx.collector.clone())
+        );
+        assert_eq!(client.get_plan(&plan_id).status, PlanStatus::Completed);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #35)")] // NotCollector
+    fn test_unregistered_caller_cannot_collect() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1000);
+
+        client.set_collector(&ctx.admin, &ctx.collector, &false);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+    }
+
+    #[test]
+    fn test_only_user_can_pay_early() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+
+        assert!(client.try_collect_installment(&plan_id, &1, &ctx.collector).is_err());
+        client.collect_installment(&plan_id, &1, &ctx.user);
+        assert_eq!(
+            client.get_plan(&plan_id).installments.get(0).unwrap().status,
+            InstallmentStatus::Paid
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #38)")] // MerchantNotApproved
+    fn test_create_plan_rejects_unregistered_merchant() {
+        let ctx = TestContext::new();
+        let stranger = Address::generate(&ctx.env);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        ctx.client().create_plan(&ctx.user, &stranger, &1000, &1, &due_dates);
+    }
+
+    #[test]
+    fn test_merchant_onboarding_lifecycle() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let merchant = Address::generate(&ctx.env);
+        let payout = Address::generate(&ctx.env);
+        let name_hash = BytesN::from_array(&ctx.env, &[1u8; 32]);
+
+        client.register_merchant(&merchant, &name_hash, &payout, &2);
+        assert_eq!(client.get_merchant(&merchant).status, MerchantStatus::Pending);
+        assert!(!client.is_merchant(&merchant));
+
+        client.approve_merchant(&ctx.admin, &merchant);
+        assert!(client.is_merchant(&merchant));
+
+        client.suspend_merchant(&ctx.admin, &merchant);
+        assert!(!client.is_merchant(&merchant));
+        assert!(client.try_register_merchant(&merchant, &name_hash, &payout, &2).is_err());
+    }
+
+    #[test]
+    fn test_auction_pays_merchant_payout_address() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let merchant = Address::generate(&ctx.env);
+        let payout = Address::generate(&ctx.env);
+        client.register_merchant(&merchant, &BytesN::from_array(&ctx.env, &[2u8; 32]), &payout, &0);
+        client.approve_merchant(&ctx.admin, &merchant);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &merchant, &1000, &1, &due_dates);
+        ctx.buffer_client().set_shares(&ctx.user, &0, &500);
+        ctx.advance_time(1000);
+        client.declare_default(&plan_id, &1);
+
+        let liquidator = Address::generate(&ctx.env);
+        let token = auction_token(&ctx, &liquidator, 5000);
+        client.start_auction(&plan_id);
+        client.bid(&plan_id, &liquidator, &5000);
+
+        assert_eq!(TokenClient::new(&ctx.env, &token).balance(&payout), 1000);
+        assert_eq!(TokenClient::new(&ctx.env, &token).balance(&merchant), 0);
+    }
+
+    #[test]
+    fn test_compliance_hook_gates_create_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let compliance = ctx.env.register(MockCompliance, ());
+        let mut config = client.get_config();
+        config.compliance = Some(compliance.clone());
+        client.execute_change(&client.update_config(&config));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        assert!(client.try_create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates).is_err());
+
+        MockComplianceClient::new(&ctx.env, &compliance).set_eligible(&ctx.user, &true);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+
+        // Clearing the hook lifts the policy
+        config.compliance = None;
+        client.execute_change(&client.update_config(&config));
+        let other = Address::generate(&ctx.env);
+        ctx.buffer_client().set_shares(&other, &10000, &0);
+        client.create_plan(&other, &ctx.merchant, &1000, &1, &due_dates);
+    }
+
+    #[test]
+    fn test_granted_role_gates_privileged_calls() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let operator = Address::generate(&ctx.env);
+
+        assert!(client.try_pause(&operator).is_err());
+
+        client.grant_role(&Role::Operator, &operator);
+        assert!(client.has_role(&Role::Operator, &operator));
+        client.pause(&operator);
+        assert!(client.is_paused());
+
+        // Roles are distinct: an operator can't touch treasury parameters
+        assert!(client.try_set_collateral_factor(&operator, &ctx.buffer, &15000).is_err());
+
+        client.revoke_role(&Role::Operator, &operator);
+        assert!(!client.has_role(&Role::Operator, &operator));
+        assert!(client.try_unpause(&operator).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #41)")] // Unauthorized
+    fn test_missing_role_is_unauthorized() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let arbiter = Address::generate(&ctx.env);
+        client.grant_role(&Role::Arbiter, &arbiter);
+
+        client.set_rebate_config(&arbiter, &RebateConfig { token: ctx.buffer.clone(), rate_bps: 100 });
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #46)")] // InvalidConfig
+    fn test_admin_role_cannot_be_granted() {
+        let ctx = TestContext::new();
+        ctx.client().grant_role(&Role::Admin, &ctx.user);
+    }
+
+    #[test]
+    fn test_admin_transfer_requires_acceptance() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let new_admin = Address::generate(&ctx.env);
+
+        client.transfer_admin(&new_admin);
+        assert_eq!(client.admin(), ctx.admin);
+        assert_eq!(client.get_pending_admin(), Some(new_admin.clone()));
+
+        client.accept_admin();
+        assert_eq!(
+            ctx.env.auths().first().map(|(signer, _)| signer.clone()),
+            Some(new_admin.clone())
+        );
+        assert_eq!(client.admin(), new_admin);
+        assert_eq!(client.get_pending_admin(), None);
+        assert!(client.has_role(&Role::Treasurer, &new_admin));
+        assert!(!client.has_role(&Role::Treasurer, &ctx.admin));
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #41)")] // Unauthorized
+    fn test_accept_admin_without_proposal() {
+        let ctx = TestContext::new();
+        ctx.client().accept_admin();
+    }
+
+    #[test]
+    fn test_parameter_change_waits_for_timelock() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.execute_change(&client.set_timelock_delay(&86400));
+
+        let change_id = client.set_collateral_factor(&ctx.admin, &ctx.buffer, &15000);
+        assert_eq!(client.get_pending_change(&change_id).eta, 1000 + 86400);
+        assert!(client.try_execute_change(&change_id).is_err());
+        assert_eq!(client.get_collateral_factor(&ctx.buffer), DEFAULT_COLLATERAL_FACTOR_BPS);
+
+        ctx.advance_time(86400);
+        client.execute_change(&change_id);
+        assert_eq!(client.get_collateral_factor(&ctx.buffer), 15000);
+        assert!(client.try_get_pending_change(&change_id).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #43)")] // ChangeNotFound
+    fn test_cancelled_change_cannot_execute() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let new_buffer = Address::generate(&ctx.env);
+
+        let change_id = client.update_config(&Config::new(new_buffer));
+        client.cancel_change(&ctx.admin, &change_id);
+        assert_eq!(client.get_buffer(), ctx.buffer);
+        client.execute_change(&change_id);
+    }
+
+    #[test]
+    fn test_buffer_change_applies_after_execution() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let new_buffer = Address::generate(&ctx.env);
+
+        client.execute_change(&client.update_config(&Config::new(new_buffer.clone())));
+        assert_eq!(client.get_buffer(), new_buffer);
+    }
+
+    #[test]
+    fn test_config_defaults_match_previous_limits() {
+        let ctx = TestContext::new();
+        let config = ctx.client().get_config();
+
+        assert_eq!(config.max_installments, 12);
+        assert_eq!(config.max_ltv_bps, 8000);
+        assert_eq!(config.routing, RoutingPolicy::AvailableFirst);
+        assert!(ctx.client().try_update_config(&Config { fee_bps: 100, ..config }).is_err());
+    }
+
+    #[test]
+    fn test_protected_first_routing_and_fee_split() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let treasury = Address::generate(&ctx.env);
+
+        let mut config = client.get_config();
+        config.routing = RoutingPolicy::ProtectedFirst;
+        config.fee_bps = 100;
+        config.fee_recipient = Some(treasury.clone());
+        client.execute_change(&client.update_config(&config));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1000);
+
+        let source = client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert!(source.is_protected());
+        assert_eq!(ctx.buffer_client().get_received(&ctx.merchant), 990);
+        assert_eq!(ctx.buffer_client().get_received(&treasury), 10);
+    }
+
+    #[test]
+    fn test_grace_period_delays_default() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let mut config = client.get_config();
+        config.grace_period_secs = 3600;
+        client.execute_change(&client.update_config(&config));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.buffer_client().set_shares(&ctx.user, &0, &500);
+
+        ctx.advance_time(1000);
+        assert!(client.try_declare_default(&plan_id, &1).is_err());
+
+        ctx.advance_time(3600);
+        client.declare_default(&plan_id, &1);
+        assert_eq!(client.get_plan(&plan_id).status, PlanStatus::Defaulted);
+    }
+
+    #[test]
+    fn test_frozen_plan_blocks_collection_until_unfrozen() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let arbiter = Address::generate(&ctx.env);
+        client.grant_role(&Role::Arbiter, &arbiter);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1000);
+
+        client.freeze_plan(&arbiter, &plan_id);
+        assert!(client.is_frozen(&plan_id));
+        assert!(client.try_collect_installment(&plan_id, &1, &ctx.collector).is_err());
+        assert!(client.try_collect_installment(&plan_id, &1, &ctx.user).is_err());
+
+        // Other plans keep running
+        let later = SorobanVec::from_array(&ctx.env, [3000u64]);
+        let other = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &later);
+        ctx.advance_time(1000);
+        client.collect_installment(&other, &1, &ctx.collector);
+
+        client.unfreeze_plan(&arbiter, &plan_id);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(client.get_plan(&plan_id).status, PlanStatus::Completed);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #47)")] // PlanFrozen
+    fn test_frozen_plan_cannot_be_defaulted() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.buffer_client().set_shares(&ctx.user, &0, &500);
+        ctx.advance_time(1000);
+
+        client.freeze_plan(&ctx.admin, &plan_id);
+        client.declare_default(&plan_id, &1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #48)")] // Blacklisted
+    fn test_blacklisted_user_cannot_create_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_blacklisted(&ctx.user, &true);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+    }
+
+    #[test]
+    fn test_blacklisted_merchant_blocks_settlement() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1000);
+
+        client.set_blacklisted(&ctx.merchant, &true);
+        assert!(client.is_blacklisted(&ctx.merchant));
+        assert!(client.try_collect_installment(&plan_id, &1, &ctx.collector).is_err());
+        let later = SorobanVec::from_array(&ctx.env, [5000u64]);
+        assert!(client.try_create_plan(&ctx.user, &ctx.merchant, &1000, &1, &later).is_err());
+
+        client.set_blacklisted(&ctx.merchant, &false);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+    }
+
+    #[test]
+    fn test_merchant_cap_limits_outstanding_origination() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_merchant_cap(&ctx.merchant, &Some(3000));
+        assert_eq!(client.get_merchant_headroom(&ctx.merchant), Some(3000));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        assert_eq!(client.get_merchant_exposure(&ctx.merchant), 2000);
+        assert_eq!(client.get_merchant_headroom(&ctx.merchant), Some(1000));
+        assert!(client.try_create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates).is_err());
+
+        // Collections free up headroom
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(client.get_merchant_headroom(&ctx.merchant), Some(2000));
+        let later = SorobanVec::from_array(&ctx.env, [4000u64, 5000]);
+        client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &later);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #49)")] // MerchantCapExceeded
+    fn test_merchant_cap_rejects_oversized_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_merchant_cap(&ctx.merchant, &Some(500));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+    }
+
+    #[test]
+    fn test_user_limit_counts_concurrent_plans() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_user_limit(&ctx.user, &Some(2500));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1500, &1, &due_dates);
+        assert_eq!(client.get_user_exposure(&ctx.user), 1500);
+        assert!(client.try_create_plan(&ctx.user, &ctx.merchant, &1500, &1, &due_dates).is_err());
+
+        // Repayment lowers the exposure again
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(client.get_user_exposure(&ctx.user), 0);
+        let later = SorobanVec::from_array(&ctx.env, [3000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1500, &1, &later);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #50)")] // UserLimitExceeded
+    fn test_user_limit_rejects_oversized_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_user_limit(&ctx.user, &Some(500));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+    }
+
+    #[test]
+    fn test_rate_limit_throttles_plan_creation_per_window() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_rate_limit(&Some(RateLimit { max_plans: 2, window_secs: 86400 }));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [100_000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        assert!(client.try_create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates).is_err());
+
+        // Other users have their own window
+        let other = Address::generate(&ctx.env);
+        client.create_plan(&other, &ctx.merchant, &1000, &1, &due_dates);
+
+        ctx.advance_time(86400);
+        let later = SorobanVec::from_array(&ctx.env, [200_000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &later);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #27)")] // RateLimitExceeded
+    fn test_rate_limit_rejects_excess_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_rate_limit(&Some(RateLimit { max_plans: 1, window_secs: 86400 }));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+    }
+
+    #[test]
+    fn test_global_cap_tracks_utilization() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_global_cap(&Some(4000));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        let utilization = client.get_utilization();
+        assert_eq!(utilization.outstanding, 2000);
+        assert_eq!(utilization.cap, Some(4000));
+        assert_eq!(utilization.utilization_bps, Some(5000));
+
+        // Another user is still bound by the protocol-wide cap
+        let other = Address::generate(&ctx.env);
+        let result = client.try_create_plan(&other, &ctx.merchant, &3000, &2, &due_dates);
+        assert_eq!(result.err(), Some(Ok(ContractError::GlobalCapExceeded)));
+
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(client.get_total_outstanding(), 1000);
+
+        // Raising the cap admits the plan
+        client.set_global_cap(&Some(10_000));
+        let later = SorobanVec::from_array(&ctx.env, [6000u64, 7000u64]);
+        client.create_plan(&other, &ctx.merchant, &3000, &2, &later);
+        assert_eq!(client.get_utilization().utilization_bps, Some(4000));
+    }
+
+    #[test]
+    fn test_utilization_uncapped() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1500, &1, &due_dates);
+        let utilization = client.get_utilization();
+        assert_eq!(utilization.outstanding, 1500);
+        assert_eq!(utilization.cap, None);
+        assert_eq!(utilization.utilization_bps, None);
+    }
+
+    #[test]
+    fn test_large_plan_requires_co_approval() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let officer = Address::generate(&ctx.env);
+        client.grant_role(&Role::RiskOfficer, &officer);
+        client.set_approval_threshold(&Some(3000));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let small = client.create_plan(&ctx.user, &ctx.merchant, &3000, &1, &due_dates);
+        assert_eq!(client.get_plan(&small).status, PlanStatus::Active);
+
+        let large = client.create_plan(&ctx.user, &ctx.merchant, &4000, &1, &due_dates);
+        assert_eq!(client.get_plan(&large).status, PlanStatus::PendingApproval);
+
+        ctx.advance_time(1000);
+        assert!(client.try_collect_installment(&large, &1, &ctx.collector).is_err());
+
+        // Only a risk officer can approve, and only once
+        assert!(client.try_approve_large_plan(&ctx.user, &large).is_err());
+        client.approve_large_plan(&officer, &large);
+        assert_eq!(client.get_plan(&large).status, PlanStatus::Active);
+        assert_eq!(client.try_approve_large_plan(&officer, &large), Err(Ok(ContractError::PlanNotFound)));
+
+        client.collect_installment(&large, &1, &ctx.collector);
+        assert_eq!(client.get_plan(&large).status, PlanStatus::Completed);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #19)")] // AwaitingApproval
+    fn test_pending_approval_blocks_default() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_approval_threshold(&Some(0));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+
+        ctx.advance_time(1000);
+        client.declare_default(&plan_id, &1);
+    }
+
+    #[test]
+    fn test_governance_contract_proposes_fee_change() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let governance = ctx.env.register(MockGovernance, ());
+        let governance_client = MockGovernanceClient::new(&ctx.env, &governance);
+
+        client.transfer_admin(&governance);
+        governance_client.accept(&ctx.bridge);
+        assert_eq!(client.admin(), governance);
+
+        // From here on only real authorizations count
+        ctx.env.set_auths(&[]);
+        let treasury = Address::generate(&ctx.env);
+        let change = ParamChange::Fee(100, Some(treasury.clone()));
+        let change_id = governance_client.propose(&ctx.bridge, &change);
+        assert_eq!(client.get_pending_change(&change_id).change, change);
+
+        client.execute_change(&change_id);
+        let config = client.get_config();
+        assert_eq!(config.fee_bps, 100);
+        assert_eq!(config.fee_recipient, Some(treasury));
+        assert_eq!(config.max_ltv_bps, DEFAULT_MAX_LTV_BPS);
+
+        // The old admin lost its powers
+        assert!(client.try_set_fee(&ctx.admin, &0, &None).is_err());
+    }
+
+    #[test]
+    fn test_set_risk_params_updates_only_ltv_limits() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        assert!(client.try_set_risk_params(&ctx.admin, &9000, &8500).is_err());
+        assert!(client.try_set_fee(&ctx.admin, &100, &None).is_err());
+        assert!(client
+            .try_propose_change(&ctx.user, &ParamChange::RiskParams(5000, 6000))
+            .is_err());
+
+        client.execute_change(&client.set_risk_params(&ctx.admin, &5000, &6000));
+        let config = client.get_config();
+        assert_eq!(config.max_ltv_bps, 5000);
+        assert_eq!(config.liquidation_threshold_bps, 6000);
+        assert_eq!(config.max_installments, DEFAULT_MAX_INSTALLMENTS);
+
+        // At 50% LTV the 10000-share buffer backs at most 5000
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        assert!(client.try_create_plan(&ctx.user, &ctx.merchant, &6000, &1, &due_dates).is_err());
+    }
+
+    #[test]
+    fn test_user_plans_page() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let mut ids = SorobanVec::new(&ctx.env);
+        for _ in 0..5 {
+            ids.push_back(client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates));
+        }
+        assert_eq!(client.get_user_plan_count(&ctx.user), 5);
+
+        assert_eq!(client.get_user_plans_page(&ctx.user, &0, &2), ids.slice(0..2));
+        assert_eq!(client.get_user_plans_page(&ctx.user, &2, &2), ids.slice(2..4));
+        assert_eq!(client.get_user_plans_page(&ctx.user, &4, &2), ids.slice(4..5));
+        assert_eq!(client.get_user_plans_page(&ctx.user, &9, &2).len(), 0);
+    }
+
+    #[test]
+    fn test_user_plans_page_caps_limit() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        for _ in 0..(MAX_PAGE_SIZE + 1) {
+            client.create_plan(&ctx.user, &ctx.merchant, &10, &1, &due_dates);
+        }
+
+        assert_eq!(client.get_user_plans_page(&ctx.user, &0, &u32::MAX).len(), MAX_PAGE_SIZE);
+        assert_eq!(client.get_user_plans_page(&ctx.user, &MAX_PAGE_SIZE, &u32::MAX).len(), 1);
+        assert_eq!(client.get_user_plan_count(&Address::generate(&ctx.env)), 0);
+    }
+
+    #[test]
+    fn test_merchant_plans_index() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let other_merchant = Address::generate(&ctx.env);
+        client.register_merchant(&other_merchant, &BytesN::from_array(&ctx.env, &[8u8; 32]), &other_merchant, &0);
+        client.approve_merchant(&ctx.admin, &other_merchant);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let first = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        let other = client.create_plan(&ctx.user, &other_merchant, &100, &1, &due_dates);
+        let second = client.create_plan(&Address::generate(&ctx.env), &ctx.merchant, &100, &1, &due_dates);
+
+        assert_eq!(client.get_merchant_plan_count(&ctx.merchant), 2);
+        assert_eq!(
+            client.get_merchant_plans(&ctx.merchant, &0, &10),
+            SorobanVec::from_array(&ctx.env, [first, second])
+        );
+        assert_eq!(
+            client.get_merchant_plans(&ctx.merchant, &1, &10),
+            SorobanVec::from_array(&ctx.env, [second])
+        );
+        assert_eq!(
+            client.get_merchant_plans(&other_merchant, &0, &10),
+            SorobanVec::from_array(&ctx.env, [other])
+        );
+    }
+
+    #[test]
+    fn test_status_index_follows_transitions() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let paid = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        let kept = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        assert_eq!(client.get_status_count(&PlanStatus::Active), 2);
+
+        ctx.advance_time(1000);
+        client.collect_installment(&paid, &1, &ctx.collector);
+
+        assert_eq!(
+            client.get_plans_by_status(&PlanStatus::Active, &0, &10),
+            SorobanVec::from_array(&ctx.env, [kept])
+        );
+        assert_eq!(
+            client.get_plans_by_status(&PlanStatus::Completed, &0, &10),
+            SorobanVec::from_array(&ctx.env, [paid])
+        );
+        assert_eq!(client.get_status_count(&PlanStatus::Defaulted), 0);
+    }
+
+    #[test]
+    fn test_status_index_lists_defaulted_plans() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+
+        assert_eq!(client.get_status_count(&PlanStatus::Active), 0);
+        assert_eq!(
+            client.get_plans_by_status(&PlanStatus::Defaulted, &0, &10),
+            SorobanVec::from_array(&ctx.env, [plan_id])
+        );
+    }
+
+    #[test]
+    fn test_overdue_lists_due_installments_oldest_first() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let first = client.create_plan(
+            &ctx.user, &ctx.merchant, &300, &3,
+            &SorobanVec::from_array(&ctx.env, [5000u64, 90_000, 200_000]),
+        );
+        let second = client.create_plan(
+            &ctx.user, &ctx.merchant, &100, &1,
+            &SorobanVec::from_array(&ctx.env, [3000u64]),
+        );
+        assert_eq!(client.get_overdue(&10, &0).len(), 0);
+
+        ctx.advance_time(99_000);
+        let overdue = client.get_overdue(&10, &0);
+        assert_eq!(overdue.len(), 3);
+        assert_eq!(overdue.get(0).unwrap(), DueInstallment {
+            plan_id: second,
+            installment_number: 1,
+            amount: 100,
+            due_date: 3000,
+        });
+        assert_eq!(overdue.get(1).unwrap().plan_id, first);
+        assert_eq!(overdue.get(2).unwrap().installment_number, 2);
+
+        // Paging
+        assert_eq!(client.get_overdue(&1, &1), overdue.slice(1..2));
+        assert_eq!(client.get_overdue(&10, &3).len(), 0);
+
+        // Collected installments drop out
+        client.collect_installment(&second, &1, &ctx.collector);
+        client.collect_installment(&first, &1, &ctx.collector);
+        let overdue = client.get_overdue(&10, &0);
+        assert_eq!(overdue.len(), 1);
+        assert_eq!(overdue.get(0).unwrap().due_date, 90_000);
+    }
+
+    #[test]
+    fn test_overdue_skips_failed_and_liquidated_installments() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+
+        // Installment 1 failed; 2 and 3 are still pending
+        ctx.advance_time(2500);
+        let overdue = client.get_overdue(&10, &0);
+        assert_eq!(overdue.len(), 2);
+        assert_eq!(overdue.get(0).unwrap().installment_number, 2);
+
+        let liquidator = Address::generate(&ctx.env);
+        auction_token(&ctx, &liquidator, 5000);
+        client.start_auction(&plan_id);
+        client.bid(&plan_id, &liquidator, &4500);
+        assert_eq!(client.get_overdue(&10, &0).len(), 0);
+    }
+
+    #[test]
+    fn test_plan_summary_derived_fields() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let treasury = Address::generate(&ctx.env);
+        let mut config = client.get_config();
+        config.fee_bps = 100;
+        config.fee_recipient = Some(treasury);
+        client.execute_change(&client.update_config(&config));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000, 5000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &4000, &4, &due_dates);
+
+        let summary = client.get_plan_summary(&plan_id);
+        assert_eq!(summary.outstanding, 4000);
+        assert_eq!(summary.paid_count, 0);
+        assert_eq!(summary.next_due_number, Some(1));
+        assert_eq!(summary.progress_bps, 0);
+
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        let summary = client.get_plan_summary(&plan_id);
+        assert_eq!(summary.status, PlanStatus::Active);
+        assert_eq!(summary.outstanding, 3000);
+        assert_eq!(summary.paid_count, 1);
+        assert_eq!(summary.installments_count, 4);
+        assert_eq!(summary.next_due_number, Some(2));
+        assert_eq!(summary.next_due_date, Some(3000));
+        assert_eq!(summary.next_due_amount, Some(1000));
+        assert_eq!(summary.fees_paid, 10);
+        assert_eq!(summary.progress_bps, 2500);
+    }
+
+    #[test]
+    fn test_plan_summary_of_completed_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        let summary = client.get_plan_summary(&plan_id);
+        assert_eq!(summary.status, PlanStatus::Completed);
+        assert_eq!(summary.outstanding, 0);
+        assert_eq!(summary.next_due_number, None);
+        assert_eq!(summary.fees_paid, 0);
+        assert_eq!(summary.progress_bps, BPS_DENOMINATOR);
+    }
+
+    #[test]
+    fn test_stats_follow_plan_lifecycle() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        assert_eq!(client.get_stats(), ProtocolStats::default());
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let paid = client.create_plan(&ctx.user, &ctx.merchant, &1000, &2, &due_dates);
+        client.create_plan(&ctx.user, &ctx.merchant, &600, &2, &due_dates);
+
+        ctx.advance_time(2000);
+        client.collect_installment(&paid, &1, &ctx.collector);
+        client.collect_installment(&paid, &2, &ctx.collector);
+
+        assert_eq!(client.get_stats(), ProtocolStats {
+            volume_originated: 1600,
+            volume_collected: 1000,
+            active_plans: 1,
+            completed_plans: 1,
+            defaulted_plans: 0,
+        });
+    }
+
+    #[test]
+    fn test_stats_count_defaults_and_approvals() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        defaulted_plan(&ctx);
+
+        let stats = client.get_stats();
+        assert_eq!(stats.active_plans, 0);
+        assert_eq!(stats.defaulted_plans, 1);
+
+        // Plans awaiting co-approval aren't active yet
+        client.set_approval_threshold(&Some(0));
+        let due_dates = SorobanVec::from_array(&ctx.env, [9000u64]);
+        let user = Address::generate(&ctx.env);
+        let plan_id = client.create_plan(&user, &ctx.merchant, &100, &1, &due_dates);
+        assert_eq!(client.get_stats().active_plans, 0);
+        client.approve_large_plan(&ctx.admin, &plan_id);
+        assert_eq!(client.get_stats().active_plans, 1);
+    }
+
+    #[test]
+    fn test_uncounted_plan_still_completes() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+
+        // Plans migrated from layout 0 were never counted as active
+        ctx.env.as_contract(&ctx.bridge, || {
+            ctx.env.storage().persistent().set(&DataKey::Stats, &ProtocolStats::default());
+        });
+        client.collect_installment(&plan_id, &1, &ctx.user);
+        assert_eq!(client.get_stats().active_plans, 0);
+        assert_eq!(client.get_stats().completed_plans, 1);
+    }
+
+    #[test]
+    fn test_merchant_receivables() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        defaulted_plan(&ctx);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [3000u64, 4000]);
+        let user = Address::generate(&ctx.env);
+        let plan_id = client.create_plan(&user, &ctx.merchant, &1000, &2, &due_dates);
+        ctx.advance_time(500);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        // 3000 unpaid on the defaulted plan, 500 left on the active one
+        assert_eq!(client.get_merchant_receivables(&ctx.merchant), MerchantReceivables {
+            expected: 500,
+            in_default: 3000,
+            outstanding: 3500,
+            disputed: 0,
+        });
+    }
+
+    #[test]
+    fn test_merchant_receivables_report_frozen_plans_as_disputed() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let arbiter = Address::generate(&ctx.env);
+        client.grant_role(&Role::Arbiter, &arbiter);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &2, &due_dates);
+        client.create_plan(&ctx.user, &ctx.merchant, &600, &2, &due_dates);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        client.freeze_plan(&arbiter, &plan_id);
+        client.freeze_plan(&arbiter, &plan_id);
+        let receivables = client.get_merchant_receivables(&ctx.merchant);
+        assert_eq!(receivables.disputed, 500);
+        assert_eq!(receivables.outstanding, 1100);
+
+        client.unfreeze_plan(&arbiter, &plan_id);
+        assert_eq!(client.get_merchant_receivables(&ctx.merchant).disputed, 0);
+    }
+
+    #[test]
+    fn test_merchant_receivables_after_auction() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+        let liquidator = Address::generate(&ctx.env);
+        auction_token(&ctx, &liquidator, 5000);
+
+        client.start_auction(&plan_id);
+        client.bid(&plan_id, &liquidator, &4500);
+
+        let receivables = client.get_merchant_receivables(&ctx.merchant);
+        assert_eq!(receivables.in_default, 0);
+        assert_eq!(receivables.outstanding, 0);
+    }
+
+    #[test]
+    fn test_due_between_window() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        // Installments due on days 1, 8, 15 and 30
+        let day = DUE_BUCKET_SECS;
+        let due_dates = SorobanVec::from_array(&ctx.env, [day, 8 * day, 15 * day, 30 * day]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &400, &4, &due_dates);
+
+        // Next week's workload, seen from day 7
+        let due = client.get_due_between(&(7 * day), &(14 * day), &0, &10);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due.get(0).unwrap().installment_number, 2);
+
+        let due = client.get_due_between(&day, &(15 * day), &0, &10);
+        assert_eq!(due.len(), 3);
+        assert_eq!(client.get_due_between(&day, &(15 * day), &1, &1), due.slice(1..2));
+
+        // Boundaries within a bucket are exact
+        assert_eq!(client.get_due_between(&(day + 1), &(8 * day - 1), &0, &10).len(), 0);
+        assert_eq!(client.get_due_between(&(30 * day), &(30 * day), &0, &10).get(0).unwrap().plan_id, plan_id);
+    }
+
+    #[test]
+    fn test_plan_history_records_transitions() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &2, &due_dates);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        client.collect_installment(&plan_id, &2, &ctx.user);
+
+        let history = client.get_plan_history(&plan_id, &0);
+        assert_eq!(client.get_plan_history_len(&plan_id), 4);
+        assert_eq!(history.get(0).unwrap(), HistoryEntry {
+            action: PlanAction::Created,
+            installment_number: 0,
+            actor: Some(ctx.user.clone()),
+            timestamp: 1000,
+        });
+        assert_eq!(history.get(1).unwrap(), HistoryEntry {
+            action: PlanAction::Collected,
+            installment_number: 1,
+            actor: Some(ctx.collector.clone()),
+            timestamp: 2000,
+        });
+        assert_eq!(history.get(2).unwrap().installment_number, 2);
+        assert_eq!(history.get(3).unwrap().action, PlanAction::Completed);
+        assert_eq!(client.get_plan_history(&plan_id, &1).len(), 0);
+    }
+
+    #[test]
+    fn test_plan_history_records_default() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+
+        // A second failure doesn't default the plan twice
+        ctx.advance_time(1000);
+        client.declare_default(&plan_id, &2);
+
+        let mut actions = SorobanVec::new(&ctx.env);
+        for entry in client.get_plan_history(&plan_id, &0).iter() {
+            actions.push_back(entry.action);
+        }
+        assert_eq!(actions, SorobanVec::from_array(&ctx.env, [
+            PlanAction::Created,
+            PlanAction::Failed,
+            PlanAction::Defaulted,
+            PlanAction::Failed,
+        ]));
+        assert_eq!(client.get_stats().defaulted_plans, 1);
+    }
+
+    #[test]
+    fn test_user_credit_tracks_repayments() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &900, &3, &due_dates);
+        client.create_plan(&ctx.user, &ctx.merchant, &500, &1, &due_dates.slice(0..1));
+
+        // Installment 1 early, 2 on time, 3 late
+        client.collect_installment(&plan_id, &1, &ctx.user);
+        ctx.advance_time(2000);
+        client.collect_installment(&plan_id, &2, &ctx.collector);
+        ctx.advance_time(5000);
+        client.collect_installment(&plan_id, &3, &ctx.collector);
+
+        let credit = client.get_user_credit(&ctx.user);
+        assert_eq!(credit.history, CreditHistory {
+            plans_completed: 1,
+            installments_paid: 3,
+            on_time_payments: 2,
+            total_repaid: 900,
+            defaults: 0,
+        });
+        assert_eq!(credit.on_time_bps, 6666);
+        assert_eq!(credit.outstanding, 500);
+    }
+
+    #[test]
+    fn test_user_credit_counts_defaults() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        defaulted_plan(&ctx);
+
+        let credit = client.get_user_credit(&ctx.user);
+        assert_eq!(credit.history.defaults, 1);
+        assert_eq!(credit.history.installments_paid, 0);
+        assert_eq!(credit.on_time_bps, 0);
+        assert_eq!(credit.outstanding, 3000);
+    }
+
+    #[test]
+    fn test_plan_header_matches_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &2, &due_dates);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        let plan = client.get_plan(&plan_id);
+        let header = client.get_plan_header(&plan_id);
+        assert_eq!(header, PlanHeader::from(plan.clone()));
+        assert_eq!(header.user, ctx.user);
+        assert_eq!(header.merchant, ctx.merchant);
+        assert_eq!(header.protected_shares, plan.protected_shares);
+        assert_eq!(header.status, PlanStatus::Active);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #7)")] // PlanNotFound
+    fn test_plan_header_unknown_plan() {
+        let ctx = TestContext::new();
+        ctx.client().get_plan_header(&999u64);
+    }
+
+    #[test]
+    fn test_installment_listing() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000, 5000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1002, &4, &due_dates);
+
+        let page = client.get_installments(&plan_id, &1, &2);
+        assert_eq!(page.len(), 2);
+        assert_eq!(page.get(0).unwrap().number, 2);
+        assert_eq!(page.get(1).unwrap().due_date, 4000);
+        assert_eq!(client.get_installments(&plan_id, &3, &10).len(), 1);
+        assert_eq!(client.get_installments(&plan_id, &4, &10).len(), 0);
+
+        // The last installment carries the remainder
+        let last = client.get_installment(&plan_id, &4);
+        assert_eq!(last.amount, 252);
+        assert_eq!(last.status, InstallmentStatus::Pending);
+    }
+
+    #[test]
+    fn test_get_installment_out_of_range() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+
+        assert!(client.try_get_installment(&plan_id, &0).is_err());
+        assert!(client.try_get_installment(&plan_id, &2).is_err());
+        assert_eq!(client.get_installment(&plan_id, &1).amount, 1000);
+    }
+
+    #[test]
+    fn test_plan_count() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        assert_eq!(client.get_plan_count(), 0);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        client.create_plan(&Address::generate(&ctx.env), &ctx.merchant, &100, &1, &due_dates);
+        assert_eq!(client.get_plan_count(), 2);
+
+        // Failed creations don't consume an ID
+        assert!(client.try_create_plan(&ctx.user, &ctx.merchant, &0, &1, &due_dates).is_err());
+        assert_eq!(client.get_plan_count(), 2);
+    }
+
+    #[test]
+    fn test_installments_by_status() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+
+        let failed = client.get_installments_by_status(&plan_id, &InstallmentStatus::Failed);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed.get(0).unwrap().number, 1);
+
+        let pending = client.get_installments_by_status(&plan_id, &InstallmentStatus::Pending);
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending.get(0).unwrap().number, 2);
+        assert_eq!(pending.get(1).unwrap().number, 3);
+
+        assert_eq!(client.get_installments_by_status(&plan_id, &InstallmentStatus::Paid).len(), 0);
+    }
+
+    #[test]
+    fn test_simulate_collect_predicts_source() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        assert!(client.try_simulate_collect(&plan_id, &1).is_err()); // NotDueYet
+
+        ctx.advance_time(1000);
+        assert_eq!(client.simulate_collect(&plan_id, &1), PaymentSource::available());
+
+        // Nothing was debited or written
+        assert_eq!(client.get_installment(&plan_id, &1).status, InstallmentStatus::Pending);
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).available_shares, 8000);
+
+        ctx.buffer_client().set_shares(&ctx.user, &0, &2000);
+        assert_eq!(client.simulate_collect(&plan_id, &1), PaymentSource::protected());
+        assert_eq!(client.collect_installment(&plan_id, &1, &ctx.collector), PaymentSource::protected());
+        assert!(client.try_simulate_collect(&plan_id, &1).is_err()); // AlreadyPaid
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #11)")] // InsufficientFunds
+    fn test_simulate_collect_reports_insufficient_funds() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.buffer_client().set_shares(&ctx.user, &0, &500);
+
+        ctx.advance_time(1000);
+        client.simulate_collect(&plan_id, &1);
+    }
+
+    #[test]
+    fn test_plan_by_index() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let first = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        let second = client.create_plan(&ctx.user, &ctx.merchant, &200, &1, &due_dates);
+
+        assert_eq!(client.get_plan_by_index(&0).plan_id, first);
+        assert_eq!(client.get_plan_by_index(&1).plan_id, second);
+        assert_eq!(client.get_plan_by_index(&1).total_amount, 200);
+        assert!(client.try_get_plan_by_index(&client.get_plan_count()).is_err());
+    }
+
+    #[test]
+    fn test_has_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+
+        assert!(client.has_plan(&plan_id));
+        assert!(!client.has_plan(&999u64));
+    }
+
+    #[test]
+    fn test_get_plans_batch() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let first = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        let second = client.create_plan(&ctx.user, &ctx.merchant, &200, &1, &due_dates);
+        let missing = 999u64;
+
+        let plans = client.get_plans(&SorobanVec::from_array(&ctx.env, [second, missing, first]));
+        assert_eq!(plans.len(), 3);
+        assert_eq!(plans.get(0).unwrap().unwrap().total_amount, 200);
+        assert_eq!(plans.get(1).unwrap(), None);
+        assert_eq!(plans.get(2).unwrap().unwrap().plan_id, first);
+    }
+
+    #[test]
+    fn test_next_obligation_across_plans() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        assert_eq!(client.get_next_obligation(&ctx.user), None);
+
+        let later = client.create_plan(
+            &ctx.user, &ctx.merchant, &1000, &2,
+            &SorobanVec::from_array(&ctx.env, [5000u64, 9000]),
+        );
+        let sooner = client.create_plan(
+            &ctx.user, &ctx.merchant, &300, &1,
+            &SorobanVec::from_array(&ctx.env, [3000u64]),
+        );
+
+        assert_eq!(client.get_next_obligation(&ctx.user), Some(DueInstallment {
+            plan_id: sooner,
+            installment_number: 1,
+            amount: 300,
+            due_date: 3000,
+        }));
+
+        // Paying it (completing the plan) moves on to the other plan
+        client.collect_installment(&sooner, &1, &ctx.user);
+        let next = client.get_next_obligation(&ctx.user).unwrap();
+        assert_eq!(next.plan_id, later.clone());
+        assert_eq!(next.due_date, 5000);
+
+        client.collect_installment(&later, &1, &ctx.user);
+        assert_eq!(client.get_next_obligation(&ctx.user).unwrap().installment_number, 2);
+    }
+
+    #[test]
+    fn test_next_obligation_includes_defaulted_plans() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+
+        // Installment 1 failed; 2 is still owed
+        let next = client.get_next_obligation(&ctx.user).unwrap();
+        assert_eq!((next.plan_id, next.installment_number, next.due_date), (plan_id, 2, 3000));
+    }
+
+    #[test]
+    fn test_settlement_history() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let treasury = Address::generate(&ctx.env);
+        client.execute_change(&client.set_fee(&ctx.admin, &100, &Some(treasury)));
+        // Collects out of order
+        let mut config = client.get_config();
+        config.sequential_collection = false;
+        client.execute_change(&client.update_config(&config));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        ctx.advance_time(2000);
+        client.collect_installment(&plan_id, &2, &ctx.collector);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        assert_eq!(client.get_settlement_count(&ctx.merchant), 2);
+        let history = client.get_settlement_history(&ctx.merchant, &0);
+        assert_eq!(history.get(0).unwrap(), Settlement {
+            plan_id,
+            installment_number: 2,
+            amount: 990,
+            fee: 10,
+            timestamp: 3000,
+        });
+        assert_eq!(history.get(1).unwrap().installment_number, 1);
+        assert_eq!(client.get_settlement_history(&ctx.merchant, &1).len(), 0);
+    }
+
+    #[test]
+    fn test_settlement_history_pages_and_auction_payouts() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+        let liquidator = Address::generate(&ctx.env);
+        auction_token(&ctx, &liquidator, 5000);
+
+        client.start_auction(&plan_id);
+        client.bid(&plan_id, &liquidator, &4500);
+        let payout = client.get_settlement_history(&ctx.merchant, &0).get(0).unwrap();
+        assert_eq!(payout.installment_number, 0);
+        assert_eq!(payout.amount, 3000);
+
+        // Entries roll over to a new page every 20 settlements
+        let user = Address::generate(&ctx.env);
+        let due_dates = SorobanVec::from_array(&ctx.env, [9000u64]);
+        for _ in 0..HISTORY_PAGE_SIZE {
+            let plan_id = client.create_plan(&user, &ctx.merchant, &10, &1, &due_dates);
+            client.collect_installment(&plan_id, &1, &user);
+        }
+        assert_eq!(client.get_settlement_count(&ctx.merchant), HISTORY_PAGE_SIZE + 1);
+        assert_eq!(client.get_settlement_history(&ctx.merchant, &0).len(), HISTORY_PAGE_SIZE);
+        assert_eq!(client.get_settlement_history(&ctx.merchant, &1).len(), 1);
+    }
+
+    #[test]
+    fn test_plan_by_legacy_id() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &200, &1, &due_dates);
+        assert_eq!(plan_id, 1);
+
+        let mut legacy = [0u8; LEGACY_ID_LEN];
+        legacy[0..8].copy_from_slice(&plan_id.to_be_bytes());
+        let plan = client.get_plan_by_legacy_id(&String::from_bytes(&ctx.env, &legacy));
+        assert_eq!(plan.total_amount, 200);
+
+        let malformed = String::from_str(&ctx.env, "plan_1");
+        assert!(client.try_get_plan_by_legacy_id(&malformed).is_err());
+    }
+
+    #[test]
+    fn test_plan_access_extends_ttl() {
+        use soroban_sdk::testutils::storage::{Instance as _, Persistent as _};
+
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+
+        let plan_ttl = || ctx.env.as_contract(&ctx.bridge, || {
+            ctx.env.storage().persistent().get_ttl(&PlanKey::Plan(plan_id))
+        });
+        assert_eq!(plan_ttl(), TTL_EXTEND_TO);
+
+        // Let the entries age below the threshold, then keep them alive
+        ctx.env.as_contract(&ctx.bridge, || {
+            ctx.env.storage().instance().extend_ttl(TTL_EXTEND_TO, TTL_EXTEND_TO);
+        });
+        ctx.env.ledger().with_mut(|l| l.sequence_number += TTL_EXTEND_TO - TTL_THRESHOLD + 1);
+        assert_eq!(plan_ttl(), TTL_THRESHOLD - 1);
+
+        client.bump_plan(&plan_id);
+        assert_eq!(plan_ttl(), TTL_EXTEND_TO);
+        ctx.env.as_contract(&ctx.bridge, || {
+            let store = ctx.env.storage();
+            assert_eq!(store.persistent().get_ttl(&DataKey::UserPlans(ctx.user.clone(), 0)), TTL_EXTEND_TO);
+            assert_eq!(store.persistent().get_ttl(&IndexKey::MerchantPlans(ctx.merchant.clone(), 0)), TTL_EXTEND_TO);
+            assert_eq!(store.persistent().get_ttl(&IndexKey::MerchantPlanCount(ctx.merchant.clone())), TTL_EXTEND_TO);
+            assert_eq!(store.persistent().get_ttl(&IndexKey::StatusPlans(PlanStatus::Active, 0)), TTL_EXTEND_TO);
+            assert_eq!(store.persistent().get_ttl(&DataKey::DueBucket(2000 / DUE_BUCKET_SECS)), TTL_EXTEND_TO);
+            assert_eq!(store.persistent().get_ttl(&DataKey::DueDays), TTL_EXTEND_TO);
+            assert_eq!(store.persistent().get_ttl(&PlanKey::PlanHistory(plan_id, 0)), TTL_EXTEND_TO);
+            assert_eq!(store.instance().get_ttl(), TTL_EXTEND_TO);
+        });
+    }
+
+    #[test]
+    fn test_bump_unknown_plan() {
+        let ctx = TestContext::new();
+        assert!(ctx.client().try_bump_plan(&999).is_err());
+    }
+
+    #[test]
+    fn test_archive_completed_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        client.collect_installment(&plan_id, &1, &ctx.user);
+
+        // Held for the retention period after completion
+        ctx.advance_time(ARCHIVE_RETENTION_SECS - 1);
+        assert!(client.try_archive_plan(&plan_id).is_err());
+        ctx.advance_time(1);
+
+        let header = client.archive_plan(&plan_id);
+        assert_eq!(header.status, PlanStatus::Completed);
+        assert!(client.try_get_plan(&plan_id).is_err());
+        assert!(client.has_plan(&plan_id));
+        assert_eq!(client.get_plan_header(&plan_id), header);
+        assert_eq!(client.get_plans(&SorobanVec::from_array(&ctx.env, [plan_id])).get(0).unwrap(), Some(header));
+        assert_eq!(client.get_plan_history(&plan_id, &0).last().unwrap().action, PlanAction::Archived);
+        // Per-user scans skip the archived plan
+        client.reconcile_locks(&ctx.user);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #25)")] // PlanNotClosed
+    fn test_archive_rejects_open_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+        ctx.advance_time(ARCHIVE_RETENTION_SECS);
+        client.archive_plan(&plan_id);
+    }
+
+    #[test]
+    fn test_installments_stored_as_separate_entries() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        ctx.env.as_contract(&ctx.bridge, || {
+            let storage = ctx.env.storage().persistent();
+            let header: PlanHeader = storage.get(&PlanKey::Plan(plan_id)).unwrap();
+            assert_eq!(header.installments_count, 3);
+
+            let first: InstallmentRecord = storage.get(&PlanKey::Installment(plan_id, 1)).unwrap();
+            assert_eq!(first.status, InstallmentStatus::Paid);
+            assert!(!storage.has(&PlanKey::Installment(plan_id, 3)));
+        });
+
+        // Reads reassemble the full plan
+        let plan = client.get_plan(&plan_id);
+        assert_eq!(plan.installments.len(), 3);
+        assert_eq!(plan.installments.get(0).unwrap().status, InstallmentStatus::Paid);
+        assert_eq!(client.get_installment(&plan_id, &2).status, InstallmentStatus::Pending);
+    }
+
+    #[test]
+    fn test_installment_bitmaps_track_status() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+
+        ctx.buffer_client().set_shares(&ctx.user, &5000, &0);
+        ctx.advance_time(2000);
+        client.collect_installment(&plan_id, &2, &ctx.collector);
+
+        let header = client.get_plan_header(&plan_id);
+        assert_eq!(header.failed_bitmap, 0b001);
+        assert_eq!(header.paid_bitmap, 0b010);
+        assert!(header.is_pending(3));
+        assert!(!header.all_paid());
+
+        // Installment 1 failed: `all_paid` never holds for this plan
+        client.collect_installment(&plan_id, &3, &ctx.collector);
+        assert_eq!(client.get_plan(&plan_id).status, PlanStatus::Defaulted);
+    }
+
+    #[test]
+    fn test_max_installments_limited_by_bitmap() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let mut config = client.get_config();
+        config.max_installments = MAX_INSTALLMENTS_LIMIT + 1;
+        assert!(client.try_update_config(&config).is_err());
+
+        config.max_installments = MAX_INSTALLMENTS_LIMIT;
+        client.execute_change(&client.update_config(&config));
+        assert_eq!(client.get_config().max_installments, MAX_INSTALLMENTS_LIMIT);
+    }
+
+    #[test]
+    fn test_user_plans_split_into_buckets() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let mut ids = SorobanVec::new(&ctx.env);
+        for _ in 0..INDEX_BUCKET_SIZE + 10 {
+            ids.push_back(client.create_plan(&ctx.user, &ctx.merchant, &10, &1, &due_dates));
+        }
+
+        ctx.env.as_contract(&ctx.bridge, || {
+            let storage = ctx.env.storage().persistent();
+            let head: SorobanVec<u64> = storage.get(&DataKey::UserPlans(ctx.user.clone(), 1)).unwrap();
+            assert_eq!(head.len(), 10);
+            assert!(!storage.has(&DataKey::UserPlans(ctx.user.clone(), 2)));
+        });
+
+        // Pages straddling two buckets come back in creation order
+        let page = client.get_user_plans_page(&ctx.user, &45, &10);
+        assert_eq!(page, ids.slice(45..55));
+        assert_eq!(client.get_user_plans(&ctx.user), ids);
+        assert_eq!(client.get_user_plan_count(&ctx.user), INDEX_BUCKET_SIZE + 10);
+
+        // The merchant index is bucketed the same way
+        ctx.env.as_contract(&ctx.bridge, || {
+            let storage = ctx.env.storage().persistent();
+            let head: SorobanVec<u64> = storage.get(&IndexKey::MerchantPlans(ctx.merchant.clone(), 1)).unwrap();
+            assert_eq!(head.len(), 10);
+            let slot: u32 = storage.get(&PlanKey::MerchantSlot(ids.get(55).unwrap())).unwrap();
+            assert_eq!(slot, 55);
+        });
+        assert_eq!(client.get_merchant_plans(&ctx.merchant, &45, &10), ids.slice(45..55));
+        assert_eq!(client.get_merchant_plan_count(&ctx.merchant), INDEX_BUCKET_SIZE + 10);
+    }
+
+    #[test]
+    fn test_status_lists_fill_freed_positions() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let mut ids = SorobanVec::new(&ctx.env);
+        for _ in 0..INDEX_BUCKET_SIZE + 5 {
+            ids.push_back(client.create_plan(&ctx.user, &ctx.merchant, &10, &1, &due_dates));
+        }
+        let first = ids.get(0).unwrap();
+        let last = ids.get(INDEX_BUCKET_SIZE + 4).unwrap();
+
+        // Completing the first plan moves the last one into its position
+        client.collect_installment(&first, &1, &ctx.user);
+        assert_eq!(client.get_status_count(&PlanStatus::Active), INDEX_BUCKET_SIZE + 4);
+        assert_eq!(client.get_plans_by_status(&PlanStatus::Active, &0, &1), SorobanVec::from_array(&ctx.env, [last]));
+        assert_eq!(client.get_plans_by_status(&PlanStatus::Active, &INDEX_BUCKET_SIZE, &10).len(), 4);
+        assert_eq!(client.get_plans_by_status(&PlanStatus::Completed, &0, &10), SorobanVec::from_array(&ctx.env, [first]));
+
+        // The moved plan can still leave the list
+        client.collect_installment(&last, &1, &ctx.user);
+        assert_eq!(client.get_status_count(&PlanStatus::Active), INDEX_BUCKET_SIZE + 3);
+        assert_eq!(client.get_plans_by_status(&PlanStatus::Active, &0, &1), SorobanVec::from_array(&ctx.env, [ids.get(INDEX_BUCKET_SIZE + 3).unwrap()]));
+        assert_eq!(client.get_status_count(&PlanStatus::Completed), 2);
+
+        // Archiving empties the closed list
+        ctx.advance_time(ARCHIVE_RETENTION_SECS);
+        client.archive_plan(&first);
+        client.archive_plan(&last);
+        assert_eq!(client.get_status_count(&PlanStatus::Completed), 0);
+        ctx.env.as_contract(&ctx.bridge, || {
+            assert!(!ctx.env.storage().persistent().has(&IndexKey::StatusPlans(PlanStatus::Completed, 0)));
+        });
+    }
+
+    #[test]
+    fn test_storage_key_placement() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        ctx.env.as_contract(&ctx.bridge, || {
+            let instance = ctx.env.storage().instance();
+            let persistent = ctx.env.storage().persistent();
+
+            // Global configuration stays in instance storage
+            assert!(instance.has(&DataKey::Admin));
+            assert!(instance.has(&DataKey::Config));
+            assert!(instance.has(&DataKey::Version));
+
+            // Counters touched by plan traffic each get their own entry
+            for key in BridgeContract::counter_keys(&ctx.env).iter() {
+                assert!(!instance.has(&key));
+            }
+            assert!(persistent.has(&DataKey::PlanCounter));
+            assert!(persistent.has(&DataKey::TotalOutstanding));
+            assert!(persistent.has(&DataKey::Stats));
+
+            // Per-plan and per-account data is persistent
+            assert!(persistent.has(&PlanKey::Plan(plan_id)));
+            assert!(persistent.has(&DataKey::UserPlanCount(ctx.user.clone())));
+            assert!(!instance.has(&PlanKey::Plan(plan_id)));
+        });
+    }
+
+    #[test]
+    fn test_create_plan_with_nonce_is_idempotent() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+
+        let plan_id = client.create_plan_with_nonce(&ctx.user, &ctx.merchant, &1000, &2, &due_dates, &42);
+        assert_eq!(plan_id, client.get_keyed_plan_id(&ctx.user, &ctx.merchant, &42));
+        assert!(plan_id & KEYED_ID_FLAG != 0);
+        let count = client.get_plan_count();
+
+        // A retry after a lost acknowledgement returns the same plan
+        let retry = client.create_plan_with_nonce(&ctx.user, &ctx.merchant, &1000, &2, &due_dates, &42);
+        assert_eq!(retry, plan_id);
+        assert_eq!(client.get_plan_count(), count);
+        assert_eq!(client.get_user_plan_count(&ctx.user), 1);
+
+        let plan = client.get_plan_by_index(&(count - 1));
+        assert_eq!(plan.plan_id, plan_id);
+
+        // A new nonce creates a new plan
+        let other = client.create_plan_with_nonce(&ctx.user, &ctx.merchant, &1000, &2, &due_dates, &43);
+        assert_ne!(other, plan_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #14)")] // NonceReused
+    fn test_create_plan_with_nonce_rejects_different_terms() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+
+        client.create_plan_with_nonce(&ctx.user, &ctx.merchant, &1000, &2, &due_dates, &7);
+        client.create_plan_with_nonce(&ctx.user, &ctx.merchant, &1500, &2, &due_dates, &7);
+    }
+
+    fn seed_v0_plan(ctx: &TestContext) -> String {
+        let env = &ctx.env;
+        let legacy_id = BridgeContract::legacy_id_for(env, 0);
+        let installments = SorobanVec::from_array(env, [
+            Installment {
+                number: 1,
+                amount: 500,
+                due_date: 2000,
+                paid_at: Some(1500),
+                payment_source: Some(0),
+                status: InstallmentStatus::Paid,
+            },
+            Installment {
+                number: 2,
+                amount: 500,
+                due_date: 3000,
+                paid_at: None,
+                payment_source: None,
+                status: InstallmentStatus::Pending,
+            },
+        ]);
+        let plan = BridgePlanV0 {
+            plan_id: legacy_id.clone(),
+            user: ctx.user.clone(),
+            merchant: ctx.merchant.clone(),
+            total_amount: 1000,
+            total_shares: 1000,
+            installments_count: 2,
+            installments,
+            protected_shares: 500,
+            status: PlanStatus::Active,
+            created_at: 1000,
+            insured_amount: 0,
+            release_at: 0,
+            cross_margin: false,
+            rebate_accrued: 0,
+            rebate_checkpoint: 1000,
+        };
+        let ids = SorobanVec::from_array(env, [legacy_id.clone()]);
+
+        env.as_contract(&ctx.bridge, || {
+            let storage = env.storage().persistent();
+            storage.set(&PlanKeyV0::Plan(legacy_id.clone()), &plan);
+            storage.set(&PlanKeyV0::UserPlans(ctx.user.clone()), &ids);
+            storage.set(&PlanKeyV0::Frozen(legacy_id.clone()), &true);
+            storage.set(&PlanKeyV0::MerchantPlans(ctx.merchant.clone()), &ids);
+            storage.set(&PlanKeyV0::StatusPlans(PlanStatus::Active), &ids);
+            storage.set(&DataKey::PlanCounter, &1u64);
+            env.storage().instance().remove(&DataKey::PlanVersion);
+        });
+        legacy_id
+    }
+
+    #[test]
+    fn test_migrate_plans_upgrades_v0_records() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let legacy_id = seed_v0_plan(&ctx);
+        assert_eq!(client.get_plan_version(), 0);
+
+        assert_eq!(client.migrate_plans(&0, &10), 0);
+        assert_eq!(client.get_plan_version(), PLAN_VERSION);
+
+        let plan = client.get_plan(&0);
+        assert_eq!(plan.paid_bitmap, 0b01);
+        assert_eq!(plan.installments.get(1).unwrap().status, InstallmentStatus::Pending);
+        assert!(client.is_frozen(&0));
+        assert_eq!(client.get_user_plans(&ctx.user), SorobanVec::from_array(&ctx.env, [0u64]));
+        assert_eq!(client.get_merchant_plans(&ctx.merchant, &0, &10).len(), 1);
+        assert_eq!(client.get_plans_by_status(&PlanStatus::Active, &0, &10).len(), 1);
+
+        // The due-date index lists the pending installment under the new ID
+        ctx.advance_time(2500);
+        let overdue = client.get_overdue(&10, &0);
+        assert_eq!(overdue.len(), 1);
+        assert_eq!(overdue.get(0).unwrap().plan_id, 0);
+
+        ctx.env.as_contract(&ctx.bridge, || {
+            let storage = ctx.env.storage().persistent();
+            assert!(!storage.has(&PlanKeyV0::Plan(legacy_id.clone())));
+            assert!(!storage.has(&PlanKeyV0::UserPlans(ctx.user.clone())));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #33)")] // Paused
+    fn test_plan_operations_wait_for_migrate_plans() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        seed_v0_plan(&ctx);
+
+        // A batch of zero leaves the migration pending
+        assert_eq!(client.migrate_plans(&0, &0), 1);
+        assert!(client.try_migrate_plans(&1, &10).is_err());
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+    }
+
+    #[test]
+    fn test_regular_schedule_stored_as_start_and_interval() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+
+        let header = client.get_plan_header(&plan_id);
+        assert_eq!((header.first_due, header.interval), (2000, 1000));
+        ctx.env.as_contract(&ctx.bridge, || {
+            assert!(!ctx.env.storage().persistent().has(&PlanKey::DueDates(plan_id)));
+        });
+
+        let plan = client.get_plan(&plan_id);
+        for (i, due_date) in due_dates.iter().enumerate() {
+            assert_eq!(plan.installments.get(i as u32).unwrap().due_date, due_date);
+        }
+    }
+
+    #[test]
+    fn test_irregular_schedule_keeps_explicit_dates() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 5500]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+
+        assert_eq!(client.get_plan_header(&plan_id).interval, 0);
+        ctx.env.as_contract(&ctx.bridge, || {
+            assert!(ctx.env.storage().persistent().has(&PlanKey::DueDates(plan_id)));
+        });
+        assert_eq!(client.get_installment(&plan_id, &3).due_date, 5500);
+    }
+
+    #[test]
+    fn test_pending_installments_are_not_stored() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &3, &due_dates);
+
+        ctx.env.as_contract(&ctx.bridge, || {
+            for number in 1..=3 {
+                assert!(!ctx.env.storage().persistent().has(&PlanKey::Installment(plan_id, number)));
+            }
+        });
+
+        // Amounts and dates are computed on read, remainder on the last one
+        let plan = client.get_plan(&plan_id);
+        assert_eq!(plan.installments.get(0).unwrap().amount, 333);
+        assert_eq!(plan.installments.get(2).unwrap().amount, 334);
+        assert_eq!(plan.installments.get(2).unwrap().due_date, 4000);
+
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        ctx.env.as_contract(&ctx.bridge, || {
+            let storage = ctx.env.storage().persistent();
+            assert!(storage.has(&PlanKey::Installment(plan_id, 1)));
+            assert!(!storage.has(&PlanKey::Installment(plan_id, 2)));
+        });
+        assert_eq!(client.get_installment(&plan_id, &1).status, InstallmentStatus::Paid);
+        assert_eq!(client.get_installment(&plan_id, &2).status, InstallmentStatus::Pending);
+    }
+
+    /// Every plan is listed exactly once in its user, merchant and status
+    /// indexes, and the due-date index holds exactly its pending installments
+    fn assert_indexes_consistent(ctx: &TestContext) {
+        let client = ctx.client();
+        let statuses = [
+            PlanStatus::Active,
+            PlanStatus::Completed,
+            PlanStatus::Defaulted,
+            PlanStatus::Liquidated,
+            PlanStatus::PendingApproval,
+        ];
+        let due = client.get_due_between(&0, &u64::MAX, &0, &MAX_PAGE_SIZE);
+
+        for plan_id in 0..client.get_plan_count() {
+            let plan = client.get_plan(&plan_id);
+            let user_plans = client.get_user_plans(&plan.user);
+            assert_eq!(user_plans.iter().filter(|id| *id == plan_id).count(), 1);
+            let merchant_plans = client.get_merchant_plans(&plan.merchant, &0, &MAX_PAGE_SIZE);
+            assert_eq!(merchant_plans.iter().filter(|id| *id == plan_id).count(), 1);
+
+            for status in statuses.iter() {
+                let listed = client.get_plans_by_status(status, &0, &MAX_PAGE_SIZE)
+                    .iter()
+                    .filter(|id| *id == plan_id)
+                    .count();
+                assert_eq!(listed, (*status == plan.status) as usize);
+            }
+
+            for installment in plan.installments.iter() {
+                let indexed = due.iter()
+                    .filter(|e| e.plan_id == plan_id && e.installment_number == installment.number)
+                    .count();
+                let pending = installment.status.is_open()
+                    && plan.status != PlanStatus::Liquidated;
+                assert_eq!(indexed, pending as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn test_indexes_follow_every_transition() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let paid_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &2, &due_dates);
+        assert_indexes_consistent(&ctx);
+
+        // Collection and completion
+        ctx.advance_time(1000);
+        client.collect_installment(&paid_id, &1, &ctx.collector);
+        assert_indexes_consistent(&ctx);
+        ctx.advance_time(1000);
+        client.collect_installment(&paid_id, &2, &ctx.collector);
+        assert_eq!(client.get_plan(&paid_id).status, PlanStatus::Completed);
+        assert_indexes_consistent(&ctx);
+
+        // Default and liquidation
+        let irregular = SorobanVec::from_array(&ctx.env, [4000u64, 4500, 6000]);
+        let defaulted_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &irregular);
+        ctx.buffer_client().set_shares(&ctx.user, &0, &800);
+        ctx.advance_time(1500);
+        client.declare_default(&defaulted_id, &1);
+        assert_indexes_consistent(&ctx);
+
+        let liquidator = Address::generate(&ctx.env);
+        auction_token(&ctx, &liquidator, 5000);
+        client.start_auction(&defaulted_id);
+        ctx.advance_time(AUCTION_DURATION_SECS / 2);
+        client.bid(&defaulted_id, &liquidator, &5000);
+        assert_eq!(client.get_plan(&defaulted_id).status, PlanStatus::Liquidated);
+        assert_indexes_consistent(&ctx);
+    }
+
+    #[test]
+    fn test_cross_margin_tops_up_with_check_and_lock() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_cross_margin(&ctx.user, &true);
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        client.create_plan(&ctx.user, &ctx.merchant, &500, &1, &due_dates);
+
+        // Only the 500 shares missing from the shared lock were locked
+        let balance = ctx.buffer_client().get_balance(&ctx.user);
+        assert_eq!(balance.protected_shares, 1500);
+        assert_eq!(balance.available_shares, 8500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #4)")] // InsufficientAvailable
+    fn test_cross_margin_top_up_beyond_available() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.set_cross_margin(&ctx.user, &true);
+        ctx.buffer_client().set_shares(&ctx.user, &1000, &1000);
+        client.execute_change(&client.set_collateral_factor(&ctx.admin, &ctx.buffer, &15000));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1500, &1, &due_dates);
+    }
+
+    #[test]
+    fn test_completion_uses_paid_count_not_installments() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &2, &due_dates);
+        ctx.advance_time(2000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(client.get_plan_summary(&plan_id).paid_count, 1);
+
+        // Completion must not read installment 1 again
+        ctx.env.as_contract(&ctx.bridge, || {
+            ctx.env.storage().persistent().remove(&PlanKey::Installment(plan_id, 1));
+        });
+        client.collect_installment(&plan_id, &2, &ctx.collector);
+        assert_eq!(client.get_plan_header(&plan_id).status, PlanStatus::Completed);
+    }
+
+    #[test]
+    fn test_read_model_receives_plan_transitions() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let read_model = ctx.env.register(MockReadModel, ());
+        client.set_read_model(&Some(read_model.clone()));
+        assert_eq!(client.get_read_model(), Some(read_model.clone()));
+        // Collects out of order
+        let mut config = client.get_config();
+        config.sequential_collection = false;
+        client.execute_change(&client.update_config(&config));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3001, &2, &due_dates);
+        ctx.advance_time(2500);
+        client.collect_installment(&plan_id, &2, &ctx.collector);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        let updates = MockReadModelClient::new(&ctx.env, &read_model).updates();
+        let mut actions = SorobanVec::new(&ctx.env);
+        for update in updates.iter() {
+            actions.push_back(update.action);
+        }
+        assert_eq!(actions, SorobanVec::from_array(&ctx.env, [
+            PlanAction::Created,
+            PlanAction::Collected,
+            PlanAction::Collected,
+            PlanAction::Completed,
+        ]));
+        assert_eq!(updates.get(0).unwrap().amount, 3001);
+        // The last installment carries the rounding remainder
+        assert_eq!(updates.get(1).unwrap().amount, 1501);
+        assert_eq!(updates.get(2).unwrap().amount, 1500);
+        assert_eq!(updates.get(3).unwrap().merchant, ctx.merchant);
+
+        // Clearing the read model stops the notifications
+        client.set_read_model(&None);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &SorobanVec::from_array(&ctx.env, [4000u64]));
+        assert_eq!(MockReadModelClient::new(&ctx.env, &read_model).updates().len(), 4);
+    }
+
+    #[test]
+    fn test_broken_read_model_does_not_block_collections() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let read_model = ctx.env.register(MockReadModel, ());
+        MockReadModelClient::new(&ctx.env, &read_model).set_broken(&true);
+        client.set_read_model(&Some(read_model));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1500);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        assert_eq!(client.get_plan(&plan_id).status, PlanStatus::Completed);
+        assert_eq!(client.get_plan_history_len(&plan_id), 3);
+    }
+
+    #[test]
+    fn test_cleanup_reclaims_stale_entries_for_a_bounty() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let treasury = Address::generate(&ctx.env);
+        let token = debt_token(&ctx, &treasury, 1_000_000);
+        let config = RebateConfig { token: token.clone(), rate_bps: 1000 };
+        client.execute_change(&client.set_rebate_config(&ctx.admin, &config));
+        client.fund_rebates(&treasury, &25);
+        client.set_cleanup_bounty(&ctx.admin, &Some(10));
+
+        let stale = client.set_collateral_factor(&ctx.admin, &ctx.buffer, &15000);
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let first = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        let second = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        client.collect_installment(&first, &1, &ctx.user);
+        client.collect_installment(&second, &1, &ctx.user);
+        ctx.advance_time(ARCHIVE_RETENTION_SECS);
+
+        // A fresh change is still executable and stops the proposal scan
+        let fresh = client.set_collateral_factor(&ctx.admin, &ctx.buffer, &12000);
+        let keeper = Address::generate(&ctx.env);
+        let report = client.cleanup(&keeper, &10);
+
+        assert_eq!(report, CleanupReport { reclaimed: 3, bounty: 25 });
+        assert_eq!(TokenClient::new(&ctx.env, &token).balance(&keeper), 25);
+        assert_eq!(client.get_rebate_pool(), 0);
+        assert!(client.try_get_pending_change(&stale).is_err());
+        assert!(client.try_get_pending_change(&fresh).is_ok());
+        assert!(client.try_get_plan(&first).is_err());
+        assert!(client.try_get_plan(&second).is_err());
+        assert_eq!(client.get_status_count(&PlanStatus::Completed), 0);
+
+        // Nothing left to reclaim: no bounty
+        assert_eq!(client.cleanup(&keeper, &10), CleanupReport { reclaimed: 0, bounty: 0 });
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #43)")] // ChangeNotFound
+    fn test_expired_change_cannot_execute() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let change_id = client.set_collateral_factor(&ctx.admin, &ctx.buffer, &15000);
+        ctx.advance_time(CHANGE_EXPIRY_SECS);
+        client.execute_change(&change_id);
+    }
+
+    /// Data of the latest `name` event published by the bridge for `plan_id`
+    fn plan_event<T: TryFromVal<Env, Val>>(ctx: &TestContext, name: &str, plan_id: u64) -> T {
+        let topics: SorobanVec<Val> = (events::PREVIOUS_VERSION, Symbol::new(&ctx.env, name), plan_id).into_val(&ctx.env);
+        let (_, _, data) = ctx.env.events().all().iter()
+            .filter(|(contract, event_topics, _)| *contract == ctx.bridge && event_topics.len() == 4 && event_topics.slice(0..3) == topics)
+            .last()
+            .expect("event not published");
+        T::try_from_val(&ctx.env, &data).unwrap()
+    }
+
+    #[test]
+    fn test_plan_events_carry_structured_payloads() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+
+        let created: PlanCreated = plan_event(&ctx, "plan_new", plan_id);
+        assert_eq!(created, PlanCreated {
+            plan_id,
+            user: ctx.user.clone(),
+            merchant: ctx.merchant.clone(),
+            total_amount: 1000,
+            installments_count: 1,
+            shares_locked: 1000,
+            status: PlanStatus::Active,
+            created_at: 1000,
+        });
+
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        let paid: InstallmentPaid = plan_event(&ctx, "inst_paid", plan_id);
+        assert_eq!(paid.amount, 1000);
+        assert_eq!(paid.collector, ctx.collector);
+        assert_eq!(paid.paid_at, 2000);
+        let completed: PlanCompleted = plan_event(&ctx, "plan_done", plan_id);
+        assert_eq!(completed.total_paid, 1000);
+    }
+
+    #[test]
+    fn test_default_events_carry_structured_payloads() {
+        let ctx = TestContext::new();
+        let plan_id = defaulted_plan(&ctx);
+
+        let failed: InstallmentFailed = plan_event(&ctx, "inst_fail", plan_id);
+        assert_eq!(failed, InstallmentFailed { plan_id, installment_number: 1, amount: 1000, due_date: 2000 });
+        let defaulted: PlanDefaulted = plan_event(&ctx, "plan_dflt", plan_id);
+        assert_eq!(defaulted.unpaid_amount, 3000);
+        assert_eq!(defaulted.merchant, ctx.merchant);
+        assert_eq!(defaulted.defaulted_at, 2500);
+    }
+
+    #[test]
+    fn test_default_event_reports_shortfall() {
+        let ctx = TestContext::new();
+        let plan_id = defaulted_plan(&ctx);
+
+        // 800 protected shares against a 1000 installment
+        let defaulted: PlanDefaulted = plan_event(&ctx, "plan_dflt", plan_id);
+        assert_eq!(defaulted.installment_number, 1);
+        assert_eq!(defaulted.shortfall, 200);
+
+        // Later failures don't publish a second default
+        ctx.advance_time(1000);
+        ctx.client().declare_default(&plan_id, &2);
+        let failed: InstallmentFailed = plan_event(&ctx, "inst_fail", plan_id);
+        assert_eq!(failed.installment_number, 2);
+        let defaults = ctx.env.events().all().iter()
+            .filter(|(_, topics, _)| topics.len() == 4 && topics.slice(0..3) == (events::SCHEMA_VERSION, Symbol::new(&ctx.env, "plan_dflt"), plan_id).into_val(&ctx.env))
+            .count();
+        assert_eq!(defaults, 0);
+    }
+
+    #[test]
+    fn test_completion_event_reports_unlocked_collateral() {
+        let ctx = TestContext::new();
+        let plan_id = completed_plan_with_locked_shares(&ctx);
+
+        // The 150% collateral factor leaves 2000 shares locked after the last payment
+        let done: PlanCompleted = plan_event(&ctx, "plan_done", plan_id);
+        assert_eq!(done, PlanCompleted {
+            plan_id,
+            user: ctx.user.clone(),
+            merchant: ctx.merchant.clone(),
+            total_paid: 4000,
+            completed_at: 3000,
+            unlocked_shares: 2000,
+            release_at: 0,
+        });
+    }
+
+    #[test]
+    fn test_completion_event_reports_held_collateral() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.execute_change(&client.set_release_delay(&ctx.admin, &86400));
+        let plan_id = completed_plan_with_locked_shares(&ctx);
+
+        let done: PlanCompleted = plan_event(&ctx, "plan_done", plan_id);
+        assert_eq!(done.unlocked_shares, 0);
+        assert_eq!(done.release_at, 3000 + 86400);
+    }
+
+    #[test]
+    fn test_status_transitions_emit_before_and_after() {
+        let ctx = TestContext::new();
+        let plan_id = defaulted_plan(&ctx);
+
+        let changed: PlanStatusChanged = plan_event(&ctx, "status", plan_id);
+        assert_eq!(changed, PlanStatusChanged {
+            plan_id,
+            before: PlanStatus::Active,
+            after: PlanStatus::Defaulted,
+            changed_at: 2500,
+        });
+    }
+
+    #[test]
+    fn test_freeze_events_carry_before_and_after() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+
+        client.freeze_plan(&ctx.admin, &plan_id);
+        let frozen: PlanFreezeChanged = plan_event(&ctx, "frozen", plan_id);
+        assert_eq!(frozen, PlanFreezeChanged { plan_id, arbiter: ctx.admin.clone(), before: false, after: true });
+
+        // Unfreezing twice: the second call reports no change
+        client.unfreeze_plan(&ctx.admin, &plan_id);
+        client.unfreeze_plan(&ctx.admin, &plan_id);
+        let unfrozen: PlanFreezeChanged = plan_event(&ctx, "unfrozen", plan_id);
+        assert!(!unfrozen.before && !unfrozen.after);
+    }
+
+    #[test]
+    fn test_unversioned_events_emitted_during_deprecation_window() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+
+        let versioned: PlanCreated = plan_event(&ctx, "plan_new", plan_id);
+        let legacy: SorobanVec<Val> = (symbol_short!("plan_new"), plan_id).into_val(&ctx.env);
+        let (_, _, data) = ctx.env.events().all().iter()
+            .find(|(_, topics, _)| *topics == legacy)
+            .expect("legacy event not published");
+        assert_eq!(PlanCreated::try_from_val(&ctx.env, &data).unwrap(), versioned);
+    }
+
+    #[test]
+    fn test_collateral_events_follow_the_plan_lock() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        let locked: CollateralChanged = plan_event(&ctx, "locked", plan_id);
+        assert_eq!(locked, CollateralChanged {
+            plan_id,
+            user: ctx.user.clone(),
+            shares: 2000,
+            locked_before: 0,
+            locked_after: 2000,
+        });
+
+        // Paying from Available unlocks part of the plan's collateral
+        client.collect_installment(&plan_id, &1, &ctx.user);
+        let unlocked: CollateralChanged = plan_event(&ctx, "unlocked", plan_id);
+        assert_eq!((unlocked.shares, unlocked.locked_before, unlocked.locked_after), (1000, 2000, 1000));
+
+        client.collect_installment(&plan_id, &2, &ctx.user);
+        let freed: CollateralChanged = plan_event(&ctx, "freed", plan_id);
+        assert_eq!((freed.shares, freed.locked_before, freed.locked_after), (1000, 1000, 0));
+    }
+
+    #[test]
+    fn test_collateral_events_on_delayed_release() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        client.execute_change(&client.set_release_delay(&ctx.admin, &86400));
+        let plan_id = completed_plan_with_locked_shares(&ctx);
+
+        ctx.advance_time(86400);
+        client.release_collateral(&plan_id);
+        let freed: CollateralChanged = plan_event(&ctx, "freed", plan_id);
+        assert_eq!((freed.shares, freed.locked_before, freed.locked_after), (2000, 2000, 0));
+    }
+
+    #[test]
+    fn test_every_event_carries_the_next_sequence_number() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &SorobanVec::from_array(&ctx.env, [2000u64]));
+
+        // Versioned plan events carry it as their last topic, other events as data
+        let mut seqs = SorobanVec::<u64>::new(&ctx.env);
+        for (contract, topics, _) in ctx.env.events().all().iter() {
+            if contract != ctx.bridge {
+                continue;
+            }
+            if topics.len() == 4 && topics.get(0).unwrap().shallow_eq(&events::PREVIOUS_VERSION.to_val()) {
+                seqs.push_back(u64::try_from_val(&ctx.env, &topics.get(3).unwrap()).unwrap());
+            }
+        }
+        assert!(seqs.len() >= 2);
+        for i in 1..seqs.len() {
+            assert_eq!(seqs.get(i).unwrap(), seqs.get(i - 1).unwrap() + 1);
+        }
+
+        client.freeze_plan(&ctx.admin, &plan_id);
+        client.set_global_cap(&Some(1_000_000));
+        let (_, _, data) = ctx.env.events().all().last().unwrap();
+        let frozen_seq = seqs.last().unwrap() + 1;
+        assert_eq!(u64::try_from_val(&ctx.env, &data).unwrap(), frozen_seq + 1);
+    }
+
+
+    #[test]
+    fn test_collections_emit_settled_events_net_of_fees() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let treasury = Address::generate(&ctx.env);
+        client.execute_change(&client.set_fee(&ctx.admin, &100, &Some(treasury)));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        let settled: MerchantSettled = plan_event(&ctx, "settled", plan_id);
+        assert_eq!(settled, MerchantSettled {
+            plan_id,
+            merchant: ctx.merchant.clone(),
+            payout: ctx.merchant.clone(),
+            installments: SorobanVec::from_array(&ctx.env, [1u32]),
+            gross: 1000,
+            fee: 10,
+            amount: 990,
+            settled_at: 2000,
+        });
+    }
+
+    #[test]
+    fn test_auction_proceeds_emit_settled_event_for_unpaid_installments() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+        let liquidator = Address::generate(&ctx.env);
+        auction_token(&ctx, &liquidator, 5000);
+        client.start_auction(&plan_id);
+        let price = client.bid(&plan_id, &liquidator, &5000);
+
+        let settled: MerchantSettled = plan_event(&ctx, "settled", plan_id);
+        assert_eq!(settled.installments, SorobanVec::from_array(&ctx.env, [1u32, 2, 3]));
+        assert_eq!(settled.fee, 0);
+        assert_eq!(settled.amount, price.min(settled.gross));
+        assert_eq!(settled.payout, ctx.merchant);
+    }
+
+
+    #[test]
+    fn test_mark_overdue_flags_past_due_installment() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+
+        assert!(client.try_mark_overdue(&plan_id, &1).is_err()); // NotDueYet
+        ctx.advance_time(1001);
+        client.mark_overdue(&plan_id, &1);
+
+        let overdue: InstallmentOverdue = plan_event(&ctx, "overdue", plan_id);
+        assert_eq!(overdue, InstallmentOverdue {
+            plan_id,
+            installment_number: 1,
+            user: ctx.user.clone(),
+            amount: 1000,
+            due_date: 2000,
+            marked_at: 2001,
+        });
+        assert_eq!(client.get_installment(&plan_id, &1).status, InstallmentStatus::Overdue);
+        assert_eq!(client.get_next_due(&plan_id).unwrap().number, 1);
+        assert!(client.try_mark_overdue(&plan_id, &1).is_err()); // Already flagged
+        assert!(client.try_mark_overdue(&plan_id, &2).is_err()); // NotDueYet
+
+        // Still collectable
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(client.get_installment(&plan_id, &1).status, InstallmentStatus::Paid);
+        assert!(client.try_mark_overdue(&plan_id, &1).is_err()); // AlreadyPaid
+    }
+
+
+    #[test]
+    fn test_plan_events_are_filterable_by_counterparty() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        let created: PlanCreated = plan_event(&ctx, "plan_new", plan_id);
+
+        // What an RPC filter on (v2, *, *, merchant) would return
+        let mut matched = SorobanVec::<(u64, Val)>::new(&ctx.env);
+        for (contract, topics, data) in ctx.env.events().all().iter() {
+            if contract == ctx.bridge
+                && topics.len() == 4
+                && topics.get(0).unwrap().shallow_eq(&events::SCHEMA_VERSION.to_val())
+                && Address::try_from_val(&ctx.env, &topics.get(3).unwrap()).unwrap() == ctx.merchant {
+                matched.push_back(<(u64, Val)>::try_from_val(&ctx.env, &data).unwrap());
+            }
+        }
+
+        // plan_new then locked, numbered like their v1 copies
+        assert_eq!(matched.len(), 2);
+        let (seq, payload) = matched.get(0).unwrap();
+        assert_eq!(PlanCreated::try_from_val(&ctx.env, &payload).unwrap(), created);
+        assert_eq!(matched.get(1).unwrap().0, seq + 1);
+
+        let user_topics: SorobanVec<Val> = (events::SCHEMA_VERSION, symbol_short!("plan_new"), ctx.user.clone(), ctx.merchant.clone())
+            .into_val(&ctx.env);
+        assert!(ctx.env.events().all().iter().any(|(_, topics, _)| topics == user_topics));
+    }
+
+
+    #[test]
+    fn test_recent_transitions_page_from_a_cursor() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        assert_eq!(client.get_transition_count(), 2);
+        let all = client.get_recent_transitions(&0, &10);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all.get(0).unwrap().action, PlanAction::Created);
+        assert_eq!(all.get(1).unwrap(), Transition {
+            number: 1,
+            plan_id,
+            action: PlanAction::Collected,
+            installment_number: 1,
+            actor: Some(ctx.collector.clone()),
+            timestamp: 2000,
+        });
+
+        // An indexer resumes from its last number plus one
+        let next = client.get_recent_transitions(&1, &10);
+        assert_eq!(next.len(), 1);
+        assert_eq!(next.get(0).unwrap().number, 1);
+        assert_eq!(client.get_recent_transitions(&2, &10).len(), 0);
+    }
+
+    #[test]
+    fn test_recent_transitions_keep_only_the_latest() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        ctx.env.cost_estimate().budget().reset_unlimited();
+        let due_dates = SorobanVec::from_array(&ctx.env, [5000u64]);
+        for _ in 0..(RECENT_TRANSITIONS + 5) {
+            client.create_plan(&ctx.user, &ctx.merchant, &10, &1, &due_dates);
+        }
+
+        // The first five were overwritten: a stale cursor starts at the oldest kept
+        let page = client.get_recent_transitions(&0, &3);
+        assert_eq!(page.len(), 3);
+        assert_eq!(page.get(0).unwrap().number, 5);
+        assert_eq!(page.get(2).unwrap().number, 7);
+        let tail = client.get_recent_transitions(&(RECENT_TRANSITIONS as u64), &10);
+        assert_eq!(tail.len(), 5);
+        assert_eq!(tail.get(4).unwrap().number, RECENT_TRANSITIONS as u64 + 4);
+    }
+
+
+    #[test]
+    fn test_reconcile_escrow_absorbs_clawback() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let treasury = Address::generate(&ctx.env);
+        let issuer = Address::generate(&ctx.env);
+        let asset = ctx.env.register_stellar_asset_contract_v2(issuer);
+        asset.issuer().set_flag(IssuerFlags::ClawbackEnabledFlag);
+        let token = asset.address();
+        StellarAssetClient::new(&ctx.env, &token).mint(&treasury, &1000);
+
+        let config = RebateConfig { token: token.clone(), rate_bps: 1000 };
+        client.execute_change(&client.set_rebate_config(&ctx.admin, &config));
+        client.fund_rebates(&treasury, &1000);
+        assert_eq!(client.reconcile_escrow(), 0);
+
+        StellarAssetClient::new(&ctx.env, &token).clawback(&ctx.bridge, &300);
+        assert_eq!(client.reconcile_escrow(), -300);
+        let (_, topics, _) = ctx.env.events().all().last().unwrap();
+        assert_eq!(topics, (symbol_short!("escrow"), token.clone(), 1000i128, 700i128).into_val(&ctx.env));
+        assert_eq!(client.get_rebate_pool(), 700);
+        assert_eq!(client.reconcile_escrow(), 0);
+    }
+
+    #[test]
+    fn test_reconcile_escrow_reports_unbooked_rebate_tokens() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let token = debt_token(&ctx, &ctx.bridge, 50);
+
+        let config = RebateConfig { token, rate_bps: 1000 };
+        client.execute_change(&client.set_rebate_config(&ctx.admin, &config));
+        assert_eq!(client.reconcile_escrow(), 50);
+        assert_eq!(client.get_rebate_pool(), 0);
+    }
+
+    #[test]
+    fn test_reconcile_escrow_requires_rebate_config() {
+        let ctx = TestContext::new();
+        let result = ctx.client().try_reconcile_escrow();
+        assert_eq!(result, Err(Ok(ContractError::RebatesNotConfigured)));
+    }
+
+
+    #[test]
+    fn test_merchant_attaches_anchor_ref_to_settlement() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        let anchor_ref = BytesN::from_array(&ctx.env, &[7u8; 32]);
+
+        // Nothing settled yet
+        assert!(client.try_attach_anchor_ref(&ctx.merchant, &plan_id, &1, &anchor_ref).is_err());
+
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        client.attach_anchor_ref(&ctx.merchant, &plan_id, &1, &anchor_ref);
+
+        let anchored: SettlementAnchored = plan_event(&ctx, "anchored", plan_id);
+        assert_eq!(anchored, SettlementAnchored {
+            plan_id,
+            installment_number: 1,
+            merchant: ctx.merchant.clone(),
+            anchor_ref: anchor_ref.clone(),
+        });
+        assert_eq!(client.get_anchor_ref(&plan_id, &1), Some(anchor_ref));
+        assert_eq!(client.get_anchor_ref(&plan_id, &2), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error(Contract, #41)")] // Unauthorized
+    fn test_only_plan_merchant_attaches_anchor_ref() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        let other = Address::generate(&ctx.env);
+        client.attach_anchor_ref(&other, &plan_id, &1, &BytesN::from_array(&ctx.env, &[7u8; 32]));
+    }
+
+
+    #[test]
+    fn test_index_cursor_reads_counters_together() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+
+        // The last event of create_plan carries the current sequence number
+        let (_, topics, _) = ctx.env.events().all().iter()
+            .filter(|(_, topics, _)| topics.len() == 4 && topics.get(0).unwrap().shallow_eq(&events::PREVIOUS_VERSION.to_val()))
+            .last()
+            .unwrap();
+        let last_seq = u64::try_from_val(&ctx.env, &topics.get(3).unwrap()).unwrap();
+
+        let cursor = client.get_index_cursor();
+        assert_eq!(cursor, IndexCursor {
+            plan_count: 1,
+            event_seq: last_seq,
+            transition_count: 1,
+            ledger: ctx.env.ledger().sequence(),
+            timestamp: 1000,
+        });
+    }
+
+
+    #[test]
+    fn test_clients_see_contract_error_codes() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+
+        assert_eq!(client.try_get_plan(&99).err(), Some(Ok(ContractError::PlanNotFound)));
+        assert_eq!(client.try_declare_default(&plan_id, &2), Err(Ok(ContractError::InstallmentNotFound)));
+        assert_eq!(client.try_declare_default(&plan_id, &1), Err(Ok(ContractError::NotDueYet)));
+        assert_eq!(
+            client.try_create_plan(&ctx.user, &ctx.merchant, &0, &1, &due_dates),
+            Err(Ok(ContractError::InvalidAmount))
+        );
+
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(
+            client.try_collect_installment(&plan_id, &1, &ctx.collector),
+            Err(Ok(ContractError::AlreadyPaid))
+        );
+    }
+
+    #[test]
+    fn test_contract_errors_convert_to_soroban_errors() {
+        let error: soroban_sdk::Error = ContractError::Unauthorized.into();
+        assert_eq!(error, soroban_sdk::Error::from_contract_error(41));
+        assert_eq!(ContractError::try_from(error), Ok(ContractError::Unauthorized));
+    }
+
+    #[test]
+    fn test_plan_math_overflow_is_rejected() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+
+        // A huge Buffer balance must not wrap the LTV bound into a small one
+        ctx.buffer_client().set_shares(&ctx.user, &i128::MAX, &0);
+        let result = client.try_create_plan(&ctx.user, &ctx.merchant, &(i128::MAX / 2), &1, &due_dates);
+        assert_eq!(result, Err(Ok(ContractError::InvalidAmount)));
+
+        // Nor may a new plan wrap the outstanding total under the cap
+        ctx.buffer_client().set_shares(&ctx.user, &10000, &0);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        client.set_global_cap(&Some(i128::MAX));
+        let result = client.try_create_plan(&ctx.user, &ctx.merchant, &i128::MAX, &1, &due_dates);
+        assert_eq!(result, Err(Ok(ContractError::InvalidAmount)));
+        assert_eq!(client.get_total_outstanding(), 1000);
+    }
+
+    #[test]
+    fn test_deadlines_near_the_end_of_time_do_not_wrap() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let mut config = client.get_config();
+        config.grace_period_secs = 1000;
+        client.execute_change(&client.update_config(&config));
+        client.execute_change(&client.set_timelock_delay(&1000));
+
+        // With no horizon, a due date can sit right below u64::MAX
+        let due_dates = SorobanVec::from_array(&ctx.env, [u64::MAX - 10]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        ctx.env.ledger().set_timestamp(u64::MAX - 5);
+        assert_eq!(client.try_declare_default(&plan_id, &1), Err(Ok(ContractError::NotDueYet)));
+
+        client.collect_installment(&plan_id, &1, &ctx.user);
+        assert_eq!(client.get_credit_standing(&ctx.user).on_time_streak, 1);
+
+        // Nor can a change queued this late overflow its timelock
+        let result = client.try_set_timelock_delay(&0);
+        assert_eq!(result, Err(Ok(ContractError::InvalidConfig)));
+    }
+
+    #[test]
+    fn test_rebate_pool_overflow_is_rejected() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let treasury = Address::generate(&ctx.env);
+        let token = debt_token(&ctx, &treasury, 1000);
+        let config = RebateConfig { token: token.clone(), rate_bps: 1000 };
+        client.execute_change(&client.set_rebate_config(&ctx.admin, &config));
+
+        ctx.env.as_contract(&ctx.bridge, || {
+            ctx.env.storage().persistent().set(&DataKey::RebatePool, &i128::MAX);
+        });
+        assert_eq!(client.try_fund_rebates(&treasury, &1), Err(Ok(ContractError::InvalidAmount)));
+        assert_eq!(TokenClient::new(&ctx.env, &token).balance(&treasury), 1000);
+    }
+
+    #[test]
+    fn test_ratios_saturate_at_i128_extremes() {
+        assert_eq!(BridgeContract::ratio_bps(i128::MAX, i128::MAX), BPS_DENOMINATOR);
+        assert_eq!(BridgeContract::ratio_bps(i128::MAX / 2, i128::MAX), BPS_DENOMINATOR / 2);
+        assert_eq!(BridgeContract::ratio_bps(1, 4), 2500);
+
+        let env = Env::default();
+        assert_eq!(BridgeContract::mul_div(&env, i128::MAX, 2, 2), Err(ContractError::InvalidAmount));
+        assert_eq!(BridgeContract::add_amounts(&env, i128::MAX, 1), Err(ContractError::InvalidAmount));
+        assert_eq!(BridgeContract::add_amounts(&env, i128::MIN, -1), Err(ContractError::InvalidAmount));
+        assert_eq!(BridgeContract::mul_div(&env, i128::MAX / 3, 3, 3), Ok(i128::MAX / 3));
+    }
+
+    #[test]
+    fn test_create_plan_rejects_unordered_due_dates() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [3000u64, 2000u64, 4000u64]);
+        let result = client.try_create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+        assert_eq!(result, Err(Ok(ContractError::DueDatesOutOfOrder)));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 2001u64, 4000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+        assert_eq!(client.get_installment(&plan_id, &2).due_date, 2001);
+    }
+
+    #[test]
+    fn test_create_plan_enforces_min_due_spacing() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let mut config = client.get_config();
+        config.min_due_spacing_secs = 7 * 24 * 60 * 60;
+        client.execute_change(&client.update_config(&config));
+
+        // Twelve installments a second apart would all fall due at once
+        let mut packed = SorobanVec::new(&ctx.env);
+        for i in 0..12u64 {
+            packed.push_back(2000 + i);
+        }
+        let result = client.try_create_plan(&ctx.user, &ctx.merchant, &1200, &12, &packed);
+        assert_eq!(result, Err(Ok(ContractError::InvalidDueDate)));
+
+        // The first date only has to be in the future
+        let week = 7 * 24 * 60 * 60;
+        let due_dates = SorobanVec::from_array(&ctx.env, [1001u64, 1001 + week, 1001 + 3 * week]);
+        client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+
+        config.min_due_spacing_secs = MAX_DUE_SPACING_SECS + 1;
+        assert_eq!(client.try_update_config(&config), Err(Ok(ContractError::InvalidConfig)));
+    }
+
+    #[test]
+    fn test_migrate_adds_due_spacing_to_v4_config() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let mut expected = client.get_config();
+        expected.grace_period_secs = 3600;
+        let config = v4_config(&expected);
+        ctx.env.as_contract(&ctx.bridge, || {
+            let storage = ctx.env.storage().instance();
+            storage.set(&DataKey::Config, &config);
+            storage.set(&DataKey::Version, &4u32);
+        });
+
+        client.migrate();
+        assert_eq!(client.get_config(), expected);
+    }
+
+    #[test]
+    fn test_create_plan_rejects_dust_installments() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let mut config = client.get_config();
+        config.min_installment_amount = 500;
+        client.execute_change(&client.update_config(&config));
+
+        // 1499 over 3 installments is 499 + 499 + 501
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000u64, 4000u64]);
+        let result = client.try_create_plan(&ctx.user, &ctx.merchant, &1499, &3, &due_dates);
+        assert_eq!(result, Err(Ok(ContractError::InvalidAmount)));
+
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1500, &3, &due_dates);
+        assert_eq!(client.get_installment(&plan_id, &1).amount, 500);
+
+        config.min_installment_amount = -1;
+        assert_eq!(client.try_update_config(&config), Err(Ok(ContractError::InvalidConfig)));
+    }
+
+    #[test]
+    fn test_create_plan_rejects_duplicate_due_dates() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000u64, 3000u64]);
+        let result = client.try_create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+        assert_eq!(result, Err(Ok(ContractError::DuplicateDueDate)));
+        assert_eq!(client.get_plan_count(), 0);
+    }
+
+    #[test]
+    fn test_installments_are_collected_in_order() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000u64, 4000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates);
+        ctx.advance_time(3000);
+
+        let result = client.try_collect_installment(&plan_id, &2, &ctx.collector);
+        assert_eq!(result.err(), Some(Ok(ContractError::InstallmentOutOfOrder)));
+        assert_eq!(client.try_simulate_collect(&plan_id, &3), Err(Ok(ContractError::InstallmentOutOfOrder)));
+
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        client.collect_installment(&plan_id, &2, &ctx.collector);
+        assert!(client.get_installment(&plan_id, &2).paid_at.is_some());
+    }
+
+    #[test]
+    fn test_sequential_collection_can_be_disabled() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let mut config = client.get_config();
+        assert!(config.sequential_collection);
+        config.sequential_collection = false;
+        client.execute_change(&client.update_config(&config));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        ctx.advance_time(2000);
+        client.collect_installment(&plan_id, &2, &ctx.collector);
+        assert!(client.get_installment(&plan_id, &1).paid_at.is_none());
+    }
+
+    #[test]
+    fn test_create_plan_with_nonce_rejects_different_schedule() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        let plan_id = client.create_plan_with_nonce(&ctx.user, &ctx.merchant, &1000, &2, &due_dates, &7);
+
+        // A retry must carry the same schedule, not just the same amounts
+        let moved = SorobanVec::from_array(&ctx.env, [2000u64, 4000]);
+        let result = client.try_create_plan_with_nonce(&ctx.user, &ctx.merchant, &1000, &2, &moved, &7);
+        assert_eq!(result, Err(Ok(ContractError::NonceReused)));
+
+        assert_eq!(client.create_plan_with_nonce(&ctx.user, &ctx.merchant, &1000, &2, &due_dates, &7), plan_id);
+        assert_eq!(client.get_plan_count(), 1);
+    }
+
+    #[test]
+    fn test_collection_is_recorded_before_the_buffer_is_debited() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let read_model = ctx.env.register(MockReadModel, ());
+        let read_model_client = MockReadModelClient::new(&ctx.env, &read_model);
+        read_model_client.set_probe(&ctx.buffer, &ctx.merchant);
+        client.set_read_model(&Some(read_model));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        // Collected and Completed were written while the merchant had
+        // received nothing yet: state first, Buffer calls last
+        let updates = read_model_client.updates();
+        assert_eq!(updates.get(1).unwrap().action, PlanAction::Collected);
+        assert_eq!(updates.get(2).unwrap().action, PlanAction::Completed);
+        assert_eq!(read_model_client.probed(), SorobanVec::from_array(&ctx.env, [0i128, 0, 0]));
+        assert_eq!(ctx.buffer_client().get_received(&ctx.merchant), 1000);
+    }
+
+    #[test]
+    fn test_user_cannot_finance_itself() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        // Even an approved merchant can't be its own customer
+        let name_hash = BytesN::from_array(&ctx.env, &[1u8; 32]);
+        client.register_merchant(&ctx.user, &name_hash, &ctx.user, &2);
+        client.approve_merchant(&ctx.admin, &ctx.user);
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let result = client.try_create_plan(&ctx.user, &ctx.user, &1000, &1, &due_dates);
+        assert_eq!(result, Err(Ok(ContractError::SelfFinancing)));
+        let result = client.try_create_plan_with_nonce(&ctx.user, &ctx.user, &1000, &1, &due_dates, &1);
+        assert_eq!(result, Err(Ok(ContractError::SelfFinancing)));
+
+        // So no repayment history can be minted from self-collections
+        let credit = client.get_user_credit(&ctx.user);
+        assert_eq!(credit.history, CreditHistory {
+            plans_completed: 0,
+            installments_paid: 0,
+            on_time_payments: 0,
+            total_repaid: 0,
+            defaults: 0,
         });
-        
-        if all_paid {
-            plan.status = PlanStatus::Completed;
-            
-            // Release remaining protected shares (if any)
-            if plan.protected_shares > 0 {
-                buffer_client.unlock_shares(&plan.user, &plan.protected_shares);
-                log!(&env, "Released {} remaining shares", plan.protected_shares);
-                plan.protected_shares = 0;
-            }
-        }
-        
-        // ===== SAVE UPDATED PLAN =====
-        
-        env.storage().persistent().set(&DataKey::Plan(plan_id.clone()), &plan);
-        
-        // ===== EMITIR EVENTO =====
-        
-        env.events().publish((
-            symbol_short!("inst_paid"),
-            plan_id,
-            installment_number,
-            payment_source,
-            shares_needed,
-        ), ());
-        
-        Ok(payment_source)
+        assert_eq!(client.get_user_plan_count(&ctx.user), 0);
     }
-    
-    /// Get the next due installment of a plan
-    /// 
-    /// Searches for the first installment that is pending and already due.
-    /// Useful for automatic workers that process collections.
-    pub fn get_next_due(env: Env, plan_id: String) -> Result<Option<Installment>, ContractError> {
-        let plan: BridgePlan = env.storage()
-            .persistent()
-            .get(&DataKey::Plan(plan_id))
-            .ok_or(ContractError::PlanNotFound)?;
-        
-        let current_time = env.ledger().timestamp();
-        
-        // Search for first pending and due installment
-        for i in 0..plan.installments.len() {
-            let installment = plan.installments.get(i).unwrap();
-            if installment.status == InstallmentStatus::Pending 
-                && installment.due_date <= current_time {
-                return Ok(Some(installment));
-            }
-        }
-        
-        // No due installments
-        Ok(None)
+
+    #[test]
+    fn test_create_plan_enforces_max_horizon() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let two_years = 2 * 365 * 24 * 60 * 60;
+        let mut config = client.get_config();
+        config.max_horizon_secs = two_years;
+        client.execute_change(&client.update_config(&config));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 1001 + two_years]);
+        let result = client.try_create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        assert_eq!(result, Err(Ok(ContractError::InvalidDueDate)));
+
+        // The last date may fall exactly on the horizon
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 1000 + two_years]);
+        client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
     }
-    
-    /// Get complete plan summary with updated Buffer values
-    /// 
-    /// Returns the plan along with the current token values of the user's Buffer
-    /// (available and protected). Useful for displaying in UI.
-    pub fn get_plan_summary(
-        env: Env, 
-        plan_id: String, 
-        buffer_contract: Address
-    ) -> Result<(BridgePlan, i128, i128), ContractError> {
-        let plan = Self::get_plan(env.clone(), plan_id)?;
-        
-        let buffer_client = BufferContractClient::new(&env, &buffer_contract);
-        let (available_value, protected_value, _total_value) = buffer_client.get_values(&plan.user);
-        
-        // Returns: (plan, available_value, protected_value)
-        Ok((plan, available_value, protected_value))
+
+    #[test]
+    fn test_unknown_plan_queries_fail_with_plan_not_found() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        // A user without plans is not an error
+        assert_eq!(client.try_get_user_plans(&ctx.user), Ok(Ok(SorobanVec::new(&ctx.env))));
+        assert_eq!(client.try_get_plan_history(&7, &0), Err(Ok(ContractError::PlanNotFound)));
+        assert_eq!(client.try_get_plan_history_len(&7), Err(Ok(ContractError::PlanNotFound)));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        assert_eq!(client.get_user_plans(&ctx.user), SorobanVec::from_array(&ctx.env, [plan_id]));
+        assert_eq!(client.get_plan_history_len(&plan_id), 1);
+        // Pages past the end of a known plan's trail are just empty
+        assert_eq!(client.get_plan_history(&plan_id, &1).len(), 0);
     }
 
+    #[test]
+    fn test_set_cross_margin_returns_a_result() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        assert_eq!(client.try_set_cross_margin(&ctx.user, &true), Ok(Ok(())));
+        assert!(client.is_cross_margin(&ctx.user));
     }
 
-// ============ TESTS WITH MOCK BUFFER ============
+    #[test]
+    fn test_result_errors_match_panic_with_error() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let legacy = ctx.env.register(MockLegacyCaller, ());
+        let legacy_client = MockLegacyCallerClient::new(&ctx.env, &legacy);
+        client.set_collector(&ctx.admin, &legacy, &true);
 
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+        ctx.advance_time(1000);
+        legacy_client.collect(&ctx.bridge, &plan_id, &1);
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use soroban_sdk::{testutils::Address as _, testutils::Ledger, Env, Vec as SorobanVec};
-    
-    // Simple MOCK without complex types
-    #[contract]
-    pub struct MockBuffer;
+        // A returned Err aborts the call exactly like panic_with_error!
+        let returned = legacy_client.try_collect(&ctx.bridge, &plan_id, &1).err().unwrap();
+        let raised = legacy_client.try_raise().err().unwrap();
+        assert_eq!(returned, raised);
+        assert_eq!(raised, Ok(soroban_sdk::Error::from_contract_error(ContractError::AlreadyPaid as u32)));
+    }
 
-    #[contractimpl]
-    impl MockBuffer {
-        pub fn get_balance(_env: Env, _user: Address) -> (i128, i128, i128) {
-            (10000, 0, 10000) // (available, protected, total)
-        }
+    #[test]
+    fn test_due_tolerance_allows_collection_just_before_due_date() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
 
-        pub fn lock_shares(_env: Env, _user: Address, shares: i128) -> (i128, i128, i128) {
-            (shares, 10000 - shares, shares)
-        }
+        let mut config = client.get_config();
+        config.due_tolerance_secs = 300;
+        client.execute_change(&client.update_config(&config));
 
-        pub fn unlock_shares(_env: Env, _user: Address, shares: i128) -> (i128, i128, i128) {
-            (shares, 10000 + shares, 0)
-        }
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
 
-        pub fn debit_available(_env: Env, _user: Address, shares: i128, _to: Address) -> (i128, i128, bool) {
-            (shares, 10000 - shares, false)
-        }
+        ctx.advance_time(699); // 301s early
+        let result = client.try_collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(result.err(), Some(Ok(ContractError::NotDueYet)));
 
-        pub fn debit_protected(_env: Env, _user: Address, shares: i128, _to: Address) -> (i128, i128, bool) {
-            (shares, 10000, true)
-        }
+        ctx.advance_time(1); // 300s early
+        client.collect_installment(&plan_id, &1, &ctx.collector);
 
-        pub fn get_values(_env: Env, _user: Address) -> (i128, i128, i128) {
-            (10000, 0, 10000)
-        }
+        config.due_tolerance_secs = MAX_DUE_TOLERANCE_SECS + 1;
+        assert_eq!(client.try_update_config(&config), Err(Ok(ContractError::InvalidConfig)));
+    }
 
-        pub fn shares_for_amount(_env: Env, amount: i128) -> i128 {
-            amount
-        }
+    #[test]
+    fn test_check_invariants_reports_balanced_user() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000u64, 4000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1001, &3, &due_dates);
+        client.create_plan(&ctx.user, &ctx.merchant, &500, &1, &due_dates.slice(0..1));
+
+        // Paid out of the locked shares, so the lock shrinks with the plan
+        let balance = ctx.buffer_client().get_balance(&ctx.user);
+        ctx.buffer_client().set_shares(&ctx.user, &0, &balance.protected_shares);
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+
+        let report = client.check_invariants(&ctx.user);
+        assert_eq!(report.plans_checked, 2);
+        assert_eq!(report.required_shares, report.locked_shares);
+        assert!(report.locks_balanced);
+        assert_eq!(report.unbalanced_plans.len(), 0);
+        assert!(report.ok);
     }
 
-    pub struct TestContext {
-        pub env: Env,
-        pub user: Address,
-        pub merchant: Address,
-        pub buffer: Address,
-        pub bridge: Address,
+    #[test]
+    fn test_check_invariants_flags_orphaned_locks() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        client.create_plan(&ctx.user, &ctx.merchant, &1000, &1, &due_dates);
+
+        // A failed transaction left extra shares locked in the Buffer
+        let balance = ctx.buffer_client().get_balance(&ctx.user);
+        ctx.buffer_client().set_shares(&ctx.user, &balance.available_shares, &(balance.protected_shares + 300));
+
+        let report = client.check_invariants(&ctx.user);
+        assert_eq!(report.locked_shares - report.required_shares, 300);
+        assert!(!report.locks_balanced && !report.ok);
+
+        assert_eq!(client.reconcile_locks(&ctx.user), 300);
+        assert!(client.check_invariants(&ctx.user).ok);
     }
 
-    impl TestContext {
-        pub fn new() -> Self {
-            let env = Env::default();
-            env.mock_all_auths();
-            env.ledger().set_timestamp(1000);
 
-            let buffer = env.register(MockBuffer, ());
-            let bridge = env.register(BridgeContract, ());
+    #[test]
+    fn test_credit_score_follows_repayments() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        assert_eq!(client.get_credit_score(&ctx.user), 500);
 
-            Self {
-                env: env.clone(),
-                user: Address::generate(&env),
-                merchant: Address::generate(&env),
-                buffer,
-                bridge,
-            }
+        // Installments 1 and 2 on time, 3 late, then the plan completes
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &900, &3, &due_dates);
+        client.collect_installment(&plan_id, &1, &ctx.user);
+        assert_eq!(client.get_credit_score(&ctx.user), 505);
+        ctx.advance_time(2000);
+        client.collect_installment(&plan_id, &2, &ctx.collector);
+        ctx.advance_time(5000);
+        client.collect_installment(&plan_id, &3, &ctx.collector);
+
+        assert_eq!(client.get_credit_score(&ctx.user), 500 + 2 * 5 - 10 + 20);
+        assert_eq!(client.get_user_credit(&ctx.user).score, 520);
+    }
+
+    #[test]
+    fn test_credit_score_drops_on_default_and_is_clamped() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        defaulted_plan(&ctx);
+        assert_eq!(client.get_credit_score(&ctx.user), 400);
+
+        let history = CreditHistory { defaults: 10, ..Default::default() };
+        assert_eq!(BridgeContract::credit_score(&history, &Recovery::default()), 0);
+        let history = CreditHistory { on_time_payments: 200, installments_paid: 200, ..Default::default() };
+        assert_eq!(BridgeContract::credit_score(&history, &Recovery::default()), 1000);
+    }
+
+
+    fn credit_curve() -> CreditCurve {
+        CreditCurve {
+            base_limit: 1000,
+            volume_bps: 5000,
+            tenure_amount: 500,
+            tenure_period_secs: 1000,
+            default_penalty_bps: 5000,
+            max_limit: 3000,
         }
+    }
 
-        pub fn client(&self) -> BridgeContractClient {
-            BridgeContractClient::new(&self.env, &self.bridge)
+    #[test]
+    fn test_credit_limit_grows_with_repayments_and_tenure() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        assert_eq!(client.get_credit_limit(&ctx.user), None);
+
+        let mut config = client.get_config();
+        config.credit_curve = credit_curve();
+        client.execute_change(&client.update_config(&config));
+        assert_eq!(client.get_credit_limit(&ctx.user), Some(1000));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let result = client.try_create_plan(&ctx.user, &ctx.merchant, &1001, &1, &due_dates);
+        assert_eq!(result.err(), Some(Ok(ContractError::CreditLimitExceeded)));
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &900, &1, &due_dates);
+
+        // 900 repaid and one tenure period: 1000 + 450 + 500
+        ctx.advance_time(1000);
+        client.collect_installment(&plan_id, &1, &ctx.collector);
+        assert_eq!(client.get_credit_limit(&ctx.user), Some(1950));
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [3000u64]);
+        let result = client.try_create_plan(&ctx.user, &ctx.merchant, &1951, &1, &due_dates);
+        assert_eq!(result.err(), Some(Ok(ContractError::CreditLimitExceeded)));
+        client.create_plan(&ctx.user, &ctx.merchant, &1950, &1, &due_dates);
+    }
+
+    #[test]
+    fn test_credit_tenure_survives_archiving_the_first_plan() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        client.collect_installment(&plan_id, &1, &ctx.user);
+
+        let mut config = client.get_config();
+        config.credit_curve = CreditCurve { max_limit: i128::MAX, ..credit_curve() };
+        client.execute_change(&client.update_config(&config));
+        ctx.advance_time(ARCHIVE_RETENTION_SECS);
+        let limit = client.get_credit_limit(&ctx.user);
+
+        client.archive_plan(&plan_id);
+        assert_eq!(client.get_credit_limit(&ctx.user), limit);
+        assert!(limit.unwrap() > 1000 + 50);
+    }
+
+    #[test]
+    fn test_credit_limit_is_cut_by_defaults_and_capped() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        defaulted_plan(&ctx);
+
+        let mut config = client.get_config();
+        config.credit_curve = credit_curve();
+        client.execute_change(&client.update_config(&config));
+        assert_eq!(client.get_credit_limit(&ctx.user), Some((1000 + 500) / 2));
+
+        ctx.advance_time(100_000);
+        assert_eq!(client.get_credit_limit(&ctx.user), Some(3000));
+
+        config.credit_curve = CreditCurve { max_limit: 999, ..credit_curve() };
+        assert_eq!(client.try_update_config(&config), Err(Ok(ContractError::InvalidConfig)));
+    }
+
+
+    #[test]
+    fn test_on_time_streak_forgives_a_default() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        defaulted_plan(&ctx);
+
+        let standing = client.get_credit_standing(&ctx.user);
+        assert_eq!(standing, CreditStanding {
+            score: 400,
+            outstanding_defaults: 1,
+            on_time_streak: 0,
+            payments_to_recover: RECOVERY_STREAK,
+            score_after_on_time: 405,
+            score_after_late: 390,
+            score_after_recovery: 500 + 6 * 5,
+        });
+
+        ctx.buffer_client().set_shares(&ctx.user, &10000, &800);
+        let due_dates = SorobanVec::from_array(&ctx.env, [5000u64, 6000, 7000, 8000, 9000, 10000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &600, &6, &due_dates);
+        for number in 1..=5 {
+            client.collect_installment(&plan_id, &number, &ctx.user);
         }
+        let standing = client.get_credit_standing(&ctx.user);
+        assert_eq!((standing.score, standing.on_time_streak, standing.payments_to_recover), (425, 5, 1));
 
-        pub fn advance_time(&self, seconds: u64) {
-            self.env.ledger().set_timestamp(self.env.ledger().timestamp() + seconds);
+        // The sixth forgives the default, and completes the plan
+        client.collect_installment(&plan_id, &6, &ctx.user);
+        let standing = client.get_credit_standing(&ctx.user);
+        assert_eq!((standing.outstanding_defaults, standing.payments_to_recover), (0, 0));
+        assert_eq!(client.get_credit_score(&ctx.user), 500 + 6 * 5 + 20);
+        assert_eq!(client.get_user_credit(&ctx.user).history.defaults, 1);
+    }
+
+    #[test]
+    fn test_late_installment_restarts_the_streak() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        defaulted_plan(&ctx);
+
+        ctx.buffer_client().set_shares(&ctx.user, &10000, &800);
+        let due_dates = SorobanVec::from_array(&ctx.env, [3000u64, 4000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &200, &2, &due_dates);
+        client.collect_installment(&plan_id, &1, &ctx.user);
+        assert_eq!(client.get_credit_standing(&ctx.user).on_time_streak, 1);
+
+        ctx.advance_time(2000); // 500s past the second due date
+        client.collect_installment(&plan_id, &2, &ctx.collector);
+        let standing = client.get_credit_standing(&ctx.user);
+        assert_eq!((standing.on_time_streak, standing.payments_to_recover), (0, RECOVERY_STREAK));
+    }
+
+
+    #[test]
+    fn test_collateral_tiers_lower_the_lock_of_proven_users() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let mut config = client.get_config();
+        config.collateral_tiers = SorobanVec::from_array(&ctx.env, [
+            CollateralTier { min_score: 0, ratio_bps: 10000 },
+            CollateralTier { min_score: 540, ratio_bps: 6000 },
+        ]);
+        client.execute_change(&client.update_config(&config));
+
+        // New users lock the whole plan
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000, 5000]);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &400, &4, &due_dates);
+        assert_eq!(client.get_plan(&plan_id).total_shares, 400);
+        for number in 1..=4 {
+            client.collect_installment(&plan_id, &number, &ctx.user);
         }
+
+        // Four on-time installments and a completed plan: 540
+        assert_eq!(client.get_collateral_ratio(&ctx.user), 6000);
+        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &1000, &4, &due_dates);
+        assert_eq!(client.get_plan(&plan_id).total_shares, 600);
     }
 
     #[test]
-    fn test_create_plan_basic() {
+    fn test_collateral_tiers_are_validated() {
         let ctx = TestContext::new();
         let client = ctx.client();
+        assert_eq!(client.get_collateral_ratio(&ctx.user), BPS_DENOMINATOR);
 
-        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
-        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates, &ctx.buffer);
-        let plan = client.get_plan(&plan_id);
+        let mut config = client.get_config();
+        config.collateral_tiers = SorobanVec::from_array(&ctx.env, [
+            CollateralTier { min_score: 600, ratio_bps: 6000 },
+            CollateralTier { min_score: 600, ratio_bps: 5000 },
+        ]);
+        assert_eq!(client.try_update_config(&config), Err(Ok(ContractError::InvalidConfig)));
 
-        assert_eq!(plan.user, ctx.user);
-        assert_eq!(plan.merchant, ctx.merchant);
-        assert_eq!(plan.total_amount, 3000);
-        assert_eq!(plan.installments.len(), 3);
+        config.collateral_tiers = SorobanVec::from_array(&ctx.env, [CollateralTier { min_score: 600, ratio_bps: 0 }]);
+        assert_eq!(client.try_update_config(&config), Err(Ok(ContractError::InvalidConfig)));
     }
 
+
     #[test]
-    fn test_full_lifecycle() {
+    fn test_merchant_risk_rates() {
         let ctx = TestContext::new();
         let client = ctx.client();
+        let arbiter = Address::generate(&ctx.env);
+        client.grant_role(&Role::Arbiter, &arbiter);
 
-        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
-        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &3000, &3, &due_dates, &ctx.buffer);
+        let plan_id = defaulted_plan(&ctx);
+        ctx.buffer_client().set_shares(&ctx.user, &10000, &800);
+        let due_dates = SorobanVec::from_array(&ctx.env, [5000u64]);
+        let disputed = client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
+        client.create_plan(&ctx.user, &ctx.merchant, &100, &1, &due_dates);
 
-        ctx.advance_time(1500);
-        let source = client.collect_installment(&plan_id, &1, &ctx.buffer, &ctx.merchant);
-        assert_eq!(source.to_u32(), 0);
+        // Freezing twice is one dispute
+        client.freeze_plan(&arbiter, &disputed);
+        client.freeze_plan(&arbiter, &disputed);
+        client.record_refund(&arbiter, &disputed);
+        client.record_refund(&arbiter, &plan_id);
 
-        ctx.advance_time(1000);
-        client.collect_installment(&plan_id, &2, &ctx.buffer, &ctx.merchant);
+        let risk = client.get_merchant_risk(&ctx.merchant);
+        assert_eq!(risk.plans_originated, 4);
+        assert_eq!(risk.incidents, MerchantIncidents { refunds: 2, disputes: 1, defaults: 1 });
+        assert_eq!((risk.refund_rate_bps, risk.dispute_rate_bps, risk.default_rate_bps), (5000, 2500, 2500));
 
-        ctx.advance_time(1000);
-        client.collect_installment(&plan_id, &3, &ctx.buffer, &ctx.merchant);
+        let result = client.try_record_refund(&ctx.user, &disputed);
+        assert_eq!(result, Err(Ok(ContractError::Unauthorized)));
 
-        let final_plan = client.get_plan(&plan_id);
-        assert_eq!(final_plan.status, PlanStatus::Completed);
+        // A second refund of the same plan is rejected and not counted
+        let result = client.try_record_refund(&arbiter, &disputed);
+        assert_eq!(result, Err(Ok(ContractError::AlreadyRefunded)));
+        assert_eq!(client.get_merchant_risk(&ctx.merchant).incidents.refunds, 2);
     }
 
+
     #[test]
-    fn test_ltv_max_allowed() {
+    fn test_liquidated_plan_cannot_be_collected() {
         let ctx = TestContext::new();
         let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+        let liquidator = Address::generate(&ctx.env);
+        auction_token(&ctx, &liquidator, 5000);
+        client.start_auction(&plan_id);
+        client.bid(&plan_id, &liquidator, &5000);
 
-        // Buffer total = 10000, LTV 80% = 8000 maximum allowed
-        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
-        let plan_id = client.create_plan(&ctx.user, &ctx.merchant, &8000, &3, &due_dates, &ctx.buffer);
-        let plan = client.get_plan(&plan_id);
+        // The user refilled the Buffer: nothing may be charged again
+        ctx.buffer_client().set_shares(&ctx.user, &10000, &0);
+        ctx.advance_time(2000);
+        for number in 2..=3 {
+            let result = client.try_collect_installment(&plan_id, &number, &ctx.collector);
+            assert_eq!(result.err(), Some(Ok(ContractError::AlreadyPaid)));
+        }
+        let header = client.get_plan_header(&plan_id);
+        assert!((1..=3).all(|number| !header.is_pending(number)));
+        assert_eq!(client.get_plan(&plan_id).installments.get(1).unwrap().status, InstallmentStatus::Failed);
+    }
 
-        assert_eq!(plan.total_amount, 8000);
-        assert_eq!(plan.status, PlanStatus::Active);
+
+    #[test]
+    fn test_bid_uses_the_debt_left_at_bid_time() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+        let liquidator = Address::generate(&ctx.env);
+        let token = auction_token(&ctx, &liquidator, 5000);
+        client.start_auction(&plan_id);
+
+        // The user repays installments 2 and 3 while the auction runs
+        ctx.buffer_client().set_shares(&ctx.user, &2000, &800);
+        client.collect_installment(&plan_id, &2, &ctx.user);
+        client.collect_installment(&plan_id, &3, &ctx.user);
+        assert_eq!(client.get_user_exposure(&ctx.user), 1000);
+
+        client.bid(&plan_id, &liquidator, &4500);
+        let token_client = TokenClient::new(&ctx.env, &token);
+        assert_eq!(token_client.balance(&ctx.merchant), 1000);
+        assert_eq!(token_client.balance(&ctx.user), 3500);
+        assert_eq!(client.get_user_exposure(&ctx.user), 0);
+        assert_eq!(client.get_merchant_receivables(&ctx.merchant).in_default, 0);
+
+        // A settled auction's plan is liquidated: no second bid
+        let result = client.try_bid(&plan_id, &liquidator, &4500);
+        assert_eq!(result, Err(Ok(ContractError::PlanNotDefaulted)));
     }
 
+
     #[test]
-    #[should_panic(expected = "Status(ContractError(15))")] // ExceedsMaxLTV
-    fn test_ltv_exceeds_maximum() {
+    fn test_bid_seizes_only_what_is_left_locked() {
         let ctx = TestContext::new();
         let client = ctx.client();
+        let plan_id = defaulted_plan(&ctx);
+        let liquidator = Address::generate(&ctx.env);
+        auction_token(&ctx, &liquidator, 5000);
+        ctx.buffer_client().set_shares(&ctx.user, &0, &3000);
+        assert_eq!(client.start_auction(&plan_id).collateral_shares, 3000);
 
-        // Attempt to create plan for 9000 when maximum is 8000 (80% of 10000)
-        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
-        client.create_plan(&ctx.user, &ctx.merchant, &9000, &3, &due_dates, &ctx.buffer);
+        // Installment 2 is paid from the protected balance mid-auction
+        client.collect_installment(&plan_id, &2, &ctx.user);
+        assert_eq!(client.get_plan(&plan_id).protected_shares, 2000);
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).protected_shares, 2000);
+
+        client.bid(&plan_id, &liquidator, &4500);
+        assert_eq!(client.get_auction(&plan_id).collateral_shares, 2000);
+        assert_eq!(client.get_plan(&plan_id).protected_shares, 0);
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).protected_shares, 0);
+    }
+
+
+    /// Defaulted 3000 plan with 1200 insured, auctioned in a token the fund
+    /// holds `fund_balance` of and has approved the bridge for
+    fn insured_auction(ctx: &TestContext, liquidator: &Address, fund_balance: i128) -> (u64, Address) {
+        let client = ctx.client();
+        setup_underwriting(ctx, 10000).set_score(&ctx.user, &750);
+        let plan_id = defaulted_plan(ctx);
+        assert_eq!(client.get_plan(&plan_id).insured_amount, 1200);
+
+        let token = auction_token(ctx, liquidator, 5000);
+        let fund = client.get_underwriting().unwrap().insurance_fund;
+        StellarAssetClient::new(&ctx.env, &token).mint(&fund, &fund_balance);
+        TokenClient::new(&ctx.env, &token).approve(&fund, &ctx.bridge, &2000, &1000);
+
+        client.start_auction(&plan_id);
+        ctx.advance_time(AUCTION_DURATION_SECS * 2);
+        (plan_id, token)
     }
 
     #[test]
-    #[should_panic(expected = "Status(ContractError(15))")] // ExceedsMaxLTV
-    fn test_ltv_at_100_percent_fails() {
+    fn test_insurance_fund_covers_liquidation_shortfall() {
         let ctx = TestContext::new();
         let client = ctx.client();
+        let liquidator = Address::generate(&ctx.env);
+        let (plan_id, token) = insured_auction(&ctx, &liquidator, 2000);
 
-        // Attempting to use 100% of buffer (10000) should fail
-        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000, 4000]);
-        client.create_plan(&ctx.user, &ctx.merchant, &10000, &3, &due_dates, &ctx.buffer);
+        // The floor price of 1500 leaves 1500 unpaid, 1200 of it insured
+        client.bid(&plan_id, &liquidator, &1500);
+        let token_client = TokenClient::new(&ctx.env, &token);
+        assert_eq!(token_client.balance(&ctx.merchant), 2700);
+        assert_eq!(token_client.balance(&client.get_underwriting().unwrap().insurance_fund), 800);
+        assert_eq!(client.get_insured_outstanding(), 0);
+    }
+
+    #[test]
+    fn test_unfunded_insurance_does_not_block_liquidation() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let liquidator = Address::generate(&ctx.env);
+        let (plan_id, token) = insured_auction(&ctx, &liquidator, 500);
+
+        client.bid(&plan_id, &liquidator, &1500);
+        assert_eq!(TokenClient::new(&ctx.env, &token).balance(&ctx.merchant), 2000);
+        assert_eq!(client.get_plan(&plan_id).status, PlanStatus::Liquidated);
+        // The capacity is released even though the fund paid only part
+        assert_eq!(client.get_insured_outstanding(), 0);
+    }
+
+
+    #[test]
+    fn test_cross_margin_does_not_reuse_isolated_locks() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+
+        let due_dates = SorobanVec::from_array(&ctx.env, [2000u64, 3000]);
+        client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).protected_shares, 2000);
+
+        // The isolated plan's 2000 shares don't back the cross-margin plan
+        client.set_cross_margin(&ctx.user, &true);
+        client.create_plan(&ctx.user, &ctx.merchant, &2000, &2, &due_dates);
+        assert_eq!(ctx.buffer_client().get_balance(&ctx.user).protected_shares, 4000);
+        assert_eq!(client.get_cross_locked(&ctx.user), 2000);
+        assert!(client.check_invariants(&ctx.user).locks_balanced);
     }
-}
\ No newline at end of file
+}
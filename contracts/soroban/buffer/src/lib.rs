@@ -70,6 +70,7 @@ pub enum DataKey {
     Balance(Address),
     TotalStats,
     BlendStrategy,
+    BridgeAllowance(Address),
 }
 
 #[contracttype]
@@ -299,7 +300,26 @@ impl BufferContract {
     pub fn lock_shares(env: Env, user: Address, shares: i128) -> LockResult {
         Self::require_bridge(env.clone());
         Self::require_not_paused(&env);
-        
+        Self::lock_internal(&env, &user, shares)
+    }
+
+    // Top up protected shares to `amount` when available covers the gap.
+    // Returns the balance before the lock so the caller can see what was locked.
+    pub fn check_and_lock(env: Env, user: Address, amount: i128) -> BufferBalance {
+        Self::require_bridge(env.clone());
+        Self::require_not_paused(&env);
+
+        let bal = Self::get_balance_or_default(env.clone(), user.clone());
+        let top_up = checked_sub(&env, amount, bal.protected_shares);
+
+        if top_up >= MIN_AMOUNT && top_up <= bal.available_shares {
+            Self::lock_internal(&env, &user, top_up);
+        }
+
+        bal
+    }
+
+    fn lock_internal(env: &Env, user: &Address, shares: i128) -> LockResult {
         if shares < MIN_AMOUNT {
             panic!("Invalid amount");
         }
@@ -310,15 +330,15 @@ impl BufferContract {
             panic!("Insufficient available");
         }
 
-        bal.available_shares = checked_sub(&env, bal.available_shares, shares);
-        bal.protected_shares = checked_add(&env, bal.protected_shares, shares);
-        bal.version = checked_add_u64(&env, bal.version, 1);
+        bal.available_shares = checked_sub(env, bal.available_shares, shares);
+        bal.protected_shares = checked_add(env, bal.protected_shares, shares);
+        bal.version = checked_add_u64(env, bal.version, 1);
 
         env.storage().persistent().set(&DataKey::Balance(user.clone()), &bal);
         
-        Self::update_total_stats(&env, -shares, shares, 0, false);
+        Self::update_total_stats(env, -shares, shares, 0, false);
 
-        env.events().publish((Symbol::new(&env, "lock"), user.clone()), shares);
+        env.events().publish((Symbol::new(env, "lock"), user.clone()), shares);
         
         LockResult {
             shares_locked: shares,
@@ -357,6 +377,27 @@ impl BufferContract {
         }
     }
 
+    pub fn increase_bridge_allowance(env: Env, user: Address, shares: i128) -> i128 {
+        user.require_auth();
+        
+        if shares < MIN_AMOUNT {
+            panic!("Invalid amount");
+        }
+
+        let allowance = checked_add(&env, Self::get_bridge_allowance(env.clone(), user.clone()), shares);
+        env.storage().persistent().set(&DataKey::BridgeAllowance(user.clone()), &allowance);
+        
+        env.events().publish((Symbol::new(&env, "bridge_allowance"), user), allowance);
+        
+        allowance
+    }
+
+    pub fn get_bridge_allowance(env: Env, user: Address) -> i128 {
+        env.storage().persistent()
+            .get(&DataKey::BridgeAllowance(user))
+            .unwrap_or(0)
+    }
+
     pub fn debit_available(
         env: Env,
         user: Address,
@@ -364,6 +405,7 @@ impl BufferContract {
         to: Address
     ) -> WithdrawResult {
         Self::require_bridge(env.clone());
+        Self::spend_bridge_allowance(&env, &user, shares);
         Self::withdraw_internal(env, user, shares, to, false)
     }
 
@@ -374,6 +416,7 @@ impl BufferContract {
         to: Address
     ) -> WithdrawResult {
         Self::require_bridge(env.clone());
+        Self::spend_bridge_allowance(&env, &user, shares);
         Self::withdraw_internal(env, user, shares, to, true)
     }
 
@@ -424,6 +467,12 @@ impl BufferContract {
         }
     }
 
+    pub fn get_asset(env: Env) -> Address {
+        env.storage().instance()
+            .get(&DataKey::Asset)
+            .unwrap_or_else(|| panic!("Asset not configured"))
+    }
+
     pub fn get_total_stats(env: Env) -> TotalStats {
         env.storage().persistent().get(&DataKey::TotalStats)
             .unwrap_or(TotalStats {
@@ -524,6 +573,17 @@ impl BufferContract {
         bridge.require_auth();
     }
 
+    fn spend_bridge_allowance(env: &Env, user: &Address, shares: i128) {
+        let allowance = Self::get_bridge_allowance(env.clone(), user.clone());
+        if allowance < shares {
+            panic!("Insufficient allowance");
+        }
+        env.storage().persistent().set(
+            &DataKey::BridgeAllowance(user.clone()),
+            &checked_sub(env, allowance, shares)
+        );
+    }
+
     fn require_not_paused(env: &Env) {
         let paused: bool = env.storage().instance().get(&DataKey::Paused).unwrap_or(false);
         if paused {
@@ -0,0 +1,451 @@
+#![no_std]
+
+use soroban_sdk::{contract, contractimpl, contracttype, Address, Env, IntoVal, Val, Vec};
+
+// ============ DATA TYPES ============
+
+/// Storage keys of the projections
+#[contracttype]
+#[derive(Clone)]
+pub enum DataKey {
+    Bridge,                  // Bridge contract allowed to push updates
+    Stats,                   // Protocol-wide counters
+    MerchantVolume(Address), // Amount collected on behalf of a merchant
+    Leaderboard,             // Merchants with the highest collected volume
+    DelinquentPlans(u32),    // Bucket of the plans with at least one failed installment
+    DelinquentCount,         // Number of delinquent plans
+    DelinquentSlot(u64),     // Position of a delinquent plan in the list
+}
+
+/// Plan transition, as recorded in the bridge's audit trail
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PlanAction {
+    Created,    // Plan created by the user
+    Approved,   // Large plan co-approved by a risk officer
+    Collected,  // Installment collected
+    Failed,     // Installment declared failed
+    Defaulted,  // Plan went into default
+    Completed,  // Every installment paid
+    Liquidated, // Collateral auction settled
+    Archived,   // Installments pruned after the retention period
+}
+
+/// Plan transition pushed by the bridge
+/// 
+/// Mirrors the bridge's `PlanUpdate` type (same field names and order).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlanUpdate {
+    pub plan_id: u64,
+    pub action: PlanAction,
+    pub installment_number: u32,     // 0 for plan-level actions
+    pub user: Address,
+    pub merchant: Address,
+    pub amount: i128,                // Plan total on Created, installment amount on Collected/Failed
+    pub timestamp: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    pub plans_created: u64,
+    pub installments_collected: u64,
+    pub installments_failed: u64,
+    pub plans_completed: u64,
+    pub plans_defaulted: u64,
+    pub plans_liquidated: u64,
+    pub volume_originated: i128,
+    pub volume_collected: i128,
+    pub last_update: u64,            // Timestamp of the latest applied update
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LeaderEntry {
+    pub account: Address,
+    pub volume: i128,
+}
+
+// ============ CONSTANTS ============
+
+/// Number of merchants kept in the leaderboard
+const LEADERBOARD_SIZE: u32 = 10;
+
+/// Max entries returned by a paginated query
+const MAX_PAGE_SIZE: u32 = 50;
+
+/// Plan IDs per bucket of the delinquent list
+const BUCKET_SIZE: u32 = 50;
+
+/// Ledgers per day at the ~5 second close time
+const LEDGERS_PER_DAY: u32 = 17280;
+
+/// Remaining TTL below which written entries are extended (30 days)
+const TTL_THRESHOLD: u32 = 30 * LEDGERS_PER_DAY;
+
+/// TTL that written entries are extended to (180 days)
+const TTL_EXTEND_TO: u32 = 180 * LEDGERS_PER_DAY;
+
+// ============ CONTRACT ============
+
+#[contract]
+pub struct ReadModelContract;
+
+#[contractimpl]
+impl ReadModelContract {
+    
+    /// Bind the projections to the bridge that feeds them
+    pub fn __constructor(env: Env, bridge: Address) {
+        env.storage().instance().set(&DataKey::Bridge, &bridge);
+    }
+    
+    /// Apply one plan transition (bridge only)
+    /// 
+    /// Called by the bridge after every transition it records; the read
+    /// model only derives state from these updates.
+    pub fn on_plan_update(env: Env, update: PlanUpdate) {
+        let bridge = Self::get_bridge(env.clone());
+        bridge.require_auth();
+        
+        let mut stats = Self::get_stats(env.clone());
+        stats.last_update = update.timestamp;
+        
+        match update.action {
+            PlanAction::Created => {
+                stats.plans_created += 1;
+                stats.volume_originated += update.amount;
+            }
+            PlanAction::Collected => {
+                stats.installments_collected += 1;
+                stats.volume_collected += update.amount;
+                Self::add_merchant_volume(&env, &update.merchant, update.amount);
+            }
+            PlanAction::Failed => {
+                stats.installments_failed += 1;
+                Self::mark_delinquent(&env, update.plan_id);
+            }
+            PlanAction::Defaulted => stats.plans_defaulted += 1,
+            PlanAction::Completed => {
+                stats.plans_completed += 1;
+                Self::clear_delinquent(&env, update.plan_id);
+            }
+            PlanAction::Liquidated => {
+                stats.plans_liquidated += 1;
+                Self::clear_delinquent(&env, update.plan_id);
+            }
+            PlanAction::Archived => Self::clear_delinquent(&env, update.plan_id),
+            PlanAction::Approved => {}
+        }
+        
+        Self::store(&env, &DataKey::Stats, &stats);
+        env.storage().instance().extend_ttl(TTL_THRESHOLD, TTL_EXTEND_TO);
+    }
+    
+    // ============ QUERIES ============
+    
+    /// Bridge contract feeding the projections
+    pub fn get_bridge(env: Env) -> Address {
+        env.storage().instance().get(&DataKey::Bridge).unwrap()
+    }
+    
+    /// Protocol-wide counters
+    pub fn get_stats(env: Env) -> Stats {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Stats)
+            .unwrap_or_default()
+    }
+    
+    /// Amount collected on behalf of a merchant
+    pub fn get_merchant_volume(env: Env, merchant: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::MerchantVolume(merchant))
+            .unwrap_or(0)
+    }
+    
+    /// Merchants with the highest collected volume, highest first
+    pub fn get_leaderboard(env: Env) -> Vec<LeaderEntry> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Leaderboard)
+            .unwrap_or(Vec::new(&env))
+    }
+    
+    /// Page of the plans with a failed installment
+    /// 
+    /// Plans are listed in the order they first failed, except that a plan
+    /// leaving the list (completed, liquidated or archived) hands its
+    /// position to the last one. Only the buckets covering the page (at
+    /// most two) are read.
+    pub fn get_delinquent_plans(env: Env, cursor: u32, limit: u32) -> Vec<u64> {
+        let end = cursor
+            .saturating_add(limit.min(MAX_PAGE_SIZE))
+            .min(Self::get_delinquent_count(env.clone()));
+        
+        let mut page = Vec::new(&env);
+        let mut index = cursor;
+        while index < end {
+            let bucket_start = index - index % BUCKET_SIZE;
+            let plans = Self::delinquent_bucket(&env, index / BUCKET_SIZE);
+            let to = (end - bucket_start).min(plans.len());
+            page.append(&plans.slice(index - bucket_start..to));
+            index = bucket_start + BUCKET_SIZE;
+        }
+        page
+    }
+    
+    /// Number of plans with a failed installment
+    pub fn get_delinquent_count(env: Env) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::DelinquentCount)
+            .unwrap_or(0)
+    }
+}
+
+// ============ INTERNAL HELPERS ============
+
+impl ReadModelContract {
+    fn add_merchant_volume(env: &Env, merchant: &Address, amount: i128) {
+        let volume = Self::get_merchant_volume(env.clone(), merchant.clone()) + amount;
+        Self::store(env, &DataKey::MerchantVolume(merchant.clone()), &volume);
+        
+        // Re-insert the merchant at its rank, keeping the board sorted
+        let mut board = Self::get_leaderboard(env.clone());
+        if let Some(i) = board.iter().position(|entry| entry.account == *merchant) {
+            board.remove(i as u32);
+        }
+        let rank = board.iter()
+            .position(|entry| entry.volume < volume)
+            .map_or(board.len(), |i| i as u32);
+        if rank < LEADERBOARD_SIZE {
+            board.insert(rank, LeaderEntry { account: merchant.clone(), volume });
+            if board.len() > LEADERBOARD_SIZE {
+                board.pop_back();
+            }
+        }
+        Self::store(env, &DataKey::Leaderboard, &board);
+    }
+    
+    fn delinquent_bucket(env: &Env, bucket: u32) -> Vec<u64> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::DelinquentPlans(bucket))
+            .unwrap_or(Vec::new(env))
+    }
+    
+    /// Append a plan to the head bucket of the delinquent list
+    /// 
+    /// The plan's position is kept so the plan is found without a scan.
+    fn mark_delinquent(env: &Env, plan_id: u64) {
+        if env.storage().persistent().has(&DataKey::DelinquentSlot(plan_id)) {
+            return;
+        }
+        
+        let count = Self::get_delinquent_count(env.clone());
+        let key = DataKey::DelinquentPlans(count / BUCKET_SIZE);
+        let mut plans = Self::delinquent_bucket(env, count / BUCKET_SIZE);
+        plans.push_back(plan_id);
+        Self::store(env, &key, &plans);
+        Self::store(env, &DataKey::DelinquentCount, &(count + 1));
+        Self::store(env, &DataKey::DelinquentSlot(plan_id), &count);
+    }
+    
+    /// Drop a plan from the delinquent list, moving the last plan of the
+    /// list into its position
+    /// 
+    /// Reads and rewrites at most two buckets.
+    fn clear_delinquent(env: &Env, plan_id: u64) {
+        let slot: u32 = match env.storage().persistent().get(&DataKey::DelinquentSlot(plan_id)) {
+            Some(slot) => slot,
+            None => return,
+        };
+        env.storage().persistent().remove(&DataKey::DelinquentSlot(plan_id));
+        
+        let last = Self::get_delinquent_count(env.clone()) - 1;
+        let last_key = DataKey::DelinquentPlans(last / BUCKET_SIZE);
+        let mut last_plans = Self::delinquent_bucket(env, last / BUCKET_SIZE);
+        let moved = last_plans.pop_back().unwrap_or(plan_id);
+        
+        if moved != plan_id {
+            if slot / BUCKET_SIZE == last / BUCKET_SIZE {
+                last_plans.set(slot % BUCKET_SIZE, moved);
+            } else {
+                let key = DataKey::DelinquentPlans(slot / BUCKET_SIZE);
+                let mut plans = Self::delinquent_bucket(env, slot / BUCKET_SIZE);
+                plans.set(slot % BUCKET_SIZE, moved);
+                Self::store(env, &key, &plans);
+            }
+            Self::store(env, &DataKey::DelinquentSlot(moved), &slot);
+        }
+        
+        if last_plans.is_empty() {
+            env.storage().persistent().remove(&last_key);
+        } else {
+            Self::store(env, &last_key, &last_plans);
+        }
+        Self::store(env, &DataKey::DelinquentCount, &last);
+    }
+    
+    /// Write a persistent entry and extend its TTL
+    fn store<V: IntoVal<Env, Val>>(env: &Env, key: &DataKey, value: &V) {
+        env.storage().persistent().set(key, value);
+        env.storage().persistent().extend_ttl(key, TTL_THRESHOLD, TTL_EXTEND_TO);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use soroban_sdk::testutils::{storage::{Instance as _, Persistent as _}, Address as _};
+
+    struct TestContext {
+        env: Env,
+        bridge: Address,
+        read_model: Address,
+    }
+
+    impl TestContext {
+        fn new() -> Self {
+            let env = Env::default();
+            env.mock_all_auths();
+            let bridge = Address::generate(&env);
+            let read_model = env.register(ReadModelContract, (bridge.clone(),));
+            TestContext { env, bridge, read_model }
+        }
+
+        fn client(&self) -> ReadModelContractClient<'_> {
+            ReadModelContractClient::new(&self.env, &self.read_model)
+        }
+
+        fn push(&self, plan_id: u64, action: PlanAction, merchant: &Address, amount: i128) {
+            self.client().on_plan_update(&PlanUpdate {
+                plan_id,
+                action,
+                installment_number: 0,
+                user: Address::generate(&self.env),
+                merchant: merchant.clone(),
+                amount,
+                timestamp: 1000,
+            });
+        }
+    }
+
+    #[test]
+    fn test_updates_feed_stats_and_leaderboard() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        assert_eq!(client.get_bridge(), ctx.bridge);
+
+        let small = Address::generate(&ctx.env);
+        let large = Address::generate(&ctx.env);
+        ctx.push(0, PlanAction::Created, &small, 3000);
+        ctx.push(1, PlanAction::Created, &large, 9000);
+        ctx.push(0, PlanAction::Collected, &small, 1000);
+        ctx.push(1, PlanAction::Collected, &large, 3000);
+        ctx.push(0, PlanAction::Collected, &small, 2500);
+
+        let stats = client.get_stats();
+        assert_eq!(stats.plans_created, 2);
+        assert_eq!(stats.installments_collected, 3);
+        assert_eq!(stats.volume_originated, 12000);
+        assert_eq!(stats.volume_collected, 6500);
+
+        // The merchant overtaking another moves up without duplicating its entry
+        let board = client.get_leaderboard();
+        assert_eq!(board.len(), 2);
+        assert_eq!(board.get(0).unwrap(), LeaderEntry { account: small.clone(), volume: 3500 });
+        assert_eq!(board.get(1).unwrap(), LeaderEntry { account: large, volume: 3000 });
+        assert_eq!(client.get_merchant_volume(&small), 3500);
+    }
+
+    #[test]
+    fn test_delinquent_plans_track_failures_until_closed() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let merchant = Address::generate(&ctx.env);
+
+        ctx.push(0, PlanAction::Failed, &merchant, 1000);
+        ctx.push(0, PlanAction::Failed, &merchant, 1000);
+        ctx.push(1, PlanAction::Failed, &merchant, 1000);
+        ctx.push(2, PlanAction::Failed, &merchant, 1000);
+        assert_eq!(client.get_delinquent_count(), 3);
+        assert_eq!(client.get_delinquent_plans(&1, &10), Vec::from_array(&ctx.env, [1u64, 2]));
+
+        ctx.push(1, PlanAction::Liquidated, &merchant, 0);
+        ctx.push(0, PlanAction::Archived, &merchant, 0);
+        assert_eq!(client.get_delinquent_plans(&0, &10), Vec::from_array(&ctx.env, [2u64]));
+        assert_eq!(client.get_stats().installments_failed, 4);
+    }
+
+    #[test]
+    fn test_delinquent_plans_fill_freed_positions() {
+        let ctx = TestContext::new();
+        let client = ctx.client();
+        let merchant = Address::generate(&ctx.env);
+
+        for plan_id in 0..60u64 {
+            ctx.push(plan_id, PlanAction::Failed, &merchant, 1000);
+        }
+        assert_eq!(client.get_delinquent_count(), 60);
+        assert_eq!(client.get_delinquent_plans(&48, &4), Vec::from_array(&ctx.env, [48u64, 49, 50, 51]));
+
+        // The last plan moves into the freed position
+        ctx.push(3, PlanAction::Completed, &merchant, 0);
+        assert_eq!(client.get_delinquent_count(), 59);
+        assert_eq!(client.get_delinquent_plans(&2, &3), Vec::from_array(&ctx.env, [2u64, 59, 4]));
+
+        ctx.push(59, PlanAction::Archived, &merchant, 0);
+        ctx.push(58, PlanAction::Liquidated, &merchant, 0);
+        assert_eq!(client.get_delinquent_plans(&2, &3), Vec::from_array(&ctx.env, [2u64, 57, 4]));
+        assert_eq!(client.get_delinquent_plans(&55, &10), Vec::from_array(&ctx.env, [55u64, 56]));
+
+        // A plan listed again isn't duplicated
+        ctx.push(57, PlanAction::Failed, &merchant, 1000);
+        assert_eq!(client.get_delinquent_count(), 57);
+    }
+
+    #[test]
+    fn test_writes_extend_ttl() {
+        let ctx = TestContext::new();
+        let merchant = Address::generate(&ctx.env);
+        ctx.push(0, PlanAction::Collected, &merchant, 1000);
+        ctx.push(0, PlanAction::Failed, &merchant, 1000);
+
+        ctx.env.as_contract(&ctx.read_model, || {
+            let store = ctx.env.storage();
+            for key in [
+                DataKey::Stats,
+                DataKey::MerchantVolume(merchant.clone()),
+                DataKey::Leaderboard,
+                DataKey::DelinquentPlans(0),
+                DataKey::DelinquentCount,
+                DataKey::DelinquentSlot(0),
+            ] {
+                assert_eq!(store.persistent().get_ttl(&key), TTL_EXTEND_TO);
+            }
+            assert_eq!(store.instance().get_ttl(), TTL_EXTEND_TO);
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_only_bridge_can_push_updates() {
+        let env = Env::default();
+        let bridge = Address::generate(&env);
+        let read_model = env.register(ReadModelContract, (bridge,));
+        let client = ReadModelContractClient::new(&env, &read_model);
+
+        client.on_plan_update(&PlanUpdate {
+            plan_id: 0,
+            action: PlanAction::Created,
+            installment_number: 0,
+            user: Address::generate(&env),
+            merchant: Address::generate(&env),
+            amount: 1000,
+            timestamp: 1000,
+        });
+    }
+}
@@ -0,0 +1,309 @@
+//! Access to the bridge contract through the Stellar CLI
+//!
+//! The CLI handles RPC, simulation, signing and submission, so the worker
+//! only shells out and parses its JSON output.
+
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::error::{contract_code, Error};
+use crate::xdr::{self, EventValue, ScVal};
+
+/// Pending installment, as returned by `get_due_between`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DueInstallment {
+    #[serde(deserialize_with = "int")]
+    pub plan_id: u64,
+    #[serde(deserialize_with = "int")]
+    pub installment_number: u32,
+    #[serde(deserialize_with = "int")]
+    pub amount: i128,
+    #[serde(deserialize_with = "int")]
+    pub due_date: u64,
+}
+
+/// Bridge event, reduced to what the worker acts on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeEvent {
+    pub id: String,                      // RPC paging token
+    pub name: String,                    // e.g. `inst_paid`, `plan_new`, `paused`
+    pub seq: u64,                        // Global bridge event sequence
+    pub plan_id: Option<u64>,            // Plan events only
+    pub installment_number: Option<u32>, // Installment events only
+}
+
+/// Where to read events from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventCursor {
+    Ledger(u32),
+    After(String), // Paging token of the last event read
+}
+
+pub trait Chain {
+    /// Ledger to start reading events from when no ledger is configured
+    fn latest_ledger(&self) -> Result<u32, Error>;
+
+    /// Bridge events after `cursor`, oldest first
+    fn events(&self, cursor: &EventCursor) -> Result<Vec<BridgeEvent>, Error>;
+
+    /// One page of `get_due_between`
+    fn due_between(&self, from_ts: u64, to_ts: u64, cursor: u32, limit: u32) -> Result<Vec<DueInstallment>, Error>;
+
+    /// Call `collect_installment`; with `send` false it is only simulated
+    ///
+    /// Returns the CLI output, i.e. the payment source used.
+    fn collect(&self, plan_id: u64, installment_number: u32, send: bool) -> Result<String, Error>;
+}
+
+pub struct StellarCli {
+    config: Config,
+}
+
+impl StellarCli {
+    pub fn new(config: Config) -> StellarCli {
+        StellarCli { config }
+    }
+
+    fn run(&self, args: &[String]) -> Result<String, Error> {
+        let output = Command::new(&self.config.stellar_bin)
+            .args(args)
+            .output()
+            .map_err(|e| Error::Cli(format!("can't run {}: {e}", self.config.stellar_bin)))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        if output.status.success() {
+            return Ok(stdout);
+        }
+        match contract_code(&stderr) {
+            Some(code) => Err(Error::Contract(code)),
+            None => Err(Error::Cli(stderr.trim().to_string())),
+        }
+    }
+
+    fn invoke(&self, send: bool, function: &str, params: &[(&str, String)]) -> Result<String, Error> {
+        let mut args: Vec<String> = [
+            "contract",
+            "invoke",
+            "--id",
+            &self.config.bridge_id,
+            "--source",
+            &self.config.source,
+            "--network",
+            &self.config.network,
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        args.push(if send { "--send=yes" } else { "--send=no" }.to_string());
+        args.push("--".to_string());
+        args.push(function.to_string());
+        for (name, value) in params {
+            args.push(format!("--{name}"));
+            args.push(value.clone());
+        }
+        self.run(&args)
+    }
+}
+
+impl Chain for StellarCli {
+    fn latest_ledger(&self) -> Result<u32, Error> {
+        let args = ["ledger", "latest", "--network", &self.config.network, "--output", "json"];
+        let output = self.run(&args.map(String::from))?;
+        let latest: serde_json::Value =
+            serde_json::from_str(&output).map_err(|e| Error::Decode(format!("ledger latest: {e}")))?;
+        latest["sequence"]
+            .as_u64()
+            .map(|sequence| sequence as u32)
+            .ok_or_else(|| Error::Decode("ledger latest: no sequence".to_string()))
+    }
+
+    fn events(&self, cursor: &EventCursor) -> Result<Vec<BridgeEvent>, Error> {
+        let mut args: Vec<String> = ["events", "--network", &self.config.network, "--id", &self.config.bridge_id]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        match cursor {
+            EventCursor::Ledger(ledger) => args.extend(["--start-ledger".to_string(), ledger.to_string()]),
+            EventCursor::After(token) => args.extend(["--cursor".to_string(), token.clone()]),
+        }
+        args.extend(["--count", "200", "--output", "json"].map(String::from));
+
+        parse_events(&self.run(&args)?)
+    }
+
+    fn due_between(&self, from_ts: u64, to_ts: u64, cursor: u32, limit: u32) -> Result<Vec<DueInstallment>, Error> {
+        let output = self.invoke(
+            false,
+            "get_due_between",
+            &[
+                ("from_ts", from_ts.to_string()),
+                ("to_ts", to_ts.to_string()),
+                ("cursor", cursor.to_string()),
+                ("limit", limit.to_string()),
+            ],
+        )?;
+        serde_json::from_str(output.trim()).map_err(|e| Error::Decode(format!("get_due_between: {e}")))
+    }
+
+    fn collect(&self, plan_id: u64, installment_number: u32, send: bool) -> Result<String, Error> {
+        let output = self.invoke(
+            send,
+            "collect_installment",
+            &[
+                ("plan_id", plan_id.to_string()),
+                ("installment_number", installment_number.to_string()),
+                ("caller", self.config.collector.clone()),
+            ],
+        )?;
+        Ok(output.trim().to_string())
+    }
+}
+
+/// RPC event as printed by `stellar events --output json`
+#[derive(Deserialize)]
+struct RpcEvent {
+    id: String,
+    topic: Vec<String>,
+    value: String,
+}
+
+/// Parse the CLI's stream of JSON events, keeping one copy of each
+///
+/// Plan events are read from their `v2` copy, whose data is `(seq,
+/// payload)`; other events carry `seq` as data. The `v1` and unversioned
+/// `(name, plan_id)` copies of plan events are skipped. Any other shape
+/// is a `Decode` error rather than a dropped event: the bridge changed its
+/// events and the worker needs updating.
+pub fn parse_events(output: &str) -> Result<Vec<BridgeEvent>, Error> {
+    let mut events = Vec::new();
+    for item in serde_json::Deserializer::from_str(output).into_iter::<RpcEvent>() {
+        let raw = item.map_err(|e| Error::Decode(format!("event: {e}")))?;
+        let topics = raw
+            .topic
+            .iter()
+            .map(|topic| xdr::decode(topic))
+            .collect::<Result<Vec<_>, _>>()?;
+        let value = xdr::decode(&raw.value)?;
+
+        let head = topics.first().and_then(EventValue::as_symbol);
+        let event = match (head, &value) {
+            (Some("v2"), ScVal::Vec(Some(data))) if data.len() == 2 => {
+                let payload = &data[1];
+                BridgeEvent {
+                    id: raw.id,
+                    name: topics.get(1).and_then(EventValue::as_symbol).unwrap_or_default().to_string(),
+                    seq: data[0].as_u64().ok_or_else(|| Error::Decode("event seq".to_string()))?,
+                    plan_id: payload.field("plan_id").and_then(EventValue::as_u64),
+                    installment_number: payload
+                        .field("installment_number")
+                        .and_then(EventValue::as_u64)
+                        .map(|number| number as u32),
+                }
+            }
+            (Some("v1"), _) => continue,
+            (Some(name), ScVal::U64(seq)) => BridgeEvent {
+                id: raw.id,
+                name: name.to_string(),
+                seq: *seq,
+                plan_id: None,
+                installment_number: None,
+            },
+            (Some(_), _) if topics.len() == 2 && topics[1].as_u64().is_some() => continue,
+            _ => return Err(Error::Decode(format!("event {}: unexpected topics or data", raw.id))),
+        };
+        events.push(event);
+    }
+    Ok(events)
+}
+
+/// Integer printed by the CLI either as a JSON number or, for 128-bit
+/// values, as a string
+fn int<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: std::str::FromStr,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    let text = match &value {
+        serde_json::Value::Number(number) => number.to_string(),
+        serde_json::Value::String(text) => text.clone(),
+        other => return Err(serde::de::Error::custom(format!("expected an integer, got {other}"))),
+    };
+    text.parse()
+        .map_err(|_| serde::de::Error::custom(format!("integer out of range: {text}")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::xdr::test::{account, encode, map, symbol, vec};
+
+    fn rpc_event(id: &str, topics: &[ScVal], value: &ScVal) -> String {
+        let topics: Vec<String> = topics.iter().map(encode).collect();
+        serde_json::json!({
+            "type": "contract",
+            "ledger": 1200,
+            "id": id,
+            "topic": topics,
+            "value": encode(value),
+            "inSuccessfulContractCall": true,
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_parse_events_keeps_one_copy_of_each() {
+        let payload = map(vec![
+            (symbol("installment_number"), ScVal::U32(1)),
+            (symbol("plan_id"), ScVal::U64(3)),
+        ]);
+        let output = [
+            rpc_event(
+                "1-1",
+                &[symbol("v2"), symbol("inst_paid"), account(1), account(2)],
+                &vec(vec![ScVal::U64(10), payload.clone()]),
+            ),
+            rpc_event("1-2", &[symbol("v1"), symbol("inst_paid"), ScVal::U64(3), ScVal::U64(10)], &payload),
+            rpc_event("1-3", &[symbol("inst_paid"), ScVal::U64(3)], &payload),
+            rpc_event("1-4", &[symbol("paused")], &ScVal::U64(11)),
+        ]
+        .join("\n");
+
+        let events = parse_events(&output).unwrap();
+        assert_eq!(events, vec![
+            BridgeEvent {
+                id: "1-1".to_string(),
+                name: "inst_paid".to_string(),
+                seq: 10,
+                plan_id: Some(3),
+                installment_number: Some(1),
+            },
+            BridgeEvent {
+                id: "1-4".to_string(),
+                name: "paused".to_string(),
+                seq: 11,
+                plan_id: None,
+                installment_number: None,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_events_rejects_unknown_shapes() {
+        let output = rpc_event("2-1", &[symbol("inst_paid"), symbol("new")], &ScVal::Void);
+        assert_eq!(
+            parse_events(&output),
+            Err(Error::Decode("event 2-1: unexpected topics or data".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_due_installments_accept_string_amounts() {
+        let output = r#"[{"amount":"1000","due_date":2000,"installment_number":1,"plan_id":4}]"#;
+        let due: Vec<DueInstallment> = serde_json::from_str(output).unwrap();
+        assert_eq!(due, vec![DueInstallment { plan_id: 4, installment_number: 1, amount: 1000, due_date: 2000 }]);
+    }
+}
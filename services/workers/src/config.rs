@@ -0,0 +1,187 @@
+use crate::error::Error;
+
+pub const USAGE: &str = "\
+Usage: redi-worker [options]
+
+Options (environment fallback in brackets):
+  --network <name>         Stellar CLI network              [REDI_NETWORK]
+  --bridge <contract id>   Bridge contract                  [REDI_BRIDGE_ID]
+  --source <identity>      Stellar CLI identity that signs  [REDI_SOURCE]
+  --collector <address>    Collector passed as `caller`     [REDI_COLLECTOR]
+  --stellar-bin <path>     Stellar CLI binary (stellar)     [REDI_STELLAR_BIN]
+  --start-ledger <n>       First ledger to read events from [REDI_START_LEDGER]
+  --poll-secs <n>          Delay between ticks (30)
+  --lookahead-secs <n>     Track installments due this far ahead (86400)
+  --resync-secs <n>        Full reload of the due index (3600)
+  --max-attempts <n>       Attempts before giving up on an installment (8)
+  --backoff-secs <n>       First retry delay, doubled per attempt (60)
+  --max-backoff-secs <n>   Longest retry delay (3600)
+  --dry-run                Simulate collections without submitting them
+  --once                   Run a single tick and exit
+";
+
+/// Worker settings, from flags with environment fallbacks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub network: String,
+    pub bridge_id: String,
+    pub source: String,
+    pub collector: String,       // Address the bridge checks against its collectors
+    pub stellar_bin: String,
+    pub start_ledger: Option<u32>, // None = start from the latest ledger
+    pub poll_secs: u64,
+    pub lookahead_secs: u64,
+    pub resync_secs: u64,
+    pub max_attempts: u32,
+    pub backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    pub dry_run: bool,
+    pub once: bool,
+}
+
+impl Config {
+    /// Parse `args` (without the program name), falling back to `env`
+    pub fn parse<I, E>(args: I, env: E) -> Result<Config, Error>
+    where
+        I: IntoIterator<Item = String>,
+        E: Fn(&str) -> Option<String>,
+    {
+        let mut flags: Vec<(String, String)> = Vec::new();
+        let mut dry_run = false;
+        let mut once = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--dry-run" => dry_run = true,
+                "--once" => once = true,
+                "--help" | "-h" => return Err(Error::Config(USAGE.to_string())),
+                flag if flag.starts_with("--") => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| Error::Config(format!("{flag} needs a value")))?;
+                    flags.push((flag.to_string(), value));
+                }
+                other => return Err(Error::Config(format!("unexpected argument {other}"))),
+            }
+        }
+
+        let lookup = |flag: &str, var: Option<&str>| -> Option<String> {
+            flags
+                .iter()
+                .rev()
+                .find(|(name, _)| name == flag)
+                .map(|(_, value)| value.clone())
+                .or_else(|| var.and_then(&env))
+        };
+        let required = |flag: &str, var: &str| {
+            lookup(flag, Some(var)).ok_or_else(|| Error::Config(format!("{flag} (or {var}) is required")))
+        };
+        let number = |flag: &str, default: u64| -> Result<u64, Error> {
+            match lookup(flag, None) {
+                Some(value) => value
+                    .parse()
+                    .map_err(|_| Error::Config(format!("{flag} must be a number, got {value}"))),
+                None => Ok(default),
+            }
+        };
+
+        for (flag, _) in &flags {
+            if !KNOWN_FLAGS.contains(&flag.as_str()) {
+                return Err(Error::Config(format!("unknown option {flag}")));
+            }
+        }
+
+        let start_ledger = match lookup("--start-ledger", Some("REDI_START_LEDGER")) {
+            Some(value) => Some(
+                value
+                    .parse()
+                    .map_err(|_| Error::Config(format!("--start-ledger must be a number, got {value}")))?,
+            ),
+            None => None,
+        };
+
+        let config = Config {
+            network: required("--network", "REDI_NETWORK")?,
+            bridge_id: required("--bridge", "REDI_BRIDGE_ID")?,
+            source: required("--source", "REDI_SOURCE")?,
+            collector: required("--collector", "REDI_COLLECTOR")?,
+            stellar_bin: lookup("--stellar-bin", Some("REDI_STELLAR_BIN")).unwrap_or_else(|| "stellar".to_string()),
+            start_ledger,
+            poll_secs: number("--poll-secs", 30)?,
+            lookahead_secs: number("--lookahead-secs", 24 * 60 * 60)?,
+            resync_secs: number("--resync-secs", 60 * 60)?,
+            max_attempts: number("--max-attempts", 8)? as u32,
+            backoff_secs: number("--backoff-secs", 60)?,
+            max_backoff_secs: number("--max-backoff-secs", 60 * 60)?,
+            dry_run,
+            once,
+        };
+
+        if config.max_attempts == 0 || config.backoff_secs > config.max_backoff_secs {
+            return Err(Error::Config(
+                "--max-attempts must be positive and --backoff-secs at most --max-backoff-secs".to_string(),
+            ));
+        }
+        Ok(config)
+    }
+}
+
+const KNOWN_FLAGS: [&str; 12] = [
+    "--network",
+    "--bridge",
+    "--source",
+    "--collector",
+    "--stellar-bin",
+    "--start-ledger",
+    "--poll-secs",
+    "--lookahead-secs",
+    "--resync-secs",
+    "--max-attempts",
+    "--backoff-secs",
+    "--max-backoff-secs",
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_flags_override_environment() {
+        let env = |var: &str| match var {
+            "REDI_NETWORK" => Some("testnet".to_string()),
+            "REDI_BRIDGE_ID" => Some("CBRIDGE".to_string()),
+            "REDI_SOURCE" => Some("worker".to_string()),
+            "REDI_COLLECTOR" => Some("GCOLLECTOR".to_string()),
+            _ => None,
+        };
+        let config = Config::parse(args(&["--network", "mainnet", "--poll-secs", "5", "--dry-run"]), env).unwrap();
+
+        assert_eq!(config.network, "mainnet");
+        assert_eq!(config.bridge_id, "CBRIDGE");
+        assert_eq!(config.poll_secs, 5);
+        assert_eq!(config.max_attempts, 8);
+        assert_eq!(config.stellar_bin, "stellar");
+        assert!(config.dry_run && !config.once);
+    }
+
+    #[test]
+    fn test_missing_or_malformed_settings_are_rejected() {
+        let none = |_: &str| None;
+        assert!(Config::parse(args(&["--network", "testnet"]), none).is_err());
+
+        let base = ["--network", "t", "--bridge", "C", "--source", "s", "--collector", "G"];
+        let mut bad = args(&base);
+        bad.extend(args(&["--poll-secs", "soon"]));
+        assert!(Config::parse(bad, none).is_err());
+
+        let mut unknown = args(&base);
+        unknown.extend(args(&["--colector", "G"]));
+        assert!(Config::parse(unknown, none).is_err());
+        assert!(Config::parse(args(&base), none).is_ok());
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+
+/// Bridge error codes the worker reacts to (see the bridge README)
+pub mod code {
+    pub const PLAN_NOT_FOUND: u32 = 7;
+    pub const INSTALLMENT_NOT_FOUND: u32 = 8;
+    pub const ALREADY_PAID: u32 = 9;
+    pub const NOT_COLLECTOR: u32 = 35;
+    pub const UNAUTHORIZED: u32 = 41;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Config(String),   // Missing or malformed setting
+    Cli(String),      // Stellar CLI failed to run, or the RPC call failed
+    Contract(u32),    // Bridge rejected the call with this error code
+    Decode(String),   // Unexpected CLI output or event payload
+}
+
+impl Error {
+    /// Whether retrying can't help: the installment is gone or already settled
+    pub fn is_settled(&self) -> bool {
+        matches!(
+            self,
+            Error::Contract(code::PLAN_NOT_FOUND | code::INSTALLMENT_NOT_FOUND | code::ALREADY_PAID)
+        )
+    }
+
+    /// Whether the worker itself is misconfigured and should stop
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Error::Config(_) | Error::Contract(code::NOT_COLLECTOR | code::UNAUTHORIZED)
+        )
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(msg) => write!(f, "config: {msg}"),
+            Error::Cli(msg) => write!(f, "stellar cli: {msg}"),
+            Error::Contract(code) => write!(f, "bridge error #{code}"),
+            Error::Decode(msg) => write!(f, "decode: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Contract error code in CLI output, e.g. `Error(Contract, #11)`
+pub fn contract_code(output: &str) -> Option<u32> {
+    let start = output.find("Error(Contract, #")? + "Error(Contract, #".len();
+    let digits: String = output[start..].chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_contract_code_from_cli_output() {
+        let stderr = "error: transaction simulation failed: HostError: Error(Contract, #11)\n";
+        assert_eq!(contract_code(stderr), Some(11));
+        assert_eq!(contract_code("error: network unreachable"), None);
+
+        assert!(Error::Contract(code::ALREADY_PAID).is_settled());
+        assert!(Error::Contract(code::NOT_COLLECTOR).is_fatal());
+        assert!(!Error::Contract(11).is_settled() && !Error::Contract(11).is_fatal());
+    }
+}
@@ -0,0 +1,64 @@
+//! Off-chain collection worker for the Redi bridge contract
+//!
+//! Follows the bridge events over RPC, tracks the due installments from
+//! `get_due_between` and submits `collect_installment` as a registered
+//! collector, retrying failed collections with exponential backoff.
+
+mod chain;
+mod config;
+mod error;
+mod schedule;
+mod worker;
+mod xdr;
+
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chain::StellarCli;
+use config::Config;
+use error::Error;
+use worker::{log, Worker};
+
+fn main() -> ExitCode {
+    let config = match Config::parse(std::env::args().skip(1), |var| std::env::var(var).ok()) {
+        Ok(config) => config,
+        Err(Error::Config(message)) => {
+            eprintln!("{message}");
+            return ExitCode::from(2);
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(2);
+        }
+    };
+
+    if config.dry_run {
+        log("dry run: collections are simulated, not submitted");
+    }
+    let poll = Duration::from_secs(config.poll_secs);
+    let once = config.once;
+    let mut worker = Worker::new(StellarCli::new(config.clone()), config);
+
+    loop {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        match worker.tick(now) {
+            Ok(report) => log(&format!(
+                "tick: {} events, {} tracked, {} collected, {} retried, {} given up",
+                report.events,
+                worker.tracked(),
+                report.collected,
+                report.retried,
+                report.gave_up
+            )),
+            Err(e) => {
+                log(&format!("stopping: {e}"));
+                return ExitCode::FAILURE;
+            }
+        }
+        if once {
+            return ExitCode::SUCCESS;
+        }
+        thread::sleep(poll);
+    }
+}
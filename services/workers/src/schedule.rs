@@ -0,0 +1,141 @@
+//! Due installments tracked by the worker, with their retry state
+
+use std::collections::BTreeMap;
+
+use crate::chain::DueInstallment;
+
+/// Exponential retry delay: `base`, doubled per failed attempt, up to `max`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    pub base_secs: u64,
+    pub max_secs: u64,
+}
+
+impl Backoff {
+    /// Delay before the next attempt once `attempts` have failed
+    pub fn delay(&self, attempts: u32) -> u64 {
+        let doublings = attempts.saturating_sub(1).min(32);
+        self.base_secs.saturating_mul(1u64 << doublings).min(self.max_secs)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Tracked {
+    due: DueInstallment,
+    attempts: u32,
+    next_attempt_at: u64,
+}
+
+/// What happened to an installment after a failed attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retry {
+    At(u64),         // Next attempt time
+    GaveUp(u32),     // Attempts made; left to `declare_default`
+}
+
+pub struct Schedule {
+    entries: BTreeMap<(u64, u32), Tracked>,
+    backoff: Backoff,
+    max_attempts: u32,
+}
+
+impl Schedule {
+    pub fn new(backoff: Backoff, max_attempts: u32) -> Schedule {
+        Schedule { entries: BTreeMap::new(), backoff, max_attempts }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Replace the tracked set with a fresh read of the due index
+    ///
+    /// Installments still listed keep their retry state; the ones gone
+    /// from the index were settled elsewhere.
+    pub fn sync(&mut self, due: Vec<DueInstallment>) {
+        let mut entries = BTreeMap::new();
+        for installment in due {
+            let key = (installment.plan_id, installment.installment_number);
+            let tracked = self.entries.remove(&key).unwrap_or(Tracked {
+                next_attempt_at: installment.due_date,
+                due: installment,
+                attempts: 0,
+            });
+            entries.insert(key, tracked);
+        }
+        self.entries = entries;
+    }
+
+    /// Installments to attempt now, earliest due first
+    pub fn ready(&self, now: u64) -> Vec<DueInstallment> {
+        let mut ready: Vec<&Tracked> = self
+            .entries
+            .values()
+            .filter(|t| t.attempts < self.max_attempts && t.next_attempt_at <= now)
+            .collect();
+        ready.sort_by_key(|t| (t.due.due_date, t.due.plan_id, t.due.installment_number));
+        ready.into_iter().map(|t| t.due.clone()).collect()
+    }
+
+    pub fn remove(&mut self, plan_id: u64, installment_number: u32) {
+        self.entries.remove(&(plan_id, installment_number));
+    }
+
+    /// Drop every installment of a plan (completed, liquidated or archived)
+    pub fn remove_plan(&mut self, plan_id: u64) {
+        self.entries.retain(|(id, _), _| *id != plan_id);
+    }
+
+    /// Push a failed installment back by the backoff delay
+    pub fn failed(&mut self, plan_id: u64, installment_number: u32, now: u64) -> Retry {
+        let Some(tracked) = self.entries.get_mut(&(plan_id, installment_number)) else {
+            return Retry::GaveUp(0);
+        };
+        tracked.attempts += 1;
+        if tracked.attempts >= self.max_attempts {
+            return Retry::GaveUp(tracked.attempts);
+        }
+        tracked.next_attempt_at = now + self.backoff.delay(tracked.attempts);
+        Retry::At(tracked.next_attempt_at)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn due(plan_id: u64, installment_number: u32, due_date: u64) -> DueInstallment {
+        DueInstallment { plan_id, installment_number, amount: 1000, due_date }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let backoff = Backoff { base_secs: 60, max_secs: 300 };
+        assert_eq!(backoff.delay(1), 60);
+        assert_eq!(backoff.delay(2), 120);
+        assert_eq!(backoff.delay(3), 240);
+        assert_eq!(backoff.delay(4), 300);
+        assert_eq!(backoff.delay(80), 300);
+    }
+
+    #[test]
+    fn test_failed_installments_wait_then_give_up() {
+        let mut schedule = Schedule::new(Backoff { base_secs: 60, max_secs: 300 }, 3);
+        schedule.sync(vec![due(1, 1, 1000), due(0, 2, 900), due(2, 1, 5000)]);
+
+        let ready = schedule.ready(1000);
+        assert_eq!(ready, vec![due(0, 2, 900), due(1, 1, 1000)]);
+
+        assert_eq!(schedule.failed(0, 2, 1000), Retry::At(1060));
+        assert_eq!(schedule.ready(1000), vec![due(1, 1, 1000)]);
+        assert_eq!(schedule.ready(1060).len(), 2);
+        assert_eq!(schedule.failed(0, 2, 1060), Retry::At(1180));
+        assert_eq!(schedule.failed(0, 2, 1180), Retry::GaveUp(3));
+        assert_eq!(schedule.ready(10_000).len(), 2);
+
+        // A resync keeps the retry state of installments still due
+        schedule.sync(vec![due(0, 2, 900), due(2, 1, 5000)]);
+        assert_eq!(schedule.len(), 2);
+        assert_eq!(schedule.ready(10_000), vec![due(2, 1, 5000)]);
+    }
+}
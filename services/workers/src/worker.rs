@@ -0,0 +1,293 @@
+//! Collection loop: follow the bridge events, keep the due installments
+//! up to date and collect the ones that are due
+
+use crate::chain::{BridgeEvent, Chain, EventCursor};
+use crate::config::Config;
+use crate::error::Error;
+use crate::schedule::{Backoff, Retry, Schedule};
+
+/// Page size of `get_due_between` (the contract caps it at 50)
+const DUE_PAGE_SIZE: u32 = 50;
+
+/// Outcome of one tick, for logging
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TickReport {
+    pub events: usize,
+    pub resynced: bool,
+    pub collected: usize,
+    pub retried: usize,
+    pub gave_up: usize,
+}
+
+pub struct Worker<C: Chain> {
+    chain: C,
+    config: Config,
+    schedule: Schedule,
+    cursor: Option<EventCursor>,
+    last_seq: Option<u64>,
+    next_resync_at: u64,             // 0 = resync on the next tick
+}
+
+impl<C: Chain> Worker<C> {
+    pub fn new(chain: C, config: Config) -> Worker<C> {
+        let backoff = Backoff { base_secs: config.backoff_secs, max_secs: config.max_backoff_secs };
+        let schedule = Schedule::new(backoff, config.max_attempts);
+        let cursor = config.start_ledger.map(EventCursor::Ledger);
+        Worker { chain, config, schedule, cursor, last_seq: None, next_resync_at: 0 }
+    }
+
+    pub fn tracked(&self) -> usize {
+        self.schedule.len()
+    }
+
+    /// Run one pass at ledger time `now`
+    ///
+    /// Only fatal errors (misconfiguration) are returned; failed
+    /// collections are retried with backoff on later ticks.
+    pub fn tick(&mut self, now: u64) -> Result<TickReport, Error> {
+        let mut report = TickReport::default();
+
+        match self.poll_events() {
+            Ok(count) => report.events = count,
+            Err(e) if e.is_fatal() => return Err(e),
+            Err(e) => log(&format!("event poll failed, resyncing: {e}")),
+        }
+
+        if now >= self.next_resync_at {
+            match self.resync(now) {
+                Ok(()) => report.resynced = true,
+                Err(e) if e.is_fatal() => return Err(e),
+                Err(e) => log(&format!("resync failed: {e}")),
+            }
+        }
+
+        for due in self.schedule.ready(now) {
+            let (plan_id, number) = (due.plan_id, due.installment_number);
+            match self.chain.collect(plan_id, number, !self.config.dry_run) {
+                Ok(source) => {
+                    let verb = if self.config.dry_run { "would collect" } else { "collected" };
+                    log(&format!("{verb} plan {plan_id} installment {number} ({} from {source})", due.amount));
+                    self.schedule.remove(plan_id, number);
+                    report.collected += 1;
+                }
+                Err(e) if e.is_fatal() => return Err(e),
+                Err(e) if e.is_settled() => self.schedule.remove(plan_id, number),
+                Err(e) => match self.schedule.failed(plan_id, number, now) {
+                    Retry::At(at) => {
+                        log(&format!("plan {plan_id} installment {number} failed ({e}), retrying at {at}"));
+                        report.retried += 1;
+                    }
+                    Retry::GaveUp(attempts) => {
+                        log(&format!(
+                            "giving up on plan {plan_id} installment {number} after {attempts} attempts ({e})"
+                        ));
+                        report.gave_up += 1;
+                    }
+                },
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Apply new bridge events; a sequence gap triggers a resync
+    fn poll_events(&mut self) -> Result<usize, Error> {
+        let cursor = match &self.cursor {
+            Some(cursor) => cursor.clone(),
+            None => EventCursor::Ledger(self.chain.latest_ledger()?),
+        };
+        let events = self.chain.events(&cursor)?;
+
+        for event in &events {
+            if let Some(last) = self.last_seq {
+                if event.seq > last + 1 {
+                    log(&format!("missed bridge events {}..{}, resyncing", last + 1, event.seq - 1));
+                    self.next_resync_at = 0;
+                }
+            }
+            self.last_seq = Some(self.last_seq.map_or(event.seq, |last| last.max(event.seq)));
+            self.apply(event);
+        }
+
+        self.cursor = Some(match events.last() {
+            Some(event) => EventCursor::After(event.id.clone()),
+            None => cursor,
+        });
+        Ok(events.len())
+    }
+
+    fn apply(&mut self, event: &BridgeEvent) {
+        match (event.name.as_str(), event.plan_id, event.installment_number) {
+            ("inst_paid" | "inst_fail", Some(plan_id), Some(number)) => self.schedule.remove(plan_id, number),
+            ("plan_done" | "auc_bid" | "archived", Some(plan_id), _) => self.schedule.remove_plan(plan_id),
+            // New or newly collectable work
+            ("plan_new" | "plan_ok" | "unfrozen" | "unpaused", _, _) => self.next_resync_at = 0,
+            _ => {}
+        }
+    }
+
+    /// Reload every installment due up to the lookahead window
+    fn resync(&mut self, now: u64) -> Result<(), Error> {
+        let to_ts = now.saturating_add(self.config.lookahead_secs);
+        let mut due = Vec::new();
+        loop {
+            let page = self.chain.due_between(0, to_ts, due.len() as u32, DUE_PAGE_SIZE)?;
+            let full = page.len() as u32 == DUE_PAGE_SIZE;
+            due.extend(page);
+            if !full {
+                break;
+            }
+        }
+        self.schedule.sync(due);
+        self.next_resync_at = now.saturating_add(self.config.resync_secs);
+        Ok(())
+    }
+}
+
+pub fn log(message: &str) {
+    eprintln!("[redi-worker] {message}");
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::chain::DueInstallment;
+    use crate::error::code;
+
+    #[derive(Default)]
+    struct FakeChain {
+        due: RefCell<Vec<DueInstallment>>,
+        events: RefCell<VecDeque<Vec<BridgeEvent>>>,
+        results: RefCell<VecDeque<Result<String, Error>>>,
+        collects: RefCell<Vec<(u64, u32, bool)>>,
+        due_reads: RefCell<u32>,
+    }
+
+    impl Chain for &FakeChain {
+        fn latest_ledger(&self) -> Result<u32, Error> {
+            Ok(100)
+        }
+
+        fn events(&self, _cursor: &EventCursor) -> Result<Vec<BridgeEvent>, Error> {
+            Ok(self.events.borrow_mut().pop_front().unwrap_or_default())
+        }
+
+        fn due_between(&self, _from: u64, to_ts: u64, cursor: u32, limit: u32) -> Result<Vec<DueInstallment>, Error> {
+            *self.due_reads.borrow_mut() += 1;
+            Ok(self
+                .due
+                .borrow()
+                .iter()
+                .filter(|due| due.due_date <= to_ts)
+                .skip(cursor as usize)
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+
+        fn collect(&self, plan_id: u64, installment_number: u32, send: bool) -> Result<String, Error> {
+            self.collects.borrow_mut().push((plan_id, installment_number, send));
+            self.results.borrow_mut().pop_front().unwrap_or(Ok("0".to_string()))
+        }
+    }
+
+    fn config(dry_run: bool) -> Config {
+        Config {
+            network: "testnet".to_string(),
+            bridge_id: "CBRIDGE".to_string(),
+            source: "worker".to_string(),
+            collector: "GCOLLECTOR".to_string(),
+            stellar_bin: "stellar".to_string(),
+            start_ledger: None,
+            poll_secs: 30,
+            lookahead_secs: 0,
+            resync_secs: 3600,
+            max_attempts: 3,
+            backoff_secs: 60,
+            max_backoff_secs: 300,
+            dry_run,
+            once: false,
+        }
+    }
+
+    fn due(plan_id: u64, installment_number: u32, due_date: u64) -> DueInstallment {
+        DueInstallment { plan_id, installment_number, amount: 1000, due_date }
+    }
+
+    fn event(name: &str, seq: u64, plan_id: u64, installment_number: Option<u32>) -> BridgeEvent {
+        BridgeEvent {
+            id: format!("{seq}"),
+            name: name.to_string(),
+            seq,
+            plan_id: Some(plan_id),
+            installment_number,
+        }
+    }
+
+    #[test]
+    fn test_retries_with_backoff_until_collected() {
+        let chain = FakeChain::default();
+        chain.due.borrow_mut().push(due(1, 1, 1000));
+        chain.results.borrow_mut().extend([Err(Error::Contract(11)), Err(Error::Cli("timeout".to_string()))]);
+        let mut worker = Worker::new(&chain, config(false));
+
+        assert_eq!(worker.tick(1000).unwrap().retried, 1);
+        assert_eq!(worker.tick(1030).unwrap().collected, 0); // Backing off until 1060
+        assert_eq!(worker.tick(1060).unwrap().retried, 1);
+        assert_eq!(worker.tick(1180).unwrap().collected, 1);
+        assert_eq!(worker.tracked(), 0);
+        assert_eq!(*chain.collects.borrow(), vec![(1, 1, true), (1, 1, true), (1, 1, true)]);
+    }
+
+    #[test]
+    fn test_dry_run_only_simulates() {
+        let chain = FakeChain::default();
+        chain.due.borrow_mut().extend([due(1, 1, 1000), due(2, 1, 900)]);
+        let mut worker = Worker::new(&chain, config(true));
+
+        assert_eq!(worker.tick(1000).unwrap().collected, 2);
+        assert_eq!(*chain.collects.borrow(), vec![(2, 1, false), (1, 1, false)]);
+    }
+
+    #[test]
+    fn test_events_drop_settled_work_and_gaps_resync() {
+        let chain = FakeChain::default();
+        chain.due.borrow_mut().extend([due(1, 1, 1000), due(2, 1, 1000), due(3, 1, 1000)]);
+        chain.results.borrow_mut().extend(vec![Err(Error::Contract(11)); 3]);
+        let mut worker = Worker::new(&chain, config(false));
+        worker.tick(1000).unwrap();
+        assert_eq!(worker.tracked(), 3);
+
+        // The user paid plan 1, plan 2 was liquidated
+        chain.events.borrow_mut().push_back(vec![
+            event("inst_paid", 5, 1, Some(1)),
+            event("auc_bid", 6, 2, None),
+        ]);
+        let report = worker.tick(1010).unwrap();
+        assert_eq!((report.events, report.resynced), (2, false));
+        assert_eq!(worker.tracked(), 1);
+
+        // Event 7, a new plan, was missed: reloading the index picks it up
+        chain.due.borrow_mut().retain(|due| due.plan_id == 3);
+        chain.due.borrow_mut().push(due(4, 1, 1000));
+        chain.events.borrow_mut().push_back(vec![event("status", 8, 3, None)]);
+        let report = worker.tick(1020).unwrap();
+        assert!(report.resynced);
+        assert_eq!(report.collected, 1);
+        assert_eq!(chain.collects.borrow().last(), Some(&(4, 1, true)));
+        assert_eq!(worker.tracked(), 1);
+    }
+
+    #[test]
+    fn test_stops_when_not_a_collector() {
+        let chain = FakeChain::default();
+        chain.due.borrow_mut().push(due(1, 1, 1000));
+        chain.results.borrow_mut().push_back(Err(Error::Contract(code::NOT_COLLECTOR)));
+        let mut worker = Worker::new(&chain, config(false));
+
+        assert_eq!(worker.tick(1000), Err(Error::Contract(code::NOT_COLLECTOR)));
+    }
+}
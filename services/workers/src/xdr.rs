@@ -0,0 +1,126 @@
+//! Decoding of the `ScVal` XDR the bridge publishes in its events
+//!
+//! RPC returns event topics and data as base64 XDR. Decoding goes through
+//! the `stellar-xdr` definitions, so every `ScVal` type is understood; the
+//! helpers below only read the few shapes the worker acts on.
+
+use stellar_xdr::curr::{Limits, ReadXdr};
+
+pub use stellar_xdr::curr::ScVal;
+
+use crate::error::Error;
+
+/// Decode a base64 XDR value
+pub fn decode(encoded: &str) -> Result<ScVal, Error> {
+    ScVal::from_xdr_base64(encoded.trim(), Limits::none()).map_err(|e| Error::Decode(format!("ScVal: {e}")))
+}
+
+/// Accessors for the values found in bridge events
+pub trait EventValue {
+    fn as_symbol(&self) -> Option<&str>;
+
+    /// Unsigned integer of up to 64 bits, timepoints and durations included
+    fn as_u64(&self) -> Option<u64>;
+
+    /// Field of a `contracttype` struct, encoded as a map keyed by symbols
+    fn field(&self, name: &str) -> Option<&ScVal>;
+}
+
+impl EventValue for ScVal {
+    fn as_symbol(&self) -> Option<&str> {
+        match self {
+            ScVal::Symbol(symbol) => std::str::from_utf8(symbol.as_vec()).ok(),
+            _ => None,
+        }
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            ScVal::U64(value) => Some(*value),
+            ScVal::U32(value) => Some(*value as u64),
+            ScVal::Timepoint(value) => Some(value.0),
+            ScVal::Duration(value) => Some(value.0),
+            _ => None,
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<&ScVal> {
+        match self {
+            ScVal::Map(Some(entries)) => entries
+                .iter()
+                .find(|entry| entry.key.as_symbol() == Some(name))
+                .map(|entry| &entry.val),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use base64::Engine;
+    use stellar_xdr::curr::{
+        AccountId, Int128Parts, Int256Parts, PublicKey, ScAddress, ScMapEntry, ScSymbol, Uint256, WriteXdr,
+    };
+
+    /// Base64 XDR of a fixture value
+    pub fn encode(value: &ScVal) -> String {
+        value.to_xdr_base64(Limits::none()).unwrap()
+    }
+
+    pub fn symbol(s: &str) -> ScVal {
+        ScVal::Symbol(ScSymbol(s.try_into().unwrap()))
+    }
+
+    pub fn vec(items: Vec<ScVal>) -> ScVal {
+        ScVal::Vec(Some(items.try_into().unwrap()))
+    }
+
+    pub fn map(entries: Vec<(ScVal, ScVal)>) -> ScVal {
+        let entries: Vec<ScMapEntry> = entries.into_iter().map(|(key, val)| ScMapEntry { key, val }).collect();
+        ScVal::Map(Some(entries.try_into().unwrap()))
+    }
+
+    /// Account address whose key is `byte` repeated
+    pub fn account(byte: u8) -> ScVal {
+        ScVal::Address(ScAddress::Account(AccountId(PublicKey::PublicKeyTypeEd25519(Uint256([byte; 32])))))
+    }
+
+    #[test]
+    fn test_decodes_plan_event_data() {
+        // (seq, InstallmentPaid { plan_id, installment_number, amount, .. })
+        let data = vec(vec![
+            ScVal::U64(42),
+            map(vec![
+                (symbol("amount"), ScVal::I128(Int128Parts { hi: -1, lo: u64::MAX - 999 })),
+                (symbol("collector"), account(9)),
+                (symbol("installment_number"), ScVal::U32(2)),
+                (symbol("plan_id"), ScVal::U64(7)),
+            ]),
+        ]);
+        let decoded = decode(&encode(&data)).unwrap();
+        assert_eq!(decoded, data);
+
+        let ScVal::Vec(Some(items)) = decoded else { panic!("not a vec") };
+        assert_eq!(items[0].as_u64(), Some(42));
+        assert_eq!(items[1].field("plan_id").and_then(EventValue::as_u64), Some(7));
+        assert_eq!(items[1].field("installment_number").and_then(EventValue::as_u64), Some(2));
+        assert_eq!(items[1].field("missing"), None);
+    }
+
+    #[test]
+    fn test_decodes_types_the_bridge_does_not_emit() {
+        let value = vec(vec![ScVal::I256(Int256Parts { hi_hi: 0, hi_lo: 1, lo_hi: 2, lo_lo: 3 }), ScVal::Void]);
+        assert_eq!(decode(&encode(&value)).unwrap(), value);
+    }
+
+    #[test]
+    fn test_rejects_truncated_input() {
+        let full = base64::engine::general_purpose::STANDARD
+            .decode(encode(&symbol("inst_paid")))
+            .unwrap();
+        let truncated = base64::engine::general_purpose::STANDARD.encode(&full[..full.len() - 4]);
+        assert!(decode(&truncated).is_err());
+        assert!(decode("not base64!").is_err());
+    }
+}